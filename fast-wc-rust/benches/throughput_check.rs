@@ -0,0 +1,118 @@
+// Throughput regression check against stored per-machine baselines.
+//
+//   cargo bench --bench throughput_check                  # measure and print
+//   cargo bench --bench throughput_check -- --save        # record baselines
+//   cargo bench --bench throughput_check -- --check [--threshold 10]
+//
+// --check exits with status 1 if any scenario is more than `threshold`
+// percent slower than its baseline for this machine profile (see
+// `baseline::machine_profile`).
+use fast_wc_rust::baseline::{self, Baselines};
+use fast_wc_rust::testing::{CorpusSpec, SizeDistribution};
+use fast_wc_rust::{FastWordCounter, ProfileReport};
+use std::path::Path;
+use tempfile::TempDir;
+
+// Timed runs per scenario; the fastest is kept, as the least disturbed
+const RUNS: usize = 5;
+// Default allowed slowdown, in percent
+const DEFAULT_THRESHOLD: f64 = 10.0;
+
+fn scenarios() -> Vec<(&'static str, CorpusSpec)> {
+    vec![
+        (
+            "many_small_files",
+            CorpusSpec {
+                files: 2000,
+                file_size: SizeDistribution::Fixed(4096),
+                ..CorpusSpec::default()
+            },
+        ),
+        (
+            "large_vocab_zipf",
+            CorpusSpec {
+                files: 200,
+                file_size: SizeDistribution::Pareto {
+                    min: 16 * 1024,
+                    alpha: 1.2,
+                },
+                vocab: 200_000,
+                zipf: 1.1,
+                ..CorpusSpec::default()
+            },
+        ),
+    ]
+}
+
+fn measure(dir: &Path) -> ProfileReport {
+    let counter = FastWordCounter::builder()
+        .show_progress(false)
+        .print_results(false)
+        .build()
+        .unwrap();
+    // Warm the page cache
+    counter.profile_run(dir).unwrap();
+    (0..RUNS)
+        .map(|_| counter.profile_run(dir).unwrap())
+        .max_by(|a, b| a.bytes_per_sec().total_cmp(&b.bytes_per_sec()))
+        .unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let check = args.iter().any(|a| a == "--check");
+    let save = args.iter().any(|a| a == "--save");
+    let threshold = args
+        .iter()
+        .position(|a| a == "--threshold")
+        .and_then(|i| args.get(i + 1))
+        .map(|t| t.parse::<f64>().expect("--threshold takes a percentage"))
+        .unwrap_or(DEFAULT_THRESHOLD);
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/baselines.json");
+    let mut baselines = Baselines::load(&path).unwrap();
+    let profile = baseline::machine_profile();
+    println!("machine profile: {}", profile);
+
+    let mut regressions = 0;
+    for (name, spec) in scenarios() {
+        let dir = TempDir::new().unwrap();
+        spec.generate(dir.path()).unwrap();
+        let report = measure(dir.path());
+        let current = report.bytes_per_sec();
+
+        match baselines.get(&profile, name) {
+            Some(base) => {
+                let change = (current / base - 1.0) * 100.0;
+                println!(
+                    "{:<20} {:>10.1} MiB/s  baseline {:>10.1} MiB/s  {:+.1}%",
+                    name,
+                    current / (1024.0 * 1024.0),
+                    base / (1024.0 * 1024.0),
+                    change
+                );
+                if check && baseline::regression(base, current, threshold / 100.0).is_some() {
+                    println!("  REGRESSION: more than {}% slower", threshold);
+                    regressions += 1;
+                }
+            }
+            None => println!(
+                "{:<20} {:>10.1} MiB/s  (no baseline)",
+                name,
+                current / (1024.0 * 1024.0)
+            ),
+        }
+
+        if save {
+            baselines.record(&profile, name, &report);
+        }
+    }
+
+    if save {
+        baselines.save().unwrap();
+        println!("saved baselines to {}", path.display());
+    }
+    if regressions > 0 {
+        std::process::exit(1);
+    }
+}
@@ -1,12 +1,14 @@
+use ahash::AHashMap;
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
-use fast_wc_rust::{Config, FastWordCounter};
+use fast_wc_rust::testing::{CorpusSpec, SizeDistribution, create_test_files};
+use fast_wc_rust::{FastWordCounter, merge};
 use std::fs;
 use std::hint::black_box;
-use std::io::Write;
-use std::process::Command;
+use std::sync::atomic::AtomicU64;
+// use std::process::Command;
 use tempfile::TempDir;
 
-const CPP_BINARY: &str = "../competitors/fast-cpp/fast-wc";
+// const CPP_BINARY: &str = "../competitors/fast-cpp/fast-wc";
 
 // fn run_cpp_benchmark(temp_dir: &TempDir, num_threads: usize, parallel_merge: bool) -> bool {
 //     // DISABLED: Current breaks, for some reason, when threads >= 4
@@ -42,51 +44,6 @@ const CPP_BINARY: &str = "../competitors/fast-cpp/fast-wc";
 //     }
 // }
 
-fn create_test_files(dir: &TempDir, num_files: usize, file_size: usize) -> Vec<String> {
-    let words = [
-        "int", "main", "void", "return", "printf", "char", "const", "struct", "typedef", "static",
-        "extern", "inline", "register", "volatile", "sizeof", "malloc", "free", "memcpy", "strlen",
-        "strcpy", "strcmp", "file", "buffer", "pointer", "array", "function", "variable", "hello",
-        "world", "test", "example", "sample", "data", "string",
-    ];
-
-    let mut file_paths = Vec::new();
-
-    for i in 0..num_files {
-        let file_path = dir.path().join(format!("test_{}.c", i));
-        let mut file = fs::File::create(&file_path).unwrap();
-
-        let mut content = String::with_capacity(file_size);
-        let mut size = 0;
-
-        while size < file_size {
-            for word in &words {
-                content.push_str(word);
-                content.push(' ');
-                size += word.len() + 1;
-
-                if size >= file_size {
-                    break;
-                }
-
-                if size % 100 == 0 {
-                    content.push('\n');
-                    size += 1;
-                }
-                if size % 50 == 0 {
-                    content.push_str("();");
-                    size += 3;
-                }
-            }
-        }
-
-        file.write_all(content.as_bytes()).unwrap();
-        file_paths.push(file_path.to_string_lossy().to_string());
-    }
-
-    file_paths
-}
-
 fn bench_word_counting(c: &mut Criterion) {
     let temp_dir = TempDir::new().unwrap();
 
@@ -105,7 +62,7 @@ fn bench_word_counting(c: &mut Criterion) {
         let total_size = num_files * file_size;
         group.throughput(Throughput::Bytes(total_size as u64));
 
-        create_test_files(&temp_dir, num_files, file_size);
+        create_test_files(temp_dir.path(), num_files, file_size);
 
         let mut thread_counts = vec![1, 2, 4, 8];
         let num_cpus = num_cpus::get();
@@ -132,13 +89,14 @@ fn bench_word_counting(c: &mut Criterion) {
                         ),
                         &(num_files, file_size),
                         |b, _| {
-                            let config = Config {
-                                num_threads,
-                                use_mmap: true,
-                                silent: true,
-                                parallel_merge,
-                            };
-                            let counter = FastWordCounter::new(config);
+                            let counter = FastWordCounter::builder()
+                                .num_threads(num_threads)
+                                .use_mmap(true)
+                                .show_progress(false)
+                                .print_results(false)
+                                .parallel_merge(parallel_merge)
+                                .build()
+                                .unwrap();
 
                             b.iter(|| black_box(counter.count_directory(temp_dir.path()).unwrap()));
                         },
@@ -151,13 +109,14 @@ fn bench_word_counting(c: &mut Criterion) {
                         ),
                         &(num_files, file_size),
                         |b, _| {
-                            let config = Config {
-                                num_threads,
-                                use_mmap: false,
-                                silent: true,
-                                parallel_merge,
-                            };
-                            let counter = FastWordCounter::new(config);
+                            let counter = FastWordCounter::builder()
+                                .num_threads(num_threads)
+                                .use_mmap(false)
+                                .show_progress(false)
+                                .print_results(false)
+                                .parallel_merge(parallel_merge)
+                                .build()
+                                .unwrap();
 
                             b.iter(|| black_box(counter.count_directory(temp_dir.path()).unwrap()));
                         },
@@ -197,7 +156,7 @@ fn bench_word_counting(c: &mut Criterion) {
 fn bench_rust_vs_cpp(c: &mut Criterion) {
     let temp_dir = TempDir::new().unwrap();
 
-    let _ = create_test_files(&temp_dir, 50, 10240); // 50 files, 10KB each
+    let _ = create_test_files(temp_dir.path(), 50, 10240); // 50 files, 10KB each
     let total_size = 50 * 10240;
 
     let mut group = c.benchmark_group("rust_vs_cpp");
@@ -205,13 +164,14 @@ fn bench_rust_vs_cpp(c: &mut Criterion) {
 
     // Benchmark Rust implementation (optimal config)
     group.bench_function("rust_optimal", |b| {
-        let config = Config {
-            num_threads: num_cpus::get(),
-            use_mmap: true,
-            silent: true,
-            parallel_merge: true,
-        };
-        let counter = FastWordCounter::new(config);
+        let counter = FastWordCounter::builder()
+            .num_threads(num_cpus::get())
+            .use_mmap(true)
+            .show_progress(false)
+            .print_results(false)
+            .parallel_merge(true)
+            .build()
+            .unwrap();
 
         b.iter(|| black_box(counter.count_directory(temp_dir.path()).unwrap()));
     });
@@ -228,5 +188,160 @@ fn bench_rust_vs_cpp(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_word_counting, bench_rust_vs_cpp);
+fn bench_prefault(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    create_test_files(temp_dir.path(), 20, 1024 * 1024); // 20 files, 1MB each
+
+    let mut group = c.benchmark_group("prefault");
+    group.throughput(Throughput::Bytes(20 * 1024 * 1024));
+
+    for prefault in [false, true] {
+        group.bench_function(if prefault { "populate" } else { "on_demand" }, |b| {
+            let counter = FastWordCounter::builder()
+                .use_mmap(true)
+                .show_progress(false)
+                .print_results(false)
+                .prefault(prefault)
+                .build()
+                .unwrap();
+
+            b.iter(|| black_box(counter.count_directory(temp_dir.path()).unwrap()));
+        });
+    }
+
+    group.finish();
+}
+
+// A Zipf-distributed vocabulary over heavy-tailed file sizes, closer to a
+// real source tree than the fixed word cycle above
+fn bench_zipf_corpus(c: &mut Criterion) {
+    let temp_dir = TempDir::new().unwrap();
+    let paths = CorpusSpec {
+        files: 200,
+        file_size: SizeDistribution::Pareto {
+            min: 4096,
+            alpha: 1.2,
+        },
+        vocab: 100_000,
+        zipf: 1.1,
+        ..CorpusSpec::default()
+    }
+    .generate(temp_dir.path())
+    .unwrap();
+    let total_size: u64 = paths
+        .iter()
+        .map(|path| std::fs::metadata(path).unwrap().len())
+        .sum();
+
+    let mut group = c.benchmark_group("zipf_corpus");
+    group.throughput(Throughput::Bytes(total_size));
+    group.bench_function("count_directory", |b| {
+        let counter = FastWordCounter::builder()
+            .show_progress(false)
+            .print_results(false)
+            .build()
+            .unwrap();
+
+        b.iter(|| black_box(counter.count_directory(temp_dir.path()).unwrap()));
+    });
+    group.finish();
+}
+
+#[cfg(feature = "huge-pages")]
+#[global_allocator]
+static GLOBAL: fast_wc_rust::HugePageAlloc = fast_wc_rust::HugePageAlloc;
+
+// Insert-then-probe a vocabulary large enough that the map's table spans
+// hundreds of MiB, with and without huge-page advice. Run with
+// `cargo bench --features huge-pages -- huge_pages`
+#[cfg(feature = "huge-pages")]
+fn bench_huge_pages(c: &mut Criterion) {
+    use fast_wc_rust::HugePageAlloc;
+
+    let words: Vec<String> = (0..2_000_000).map(|i| format!("ident_{}", i)).collect();
+    let mut group = c.benchmark_group("huge_pages");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(words.len() as u64));
+
+    for enabled in [false, true] {
+        group.bench_function(if enabled { "madvise" } else { "base_pages" }, |b| {
+            HugePageAlloc::set_enabled(enabled);
+            b.iter(|| {
+                let mut counts: AHashMap<&str, u64> = AHashMap::new();
+                for word in &words {
+                    *counts.entry(word.as_str()).or_insert(0) += 1;
+                }
+                for word in words.iter().rev() {
+                    *counts.get_mut(word.as_str()).unwrap() += 1;
+                }
+                black_box(counts.len())
+            });
+        });
+    }
+
+    HugePageAlloc::set_enabled(true);
+    group.finish();
+}
+
+#[cfg(not(feature = "huge-pages"))]
+fn bench_huge_pages(_c: &mut Criterion) {}
+
+// Build `workers` maps of `words` entries each, half shared across workers
+fn create_worker_maps(workers: usize, words: usize) -> Vec<AHashMap<String, u64>> {
+    (0..workers)
+        .map(|w| {
+            (0..words)
+                .map(|i| {
+                    let word = if i % 2 == 0 {
+                        format!("shared_{}", i)
+                    } else {
+                        format!("w{}_{}", w, i)
+                    };
+                    (word, (i % 7) as u64 + 1)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn bench_merge_strategies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge_strategies");
+    let saturated = AtomicU64::new(0);
+
+    for workers in [2, 4, 8, 16] {
+        let maps = create_worker_maps(workers, 50_000);
+        let input = format!("{}workers_50000words", workers);
+
+        group.bench_with_input(BenchmarkId::new("sequential", &input), &maps, |b, maps| {
+            b.iter(|| black_box(merge::merge_sequential(maps.clone(), &saturated)))
+        });
+        group.bench_with_input(BenchmarkId::new("reduce", &input), &maps, |b, maps| {
+            b.iter(|| black_box(merge::merge_reduce(maps.clone(), &saturated)))
+        });
+        group.bench_with_input(BenchmarkId::new("partitioned", &input), &maps, |b, maps| {
+            b.iter(|| {
+                black_box(merge::merge_partitioned(
+                    maps.clone(),
+                    num_cpus::get() * 4,
+                    &saturated,
+                ))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("arena", &input), &maps, |b, maps| {
+            b.iter(|| black_box(merge::merge_arena(maps.clone(), &saturated)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_word_counting,
+    bench_rust_vs_cpp,
+    bench_merge_strategies,
+    bench_prefault,
+    bench_zipf_corpus,
+    bench_huge_pages
+);
 criterion_main!(benches);
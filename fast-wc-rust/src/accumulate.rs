@@ -0,0 +1,107 @@
+use crate::seen::SeenSpan;
+use std::path::Path;
+use std::sync::Arc;
+
+// Per-word value of a map merged by the `merge` strategies. Word counts are
+// plain `u64`s; per-word metadata (`WordMeta`) merges the same way, so a
+// feature needing more than a count adds a field to `WordMeta` instead of
+// keeping a map of its own. Maps combine in whatever order workers finish,
+// so merges must be associative and commutative.
+pub trait Accumulate: Send {
+    // Fold in another map's value for the same word. Returns true if a count
+    // saturated at `u64::MAX` doing so.
+    fn merge(&mut self, other: Self) -> bool;
+}
+
+impl Accumulate for u64 {
+    fn merge(&mut self, other: Self) -> bool {
+        let (sum, overflowed) = self.overflowing_add(other);
+        *self = if overflowed { u64::MAX } else { sum };
+        overflowed
+    }
+}
+
+// An optional layer: None until some file supplies a value
+impl<T: Accumulate> Accumulate for Option<T> {
+    fn merge(&mut self, other: Self) -> bool {
+        match (self.as_mut(), other) {
+            (Some(value), Some(other)) => value.merge(other),
+            (None, other) => {
+                *self = other;
+                false
+            }
+            (Some(_), None) => false,
+        }
+    }
+}
+
+// Path and token position of a word's first occurrence; the earliest path
+// wins, then the earliest position within it
+#[derive(Debug, Clone)]
+pub(crate) struct FirstSeen {
+    pub path: Arc<Path>,
+    pub position: u64,
+}
+
+impl Accumulate for FirstSeen {
+    fn merge(&mut self, other: Self) -> bool {
+        if (&*other.path, other.position) < (&*self.path, self.position) {
+            *self = other;
+        }
+        false
+    }
+}
+
+// Everything tracked per word besides its count, filled by one extra
+// tokenizing pass per file for the layers the configuration asks for
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WordMeta {
+    // Number of files containing the word, for `SortOrder::FileFrequency`
+    pub files: u64,
+    // For `TieBreak::FirstSeen` and `TieBreak::FileOrder`
+    pub first: Option<FirstSeen>,
+    // For `Config::seen_order`
+    pub span: Option<SeenSpan>,
+}
+
+impl Accumulate for WordMeta {
+    fn merge(&mut self, other: Self) -> bool {
+        let saturated = self.files.merge(other.files);
+        self.first.merge(other.first);
+        self.span.merge(other.span);
+        saturated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_merge_in_any_order() {
+        let mut count = u64::MAX - 1;
+        assert!(!count.merge(1));
+        assert!(count.merge(1));
+        assert_eq!(count, u64::MAX);
+
+        let at = |path: &str, position| WordMeta {
+            files: 1,
+            first: Some(FirstSeen {
+                path: Arc::from(Path::new(path)),
+                position,
+            }),
+            span: None,
+        };
+        let mut forward = at("a.c", 7);
+        forward.merge(at("b.c", 0));
+        forward.merge(WordMeta::default());
+        let mut backward = at("b.c", 0);
+        backward.merge(at("a.c", 7));
+
+        for meta in [forward, backward] {
+            assert_eq!(meta.files, 2);
+            let first = meta.first.unwrap();
+            assert_eq!((&*first.path, first.position), (Path::new("a.c"), 7));
+        }
+    }
+}
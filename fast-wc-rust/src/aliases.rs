@@ -0,0 +1,91 @@
+use ahash::AHashMap;
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+// Read an alias file: TOML mapping each canonical word to the alias (or
+// list of aliases) counted as it, e.g.
+//
+//     uint32_t = ["u32", "UINT32"]
+//     nullptr = "NULL"
+//
+// Returns alias -> canonical
+pub fn load_aliases(path: &Path) -> Result<AHashMap<String, String>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read aliases file {}", path.display()))?;
+    parse_aliases(&text).with_context(|| format!("Invalid aliases file {}", path.display()))
+}
+
+fn parse_aliases(text: &str) -> Result<AHashMap<String, String>> {
+    let table: toml::Table = text.parse()?;
+    let mut aliases = AHashMap::new();
+    for (canonical, value) in table {
+        let names: Vec<&str> = match &value {
+            toml::Value::String(alias) => vec![alias.as_str()],
+            toml::Value::Array(items) => items
+                .iter()
+                .map(|item| {
+                    item.as_str()
+                        .with_context(|| format!("aliases of '{}' must be strings", canonical))
+                })
+                .collect::<Result<_>>()?,
+            _ => bail!(
+                "'{}' must map to a string or an array of strings",
+                canonical
+            ),
+        };
+        for alias in names {
+            if let Some(previous) = aliases.insert(alias.to_string(), canonical.clone())
+                && previous != canonical
+            {
+                bail!(
+                    "'{}' is an alias of both '{}' and '{}'",
+                    alias,
+                    previous,
+                    canonical
+                );
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+// Read an `[expansions]` table from the config file, mapping abbreviations
+// to their expansion (`cfg = "config"`). Keys are lowercased to match the
+// lowercased sub-tokens they apply to.
+pub fn parse_expansions(table: &toml::Table) -> Result<AHashMap<String, String>> {
+    table
+        .iter()
+        .map(|(abbreviation, value)| match value.as_str() {
+            Some(expansion) if !expansion.is_empty() => {
+                Ok((abbreviation.to_lowercase(), expansion.to_string()))
+            }
+            _ => bail!("expansion of '{}' must be a non-empty string", abbreviation),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_aliases() -> Result<()> {
+        let aliases = parse_aliases("uint32_t = [\"u32\", \"UINT32\"]\nnullptr = \"NULL\"\n")?;
+        assert_eq!(aliases.get("u32").map(String::as_str), Some("uint32_t"));
+        assert_eq!(aliases.get("NULL").map(String::as_str), Some("nullptr"));
+        assert_eq!(aliases.len(), 3);
+
+        assert!(parse_aliases("a = \"x\"\nb = \"x\"\n").is_err());
+        assert!(parse_aliases("a = 1\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_expansions() -> Result<()> {
+        let expansions = parse_expansions(&"cfg = \"config\"\nNum = \"number\"\n".parse()?)?;
+        assert_eq!(expansions.get("cfg").map(String::as_str), Some("config"));
+        assert_eq!(expansions.get("num").map(String::as_str), Some("number"));
+        assert!(parse_expansions(&"cfg = [\"config\"]\n".parse()?).is_err());
+        Ok(())
+    }
+}
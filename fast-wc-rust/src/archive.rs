@@ -0,0 +1,152 @@
+// Source files read straight out of .tar (optionally gzip- or
+// zstd-compressed) and .zip archives, so release tarballs can be counted
+// without extracting them. Members are filtered by the configured
+// extensions and reported under `<archive path>/<member path>`.
+use crate::Config;
+use crate::discovery::has_extension;
+use crate::error::{FileError, FileErrorKind};
+use crate::input;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_SUFFIXES: [&str; 5] = [".tar", ".tar.gz", ".tgz", ".tar.zst", ".zip"];
+
+// Whether `path` names an archive this module can read
+pub fn is_archive(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|name| ARCHIVE_SUFFIXES.iter().any(|s| name.ends_with(s)))
+}
+
+// Call `f` with the path and contents of every regular member of the
+// archive at `path` that has one of the configured extensions. With
+// `decompress`, compressed members (`foo.c.gz`) are inflated too.
+pub(crate) fn for_each_member<F>(path: &Path, config: &Config, mut f: F) -> Result<(), FileError>
+where
+    F: FnMut(&Path, &[u8]),
+{
+    let data = input::load(path, config)?;
+    // A .tar.gz is inflated by `load` unless decompression is off
+    let data = match input::decompress(&data) {
+        Some(inflated) => inflated.map_err(|e| corrupt(path, e))?,
+        None => data.to_vec(),
+    };
+
+    let mut emit = |name: PathBuf, contents: Vec<u8>| {
+        if !has_extension(&name, config) {
+            return;
+        }
+        let contents = match input::decompress(&contents) {
+            Some(Ok(inflated)) if config.decompress => inflated,
+            _ => contents,
+        };
+        f(&path.join(name), &contents);
+    };
+
+    if data.starts_with(b"PK") {
+        let mut zip = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| corrupt(path, e))?;
+        for i in 0..zip.len() {
+            let mut member = zip.by_index(i).map_err(|e| corrupt(path, e))?;
+            let Some(name) = member.enclosed_name().filter(|_| member.is_file()) else {
+                continue;
+            };
+            let mut contents = Vec::with_capacity(member.size() as usize);
+            member
+                .read_to_end(&mut contents)
+                .map_err(|e| corrupt(path, e))?;
+            emit(name, contents);
+        }
+    } else {
+        let mut tar = tar::Archive::new(data.as_slice());
+        for entry in tar.entries().map_err(|e| corrupt(path, e))? {
+            let mut entry = entry.map_err(|e| corrupt(path, e))?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry.path().map_err(|e| corrupt(path, e))?.into_owned();
+            let mut contents = Vec::with_capacity(entry.size() as usize);
+            entry
+                .read_to_end(&mut contents)
+                .map_err(|e| corrupt(path, e))?;
+            emit(name, contents);
+        }
+    }
+    Ok(())
+}
+
+fn corrupt(path: &Path, e: impl std::fmt::Display) -> FileError {
+    FileError::new(
+        path,
+        FileErrorKind::DecodeFailed,
+        format!("unreadable archive: {}", e),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    // Write a tar.gz and a zip holding the same members
+    fn write_archives(dir: &Path) -> anyhow::Result<()> {
+        let members: [(&str, &[u8]); 3] = [
+            ("pkg/src/main.c", b"int main(void) { return 0; }"),
+            ("pkg/include/util.h", b"int util;"),
+            ("pkg/README", b"not counted"),
+        ];
+
+        let gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(dir.join("pkg-1.0.tar.gz"))?,
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        for (name, contents) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents)?;
+        }
+        tar.into_inner()?.finish()?;
+
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(dir.join("pkg-1.0.zip"))?);
+        for (name, contents) in members {
+            zip.start_file(name, zip::write::SimpleFileOptions::default())?;
+            zip.write_all(contents)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_members_are_read_and_filtered() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        write_archives(dir.path())?;
+        std::fs::write(dir.path().join("broken.zip"), b"PK garbage")?;
+
+        for name in ["pkg-1.0.tar.gz", "pkg-1.0.zip"] {
+            let archive = dir.path().join(name);
+            assert!(is_archive(&archive));
+            let mut members = Vec::new();
+            for_each_member(&archive, &Config::default(), |path, data| {
+                members.push((path.to_path_buf(), data.len()));
+            })?;
+            assert_eq!(
+                members,
+                [
+                    (archive.join("pkg/src/main.c"), 28),
+                    (archive.join("pkg/include/util.h"), 9)
+                ]
+            );
+        }
+
+        let broken = for_each_member(
+            &dir.path().join("broken.zip"),
+            &Config::default(),
+            |_, _| {},
+        );
+        assert!(broken.is_err_and(|e| e.kind == FileErrorKind::DecodeFailed));
+        assert!(!is_archive(Path::new("notes.tar.txt")));
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use crate::error::UnsupportedVersion;
+use anyhow::Result;
+use std::path::Path;
+
+// Format version of results files written with `--output`. Bump it whenever
+// their layout changes, and teach `check_header` to read the old one or to
+// refuse it.
+pub const RESULTS_VERSION: u32 = 1;
+
+// Format version of per-file count caches written with `--cache`
+pub const CACHE_VERSION: u32 = 1;
+
+// Format version of binary count snapshots written to `--output *.bin`
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// Artifact kinds, as named in headers
+pub const RESULTS: &str = "results";
+pub const CACHE: &str = "cache";
+pub const SNAPSHOT: &str = "snapshot";
+
+const HEADER_PREFIX: &str = "# fast-wc-rust ";
+
+// First line of a persisted artifact, e.g. "# fast-wc-rust results v1"
+pub fn header(kind: &str, version: u32) -> String {
+    format!("{}{} v{}", HEADER_PREFIX, kind, version)
+}
+
+// The version in a `header` line for `kind`, or None if `line` is not one
+// (artifacts written before headers existed count as version 0)
+pub fn parse_header(line: &str, kind: &str) -> Option<u32> {
+    line.strip_prefix(HEADER_PREFIX)?
+        .strip_prefix(kind)?
+        .strip_prefix(" v")?
+        .trim_end()
+        .parse()
+        .ok()
+}
+
+// Check the first line of the artifact at `path` and return the format
+// version to read it as. Versions newer than `supported` are refused with
+// `UnsupportedVersion`; older ones are left to the caller to migrate.
+pub fn check_header(path: &Path, first_line: &str, kind: &str, supported: u32) -> Result<u32> {
+    let found = parse_header(first_line, kind).unwrap_or(0);
+    if found > supported {
+        return Err(UnsupportedVersion {
+            path: path.to_path_buf(),
+            kind: kind.to_string(),
+            found,
+            supported,
+        }
+        .into());
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip_and_refusal() {
+        let line = header(RESULTS, RESULTS_VERSION);
+        assert_eq!(parse_header(&line, RESULTS), Some(RESULTS_VERSION));
+        assert_eq!(parse_header("   int |   3", RESULTS), None);
+
+        let path = Path::new("counts.txt");
+        assert_eq!(check_header(path, "   int |   3", RESULTS, 1).unwrap(), 0);
+        let err = check_header(path, &header(RESULTS, 9), RESULTS, 1).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnsupportedVersion>().unwrap().found, 9);
+    }
+}
@@ -0,0 +1,112 @@
+// Stored throughput baselines for the regression check bench
+// (`cargo bench --bench throughput_check -- --check`). Baselines are kept
+// per machine profile, since numbers from different hardware don't compare.
+use crate::ProfileReport;
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+
+// Environment variable overriding the detected machine profile, e.g. to
+// share baselines between identical CI runners
+pub const PROFILE_ENV: &str = "FAST_WC_BENCH_PROFILE";
+
+// Name of this machine's baseline set: `$FAST_WC_BENCH_PROFILE`, or
+// `<os>-<arch>-<cpus>cpu`
+pub fn machine_profile() -> String {
+    std::env::var(PROFILE_ENV).unwrap_or_else(|_| {
+        format!(
+            "{}-{}-{}cpu",
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            num_cpus::get()
+        )
+    })
+}
+
+// Baselines file: `{ profile: { scenario: <ProfileReport::to_json> } }`
+#[derive(Debug, Clone)]
+pub struct Baselines {
+    path: PathBuf,
+    profiles: Map<String, Value>,
+}
+
+impl Baselines {
+    // Load baselines from `path`; a missing file is an empty set
+    pub fn load(path: &Path) -> Result<Self> {
+        let profiles = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse baselines {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Map::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            profiles,
+        })
+    }
+
+    // Baseline throughput (bytes/s) of `scenario` on `profile`
+    pub fn get(&self, profile: &str, scenario: &str) -> Option<f64> {
+        self.profiles.get(profile)?.get(scenario)?["bytes_per_sec"].as_f64()
+    }
+
+    // Replace the baseline of `scenario` on `profile`
+    pub fn record(&mut self, profile: &str, scenario: &str, report: &ProfileReport) {
+        let scenarios = self
+            .profiles
+            .entry(profile)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(scenarios) = scenarios {
+            scenarios.insert(scenario.to_string(), report.to_json());
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let text = serde_json::to_string_pretty(&self.profiles)?;
+        std::fs::write(&self.path, text + "\n")
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+// Fractional slowdown of `current` against `baseline` (0.25 = 25% slower),
+// if it exceeds `threshold` (a fraction too)
+pub fn regression(baseline: f64, current: f64, threshold: f64) -> Option<f64> {
+    if baseline <= 0.0 {
+        return None;
+    }
+    let slowdown = 1.0 - current / baseline;
+    (slowdown > threshold).then_some(slowdown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_baselines_round_trip_and_regressions() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("baselines.json");
+        let report = ProfileReport {
+            bytes: 1000,
+            scan: Duration::from_secs(1),
+            ..ProfileReport::default()
+        };
+
+        let mut baselines = Baselines::load(&path)?;
+        assert_eq!(baselines.get("ci", "small"), None);
+        baselines.record("ci", "small", &report);
+        baselines.save()?;
+
+        let loaded = Baselines::load(&path)?;
+        assert_eq!(loaded.get("ci", "small"), Some(1000.0));
+        assert_eq!(loaded.get("laptop", "small"), None);
+
+        assert_eq!(regression(1000.0, 950.0, 0.10), None);
+        assert!(regression(1000.0, 800.0, 0.10).is_some_and(|s| (s - 0.2).abs() < 1e-9));
+        assert_eq!(regression(1000.0, 1500.0, 0.10), None);
+        Ok(())
+    }
+}
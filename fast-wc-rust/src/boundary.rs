@@ -0,0 +1,141 @@
+// Chunk seams for tokenizing one buffer in parallel pieces. A seam that
+// falls inside a token would hand each side a fragment, so under
+// `ChunkBoundaryPolicy::Exact` seams are moved forward to the next point
+// where the byte before and the byte after are not both token bytes.
+use crate::{ChunkBoundaryPolicy, TokenTable};
+use std::ops::Range;
+
+// Cut `data` into consecutive, non-empty ranges of roughly `chunk` bytes
+// that together cover it exactly. With `Exact` no range boundary splits a
+// token; with `Split` boundaries fall every `chunk` bytes regardless.
+pub fn chunk_ranges(
+    data: &[u8],
+    chunk: usize,
+    table: &TokenTable,
+    policy: ChunkBoundaryPolicy,
+) -> Vec<Range<usize>> {
+    let chunk = chunk.max(1);
+    let mut ranges = Vec::with_capacity(data.len() / chunk + 1);
+    let mut start = 0;
+    while start < data.len() {
+        let mut end = (start + chunk).min(data.len());
+        if policy == ChunkBoundaryPolicy::Exact {
+            while end < data.len() && splits_token(data, end, table) {
+                end += 1;
+            }
+        }
+        ranges.push(start..end);
+        start = end;
+    }
+    ranges
+}
+
+// Whether cutting `data` before index `at` would split a token
+#[inline]
+pub fn splits_token(data: &[u8], at: usize, table: &TokenTable) -> bool {
+    at > 0 && at < data.len() && table[data[at - 1] as usize] && table[data[at] as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TOKEN_CHARS, parse_token_chars};
+
+    // Reference tokenizer: maximal runs of token bytes
+    fn tokens<'a>(data: &'a [u8], table: &TokenTable) -> Vec<&'a [u8]> {
+        data.split(|&b| !table[b as usize])
+            .filter(|t| !t.is_empty())
+            .collect()
+    }
+
+    fn chunked_tokens<'a>(
+        data: &'a [u8],
+        ranges: &[Range<usize>],
+        table: &TokenTable,
+    ) -> Vec<&'a [u8]> {
+        ranges
+            .iter()
+            .flat_map(|r| tokens(&data[r.clone()], table))
+            .collect()
+    }
+
+    const INPUTS: [&[u8]; 9] = [
+        b"alpha beta_gamma delta42 ",
+        b"  leading and trailing  ",
+        b"x",
+        b"one_single_long_identifier_without_any_break",
+        b"; ; ; ,,, ()",
+        b"a b c d e f g h",
+        b"int main(void) {\n\treturn 0;\n}\n",
+        b"caf\xc3\xa9 na\xc3\xafve \xff\xfe bytes",
+        b"kebab-case-words and-more",
+    ];
+
+    fn assert_covers(data: &[u8], ranges: &[Range<usize>]) {
+        let mut at = 0;
+        for range in ranges {
+            assert_eq!(range.start, at);
+            assert!(range.end > range.start);
+            at = range.end;
+        }
+        assert_eq!(at, data.len());
+    }
+
+    #[test]
+    fn test_exact_counts_every_token_once_at_every_chunk_size() -> anyhow::Result<()> {
+        let tables = [TOKEN_CHARS, parse_token_chars("a-zA-Z0-9_-")?];
+        for table in &tables {
+            for data in INPUTS {
+                let whole = tokens(data, table);
+                for chunk in 1..=data.len() + 1 {
+                    let ranges = chunk_ranges(data, chunk, table, ChunkBoundaryPolicy::Exact);
+                    assert_covers(data, &ranges);
+                    assert!(
+                        ranges
+                            .windows(2)
+                            .all(|w| !splits_token(data, w[0].end, table)),
+                        "seam inside a token: {:?} chunk {}",
+                        String::from_utf8_lossy(data),
+                        chunk
+                    );
+                    assert_eq!(chunked_tokens(data, &ranges, table), whole);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_cuts_at_fixed_offsets() {
+        for data in INPUTS {
+            for chunk in 1..=data.len() + 1 {
+                let ranges = chunk_ranges(data, chunk, &TOKEN_CHARS, ChunkBoundaryPolicy::Split);
+                assert_covers(data, &ranges);
+                assert!(ranges.iter().all(|r| r.len() <= chunk));
+
+                // Each seam inside a token turns it into one extra fragment
+                let cut = ranges
+                    .windows(2)
+                    .filter(|w| splits_token(data, w[0].end, &TOKEN_CHARS))
+                    .count();
+                let whole = tokens(data, &TOKEN_CHARS);
+                let pieces = chunked_tokens(data, &ranges, &TOKEN_CHARS);
+                assert_eq!(pieces.len(), whole.len() + cut);
+                assert_eq!(pieces.concat(), whole.concat());
+            }
+        }
+    }
+
+    #[test]
+    fn test_empty_and_degenerate_inputs() {
+        for policy in [ChunkBoundaryPolicy::Exact, ChunkBoundaryPolicy::Split] {
+            assert!(chunk_ranges(b"", 4, &TOKEN_CHARS, policy).is_empty());
+            // A chunk size of 0 is treated as 1 rather than looping forever
+            assert_eq!(chunk_ranges(b"a b", 0, &TOKEN_CHARS, policy).len(), 3);
+        }
+        // A buffer that is one token can only be one exact chunk
+        let whole = chunk_ranges(b"abcdefgh", 2, &TOKEN_CHARS, ChunkBoundaryPolicy::Exact);
+        assert_eq!(whole.len(), 1);
+        assert_eq!(whole[0], 0..8);
+    }
+}
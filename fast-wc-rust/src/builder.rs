@@ -0,0 +1,238 @@
+// Fluent construction of a `FastWordCounter` (see `FastWordCounter::builder`).
+// The builder starts from `Config::default()` (or a preset, via `From`),
+// so options added to `Config` later get their defaults without breaking
+// callers, and `build` rejects settings that cannot work.
+use crate::{
+    ChunkBoundaryPolicy, Config, ErrorPolicy, FastWordCounter, IoMode, LogFormat, MergeStrategy,
+    PathEncoding, PathWords, SeenOrder, SortOrder, TieBreak, TokenMode, TokenTable, TokenTransform,
+};
+use anyhow::{Result, bail};
+use std::borrow::Cow;
+
+// Setters that store their argument in the `Config` field of the same name
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: $ty) -> Self {
+                self.config.$field = value;
+                self
+            }
+        )*
+    };
+}
+
+// Setters for optional limits: `.top(10)` sets one, `.top(None)` clears it
+macro_rules! optional_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, value: impl Into<Option<$ty>>) -> Self {
+                self.config.$field = value.into();
+                self
+            }
+        )*
+    };
+}
+
+// Setters for word and pattern lists
+macro_rules! list_setters {
+    ($($field:ident),* $(,)?) => {
+        $(
+            pub fn $field<I, S>(mut self, items: I) -> Self
+            where
+                I: IntoIterator<Item = S>,
+                S: Into<String>,
+            {
+                self.config.$field = items.into_iter().map(Into::into).collect();
+                self
+            }
+        )*
+    };
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CounterBuilder {
+    config: Config,
+}
+
+impl From<Config> for CounterBuilder {
+    fn from(config: Config) -> Self {
+        Self { config }
+    }
+}
+
+impl CounterBuilder {
+    setters! {
+        num_threads: usize,
+        use_mmap: bool,
+        show_progress: bool,
+        print_results: bool,
+        shares: bool,
+        parallel_merge: bool,
+        log_format: LogFormat,
+        collect_file_stats: bool,
+        skip_binary: bool,
+        line_stats: bool,
+        auto_tune: bool,
+        error_policy: ErrorPolicy,
+        follow_symlinks: bool,
+        token_mode: TokenMode,
+        code_aware: bool,
+        prefault: bool,
+        exclude_keywords: bool,
+        io_mode: IoMode,
+        small_file_bytes: usize,
+        content_hash: bool,
+        decompress: bool,
+        archives: bool,
+        split_identifiers: bool,
+        path_words: PathWords,
+        chunk_boundary_policy: ChunkBoundaryPolicy,
+        merge_strategy: MergeStrategy,
+        sort: SortOrder,
+        tie_break: TieBreak,
+        stable: bool,
+        path_encoding: PathEncoding,
+    }
+
+    optional_setters! {
+        max_token_len: usize,
+        max_unique_per_file: usize,
+        max_unique: usize,
+        sample_occurrences: usize,
+        token_chars: TokenTable,
+        flush_every_files: usize,
+        flush_every_entries: usize,
+        max_depth: usize,
+        spill_threshold: usize,
+        chunk_bytes: usize,
+        top: usize,
+        seen_order: SeenOrder,
+        min_count: u64,
+        max_count: u64,
+    }
+
+    list_setters! {
+        extensions,
+        include_globs,
+        exclude_globs,
+        stopwords,
+    }
+
+    // Alias -> canonical word pairs, replacing any set before
+    pub fn aliases<I, A, C>(mut self, pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (A, C)>,
+        A: Into<String>,
+        C: Into<String>,
+    {
+        self.config.aliases = pairs
+            .into_iter()
+            .map(|(alias, canonical)| (alias.into(), canonical.into()))
+            .collect();
+        self
+    }
+
+    // Abbreviation -> expansion pairs for `split_identifiers`, replacing
+    // any set before; abbreviations are lowercased like the sub-tokens
+    pub fn expansions<I, A, E>(mut self, pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (A, E)>,
+        A: Into<String>,
+        E: Into<String>,
+    {
+        self.config.expansions = pairs
+            .into_iter()
+            .map(|(abbreviation, expansion)| (abbreviation.into().to_lowercase(), expansion.into()))
+            .collect();
+        self
+    }
+
+    // Per-token rewrite hook (see `Config::transform`)
+    pub fn transform<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str) -> Option<Cow<'_, str>> + Send + Sync + 'static,
+    {
+        self.config.transform = Some(TokenTransform::new(f));
+        self
+    }
+
+    // The validated configuration, e.g. to adjust further or to share
+    // between counters
+    pub fn config(self) -> Result<Config> {
+        let config = self.config;
+        if config.num_threads == 0 {
+            bail!("num_threads must be at least 1");
+        }
+        if config.extensions.is_empty() {
+            bail!("extensions must name at least one extension");
+        }
+        if config.line_stats && !config.collect_file_stats {
+            bail!("line_stats requires collect_file_stats");
+        }
+        if let (Some(min), Some(max)) = (config.min_count, config.max_count)
+            && min > max
+        {
+            bail!("min_count ({}) exceeds max_count ({})", min, max);
+        }
+        let limits = [
+            ("max_token_len", config.max_token_len),
+            ("max_unique_per_file", config.max_unique_per_file),
+            ("max_unique", config.max_unique),
+            ("sample_occurrences", config.sample_occurrences),
+            ("flush_every_files", config.flush_every_files),
+            ("flush_every_entries", config.flush_every_entries),
+            ("max_depth", config.max_depth),
+            ("spill_threshold", config.spill_threshold),
+            ("top", config.top),
+        ];
+        for (name, limit) in limits {
+            if limit == Some(0) {
+                bail!("{} must be at least 1 (or unset)", name);
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn build(self) -> Result<FastWordCounter> {
+        Ok(FastWordCounter::new(self.config()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_sets_and_validates() -> Result<()> {
+        let config = FastWordCounter::builder()
+            .num_threads(2)
+            .top(10)
+            .extensions(["rs"])
+            .aliases([("u32", "uint32_t")])
+            .config()?;
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.top, Some(10));
+        assert_eq!(config.extensions, vec!["rs".to_string()]);
+        assert_eq!(config.aliases["u32"], "uint32_t");
+        // Untouched options keep their defaults
+        assert_eq!(config.chunk_bytes, Config::default().chunk_bytes);
+
+        let strict = CounterBuilder::from(Config::strict()).config()?;
+        assert_eq!(strict.error_policy, ErrorPolicy::Abort);
+        assert!(strict.stable && !strict.use_mmap);
+
+        let err = FastWordCounter::builder().num_threads(0).config();
+        assert!(err.unwrap_err().to_string().contains("num_threads"));
+        assert!(FastWordCounter::builder().top(0).build().is_err());
+        assert!(FastWordCounter::builder().line_stats(true).build().is_err());
+        assert!(
+            FastWordCounter::builder()
+                .min_count(5)
+                .max_count(2)
+                .build()
+                .is_err()
+        );
+        assert!(FastWordCounter::builder().chunk_bytes(None).build().is_ok());
+        Ok(())
+    }
+}
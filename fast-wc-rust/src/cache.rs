@@ -0,0 +1,256 @@
+// On-disk cache of per-file word counts for `--cache`, so a rerun over a
+// mostly unchanged tree only tokenizes the files that changed. Entries are
+// the `Session` cache's: valid while a file's size and mtime (or, with
+// `content_hash`, its contents hash) match.
+//
+// The file starts with an artifact header line and a line fingerprinting
+// the tokenizer settings; a cache written under other settings is ignored.
+// Entries follow in little-endian binary:
+//   path length (u32), path bytes, size (u64), mtime seconds (u64) and
+//   nanoseconds (u32), hash flag (u8), hash (u64), number of words (u32),
+//   then that many `spill` entries
+use crate::FastWordCounter;
+use crate::artifact::{self, CACHE, CACHE_VERSION};
+use crate::output::AtomicFile;
+use crate::session::CachedFile;
+use crate::spill::{read_entry, write_entry};
+use ahash::AHashMap;
+use anyhow::{Context, Result, bail};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+// Fingerprint line: a hash of every setting that changes a file's counts
+fn fingerprint(counter: &FastWordCounter) -> String {
+    let config = &counter.config;
+    let mut stopwords: Vec<&String> = counter.stopwords.iter().collect();
+    stopwords.sort_unstable();
+    let mut aliases: Vec<_> = config.aliases.iter().collect();
+    aliases.sort_unstable();
+    let mut expansions: Vec<_> = config.expansions.iter().collect();
+    expansions.sort_unstable();
+    let settings = format!(
+        "{:?} {:?} {} {:?} {:?} {} {} {:?} {:?} {} {:?}",
+        counter.token_table,
+        config.token_mode,
+        config.code_aware,
+        config.max_token_len,
+        config.max_unique_per_file,
+        config.decompress,
+        config.skip_binary,
+        stopwords,
+        aliases,
+        config.split_identifiers,
+        expansions
+    );
+    format!(
+        "tokenizer {:016x}",
+        xxhash_rust::xxh3::xxh3_64(settings.as_bytes())
+    )
+}
+
+// Entries of the cache at `path` written under the same tokenizer settings.
+// A missing cache, one from other settings, or any cache when a `transform`
+// closure (which cannot be fingerprinted) is set, has no entries.
+pub(crate) fn load(path: &Path, counter: &FastWordCounter) -> Result<Vec<(PathBuf, CachedFile)>> {
+    if counter.config.transform.is_some() {
+        return Ok(Vec::new());
+    }
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to open cache {}", path.display()));
+        }
+    };
+    let mut reader = BufReader::new(file);
+
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if artifact::check_header(path, &line, CACHE, CACHE_VERSION)? == 0 {
+        bail!("{} is not a fast-wc-rust cache", path.display());
+    }
+    line.clear();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != fingerprint(counter) {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    while let Some(entry) =
+        read_file_entry(&mut reader).with_context(|| format!("Corrupt cache {}", path.display()))?
+    {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// Replace the cache at `path` with `entries`, returning how many were
+// written. Entries without an mtime could never be validated and are left
+// out; with a `transform` set nothing is written.
+pub(crate) fn save<'a, I>(path: &Path, counter: &FastWordCounter, entries: I) -> Result<usize>
+where
+    I: Iterator<Item = (&'a PathBuf, &'a CachedFile)>,
+{
+    if counter.config.transform.is_some() {
+        return Ok(0);
+    }
+    let mut file = AtomicFile::create(path, false)
+        .with_context(|| format!("Failed to create cache {}", path.display()))?;
+    writeln!(file, "{}", artifact::header(CACHE, CACHE_VERSION))?;
+    writeln!(file, "{}", fingerprint(counter))?;
+
+    let mut saved = 0;
+    for (file_path, cached) in entries {
+        let Some(modified) = cached
+            .modified
+            .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        else {
+            continue;
+        };
+        write_file_entry(&mut file, file_path, cached, modified)?;
+        saved += 1;
+    }
+    file.commit()
+        .with_context(|| format!("Failed to write cache {}", path.display()))?;
+    Ok(saved)
+}
+
+fn write_file_entry<W: Write>(
+    writer: &mut W,
+    path: &Path,
+    cached: &CachedFile,
+    modified: Duration,
+) -> io::Result<()> {
+    let path = path_bytes(path);
+    writer.write_all(&(path.len() as u32).to_le_bytes())?;
+    writer.write_all(&path)?;
+    writer.write_all(&cached.len.to_le_bytes())?;
+    writer.write_all(&modified.as_secs().to_le_bytes())?;
+    writer.write_all(&modified.subsec_nanos().to_le_bytes())?;
+    writer.write_all(&[cached.hash.is_some() as u8])?;
+    writer.write_all(&cached.hash.unwrap_or(0).to_le_bytes())?;
+    writer.write_all(&(cached.counts.len() as u32).to_le_bytes())?;
+    for (word, &count) in cached.counts.iter() {
+        write_entry(writer, word, count)?;
+    }
+    Ok(())
+}
+
+// Next entry written by `write_file_entry`, or None at end of input
+fn read_file_entry<R: Read>(reader: &mut R) -> io::Result<Option<(PathBuf, CachedFile)>> {
+    let mut path_len = [0u8; 4];
+    match reader.read_exact(&mut path_len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut path = vec![0u8; u32::from_le_bytes(path_len) as usize];
+    reader.read_exact(&mut path)?;
+    let len = read_u64(reader)?;
+    let secs = read_u64(reader)?;
+    let mut nanos = [0u8; 4];
+    reader.read_exact(&mut nanos)?;
+    let mut has_hash = [0u8; 1];
+    reader.read_exact(&mut has_hash)?;
+    let hash = read_u64(reader)?;
+    let mut words = [0u8; 4];
+    reader.read_exact(&mut words)?;
+
+    let words = u32::from_le_bytes(words) as usize;
+    let mut counts = AHashMap::with_capacity(words);
+    for _ in 0..words {
+        let (word, count) = read_entry(reader)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        counts.insert(word, count);
+    }
+    let modified = UNIX_EPOCH + Duration::new(secs, u32::from_le_bytes(nanos));
+    Ok(Some((
+        path_from_bytes(path),
+        CachedFile {
+            len,
+            modified: Some(modified),
+            hash: (has_hash[0] != 0).then_some(hash),
+            counts: Arc::new(counts),
+            meta: None,
+        },
+    )))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    Cow::Owned(path.to_string_lossy().into_owned().into_bytes())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, Session};
+    use anyhow::Result;
+
+    fn session(token_chars: &str) -> Result<Session> {
+        Session::new(Config {
+            show_progress: false,
+            collect_file_stats: true,
+            token_chars: Some(crate::parse_token_chars(token_chars)?),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn test_cache_survives_a_restart() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int a;")?;
+        std::fs::write(dir.path().join("b.c"), "int b; b;")?;
+        let cache = dir.path().join(".wc-cache");
+
+        let first = session("a-z")?;
+        assert_eq!(first.load_cache(&cache)?, 0);
+        let expected = first.count(dir.path())?;
+        assert_eq!(first.save_cache(&cache)?, 2);
+
+        // A new process reuses both files without tokenizing them
+        let second = session("a-z")?;
+        assert_eq!(second.load_cache(&cache)?, 2);
+        assert_eq!(second.count(dir.path())?, expected);
+        assert!(second.counter().file_stats().is_empty());
+
+        // Only the rewritten file is tokenized again
+        std::fs::write(dir.path().join("b.c"), "int b;")?;
+        let third = session("a-z")?;
+        third.load_cache(&cache)?;
+        assert_eq!(third.count(dir.path())?[0], ("int".to_string(), 2));
+        assert_eq!(third.counter().file_stats().len(), 1);
+
+        // Other tokenizer settings ignore the cache
+        assert_eq!(session("a-z0-9_")?.load_cache(&cache)?, 0);
+
+        std::fs::write(&cache, "int | 3\n")?;
+        assert!(session("a-z")?.load_cache(&cache).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use crate::subtoken;
+use ahash::AHashMap;
+use std::path::PathBuf;
+
+// Naming convention of an identifier, judged from its sub-tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Casing {
+    // Lowercase parts joined by underscores, or one lowercase word: `parse_header`
+    Snake,
+    // Parts distinguished by capitals, including PascalCase: `parseHeader`, `Header`
+    Camel,
+    // Uppercase parts, joined by underscores or not: `MAX_LEN`, `EOF`
+    Screaming,
+    // Underscores between parts of differing case: `parse_Header`, `Max_len`
+    Mixed,
+}
+
+impl Casing {
+    pub const ALL: [Casing; 4] = [
+        Casing::Snake,
+        Casing::Camel,
+        Casing::Screaming,
+        Casing::Mixed,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Casing::Snake => "snake_case",
+            Casing::Camel => "camelCase",
+            Casing::Screaming => "SCREAMING_CASE",
+            Casing::Mixed => "mixed",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// Case of a single sub-token
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PartCase {
+    Lower,
+    Upper,
+    Capitalized,
+}
+
+fn part_case(part: &str) -> PartCase {
+    if !part.chars().any(char::is_lowercase) {
+        PartCase::Upper
+    } else if part.chars().next().is_some_and(char::is_uppercase) {
+        PartCase::Capitalized
+    } else {
+        PartCase::Lower
+    }
+}
+
+// Classify an identifier; None for tokens that are not identifiers (numbers,
+// underscores alone, or no letters at all). Leading and trailing underscores
+// (`_private`, `__init__`) are ignored.
+pub fn classify(word: &str) -> Option<Casing> {
+    if word.starts_with(|c: char| c.is_numeric()) || !word.chars().any(char::is_alphabetic) {
+        return None;
+    }
+    let trimmed = word.trim_matches('_');
+    let parts = subtoken::split(trimmed);
+    let cases: Vec<PartCase> = parts.iter().map(|part| part_case(part)).collect();
+    let all = |case| cases.iter().all(|&c| c == case);
+
+    Some(if all(PartCase::Upper) {
+        Casing::Screaming
+    } else if all(PartCase::Lower) {
+        Casing::Snake
+    } else if trimmed.contains('_') {
+        Casing::Mixed
+    } else {
+        Casing::Camel
+    })
+}
+
+// Identifier occurrences per convention
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CasingStats {
+    counts: [u64; 4],
+}
+
+impl CasingStats {
+    pub fn add(&mut self, casing: Casing, count: u64) {
+        self.counts[casing.index()] += count;
+    }
+
+    fn add_all(&mut self, other: &CasingStats) {
+        for casing in Casing::ALL {
+            self.add(casing, other.get(casing));
+        }
+    }
+
+    pub fn get(&self, casing: Casing) -> u64 {
+        self.counts[casing.index()]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    // Share of identifier occurrences following `casing`, in percent
+    pub fn percent(&self, casing: Casing) -> f64 {
+        match self.total() {
+            0 => 0.0,
+            total => self.get(casing) as f64 * 100.0 / total as f64,
+        }
+    }
+
+    // The more common of snake_case and camelCase (snake_case on a tie);
+    // SCREAMING_CASE is left out as the usual style of macros and constants
+    pub fn dominant(&self) -> Casing {
+        if self.get(Casing::Camel) > self.get(Casing::Snake) {
+            Casing::Camel
+        } else {
+            Casing::Snake
+        }
+    }
+}
+
+// An identifier breaking the project's dominant convention
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CasingOffender {
+    pub word: String,
+    pub casing: Casing,
+    pub count: u64,
+}
+
+// Result of `FastWordCounter::casing_audit`
+#[derive(Debug, Clone, Default)]
+pub struct CasingReport {
+    // Occurrences per convention in each directory's own files, by path
+    pub directories: Vec<(PathBuf, CasingStats)>,
+    pub total: CasingStats,
+    // Mixed identifiers, and those following the non-dominant one of
+    // snake_case and camelCase, most frequent first
+    pub offenders: Vec<CasingOffender>,
+}
+
+impl CasingReport {
+    // Build the report from each directory's identifier counts, keeping the
+    // `n` most frequent offenders
+    pub(crate) fn from_directories(
+        directories: AHashMap<PathBuf, AHashMap<String, u64>>,
+        n: usize,
+    ) -> Self {
+        let mut report = CasingReport::default();
+        let mut words: AHashMap<String, (Casing, u64)> = AHashMap::new();
+        for (dir, counts) in directories {
+            let mut stats = CasingStats::default();
+            for (word, count) in counts {
+                let Some(casing) = classify(&word) else {
+                    continue;
+                };
+                stats.add(casing, count);
+                words.entry(word).or_insert((casing, 0)).1 += count;
+            }
+            report.total.add_all(&stats);
+            report.directories.push((dir, stats));
+        }
+        report.directories.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let dominant = report.total.dominant();
+        let mut offenders: Vec<CasingOffender> = words
+            .into_iter()
+            .filter(|(_, (casing, _))| *casing != dominant && *casing != Casing::Screaming)
+            .map(|(word, (casing, count))| CasingOffender {
+                word,
+                casing,
+                count,
+            })
+            .collect();
+        offenders.sort_unstable_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        offenders.truncate(n);
+        report.offenders = offenders;
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_report() {
+        assert_eq!(classify("parse_header"), Some(Casing::Snake));
+        assert_eq!(classify("count"), Some(Casing::Snake));
+        assert_eq!(classify("parseHeader"), Some(Casing::Camel));
+        assert_eq!(classify("HttpServer"), Some(Casing::Camel));
+        assert_eq!(classify("MAX_LEN"), Some(Casing::Screaming));
+        assert_eq!(classify("__FILE__"), Some(Casing::Screaming));
+        assert_eq!(classify("parse_Header"), Some(Casing::Mixed));
+        assert_eq!(classify("_private"), Some(Casing::Snake));
+        assert_eq!(classify("0x1F"), None);
+        assert_eq!(classify("__"), None);
+
+        let counts = |pairs: &[(&str, u64)]| -> AHashMap<String, u64> {
+            pairs.iter().map(|(w, c)| (w.to_string(), *c)).collect()
+        };
+        let mut dirs = AHashMap::new();
+        dirs.insert(
+            PathBuf::from("src"),
+            counts(&[("read_file", 6), ("readFile", 2), ("MAX", 2)]),
+        );
+        dirs.insert(
+            PathBuf::from("lib"),
+            counts(&[("open_Dir", 1), ("close", 3), ("42", 9)]),
+        );
+        let report = CasingReport::from_directories(dirs, 10);
+
+        assert_eq!(report.directories[0].0, PathBuf::from("lib"));
+        assert_eq!(report.directories[1].1.percent(Casing::Snake), 60.0);
+        assert_eq!(report.total.total(), 14);
+        assert_eq!(report.total.dominant(), Casing::Snake);
+        let offenders: Vec<(&str, Casing)> = report
+            .offenders
+            .iter()
+            .map(|o| (o.word.as_str(), o.casing))
+            .collect();
+        assert_eq!(
+            offenders,
+            [("readFile", Casing::Camel), ("open_Dir", Casing::Mixed)]
+        );
+    }
+}
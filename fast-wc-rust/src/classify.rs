@@ -0,0 +1,108 @@
+use ahash::AHashMap;
+
+// Coarse lexical category of a counted token
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenCategory {
+    // Ordinary identifiers (and keywords): `parse_header`, `int`
+    Identifier,
+    // Numeric literals, i.e. tokens starting with a digit: `42`, `0x1F`, `1e9`
+    Number,
+    // All-caps names, by convention macros and constants: `MAX_LEN`, `EOF`
+    Macro,
+}
+
+impl TokenCategory {
+    pub const ALL: [TokenCategory; 3] = [
+        TokenCategory::Identifier,
+        TokenCategory::Number,
+        TokenCategory::Macro,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenCategory::Identifier => "identifiers",
+            TokenCategory::Number => "numbers",
+            TokenCategory::Macro => "macros",
+        }
+    }
+}
+
+// Classify a token: a leading digit makes a number; otherwise at least one
+// uppercase letter and no lowercase ones make a macro
+pub fn classify(word: &str) -> TokenCategory {
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        TokenCategory::Number
+    } else if word.chars().any(char::is_uppercase) && !word.chars().any(char::is_lowercase) {
+        TokenCategory::Macro
+    } else {
+        TokenCategory::Identifier
+    }
+}
+
+// Word counts split by `TokenCategory`, from `count_directory_classified`
+#[derive(Debug, Clone, Default)]
+pub struct ClassifiedCounts {
+    pub identifiers: AHashMap<String, u64>,
+    pub numbers: AHashMap<String, u64>,
+    pub macros: AHashMap<String, u64>,
+}
+
+impl ClassifiedCounts {
+    pub fn from_counts(counts: AHashMap<String, u64>) -> Self {
+        let mut classified = Self::default();
+        for (word, count) in counts {
+            let category = classify(&word);
+            classified.get_mut(category).insert(word, count);
+        }
+        classified
+    }
+
+    pub fn get(&self, category: TokenCategory) -> &AHashMap<String, u64> {
+        match category {
+            TokenCategory::Identifier => &self.identifiers,
+            TokenCategory::Number => &self.numbers,
+            TokenCategory::Macro => &self.macros,
+        }
+    }
+
+    fn get_mut(&mut self, category: TokenCategory) -> &mut AHashMap<String, u64> {
+        match category {
+            TokenCategory::Identifier => &mut self.identifiers,
+            TokenCategory::Number => &mut self.numbers,
+            TokenCategory::Macro => &mut self.macros,
+        }
+    }
+
+    // Total occurrences in a category
+    pub fn total(&self, category: TokenCategory) -> u64 {
+        self.get(category)
+            .values()
+            .fold(0, |acc, &count| acc.saturating_add(count))
+    }
+
+    // A category's words by count descending, then alphabetically
+    pub fn sorted(&self, category: TokenCategory) -> Vec<(String, u64)> {
+        let mut pairs: Vec<_> = self
+            .get(category)
+            .iter()
+            .map(|(word, &count)| (word.clone(), count))
+            .collect();
+        pairs.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify("parse_header"), TokenCategory::Identifier);
+        assert_eq!(classify("MyType"), TokenCategory::Identifier);
+        assert_eq!(classify("0x1F"), TokenCategory::Number);
+        assert_eq!(classify("42"), TokenCategory::Number);
+        assert_eq!(classify("MAX_LEN2"), TokenCategory::Macro);
+        assert_eq!(classify("_"), TokenCategory::Identifier);
+    }
+}
@@ -0,0 +1,88 @@
+// Splits C/C++ source into the spans that are code, skipping `//` and
+// `/* */` comments and string/char literals. Unterminated literals end at
+// the newline; unterminated block comments run to end of input.
+
+// Call `f` with each run of code between comments and literals
+pub fn for_each_code_span<'d, F: FnMut(&'d [u8])>(data: &'d [u8], mut f: F) {
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        let skip_to = match data[i] {
+            b'/' if data.get(i + 1) == Some(&b'/') => skip_line_comment(data, i + 2),
+            b'/' if data.get(i + 1) == Some(&b'*') => skip_block_comment(data, i + 2),
+            b'"' => skip_literal(data, i + 1, b'"'),
+            // A quote after a digit is a C++14 digit separator (1'000)
+            b'\'' if i == 0 || !data[i - 1].is_ascii_digit() => skip_literal(data, i + 1, b'\''),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        if start < i {
+            f(&data[start..i]);
+        }
+        i = skip_to;
+        start = i;
+    }
+
+    if start < data.len() {
+        f(&data[start..]);
+    }
+}
+
+// Index of the newline ending a `//` comment, honouring `\` continuations
+fn skip_line_comment(data: &[u8], mut i: usize) -> usize {
+    while i < data.len() {
+        match data[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            _ => i += 1,
+        }
+    }
+    data.len()
+}
+
+fn skip_block_comment(data: &[u8], i: usize) -> usize {
+    data[i.min(data.len())..]
+        .windows(2)
+        .position(|w| w == b"*/")
+        .map_or(data.len(), |end| i + end + 2)
+}
+
+// Index just past the closing `quote`, skipping escapes
+fn skip_literal(data: &[u8], mut i: usize, quote: u8) -> usize {
+    while i < data.len() {
+        match data[i] {
+            b'\\' => i += 2,
+            b'\n' => return i,
+            c if c == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(src: &str) -> String {
+        let mut out = Vec::new();
+        for_each_code_span(src.as_bytes(), |span| {
+            out.extend_from_slice(span);
+            out.push(b'|');
+        });
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_skips_comments_and_literals() {
+        assert_eq!(
+            code("int a; // note\nb = \"x // \\\" y\" + 'c'; /* multi\nline */ d"),
+            "int a; |\nb = | + |; | d|"
+        );
+        assert_eq!(code("n = 1'000'000;"), "n = 1'000'000;|");
+        assert_eq!(code("x /* open"), "x |");
+    }
+}
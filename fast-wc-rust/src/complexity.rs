@@ -0,0 +1,88 @@
+use crate::TokenTable;
+use std::path::PathBuf;
+
+// Keywords that introduce a branch
+pub const BRANCH_KEYWORDS: [&str; 4] = ["if", "for", "while", "case"];
+// Operators that introduce a short-circuit branch
+pub const BRANCH_OPERATORS: [&[u8]; 2] = [b"&&", b"||"];
+
+// Branch counts for one file, a cheap cyclomatic-complexity proxy
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchStats {
+    pub path: PathBuf,
+    pub branches: u64,
+    pub tokens: u64,
+    pub lines: u64,
+}
+
+impl BranchStats {
+    // Branches per 100 lines
+    pub fn density(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.branches as f64 * 100.0 / self.lines as f64
+        }
+    }
+}
+
+// Count branching keywords and operators in `data`, returning
+// (branches, tokens, lines)
+pub(crate) fn count_branches(data: &[u8], token_table: &TokenTable) -> (u64, u64, u64) {
+    let (mut branches, mut tokens, mut lines) = (0, 0, 0);
+    let mut word_start = None;
+
+    for (i, &byte) in data.iter().enumerate() {
+        if token_table[byte as usize] {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            tokens += 1;
+            if is_branch_keyword(&data[start..i]) {
+                branches += 1;
+            }
+        }
+        if byte == b'\n' {
+            lines += 1;
+        }
+        if BRANCH_OPERATORS
+            .iter()
+            .any(|op| data[i..].starts_with(op) && (i == 0 || data[i - 1] != op[0]))
+        {
+            branches += 1;
+        }
+    }
+
+    if let Some(start) = word_start {
+        tokens += 1;
+        if is_branch_keyword(&data[start..]) {
+            branches += 1;
+        }
+    }
+    if data.last().is_some_and(|&b| b != b'\n') {
+        lines += 1;
+    }
+
+    (branches, tokens, lines)
+}
+
+fn is_branch_keyword(word: &[u8]) -> bool {
+    BRANCH_KEYWORDS.iter().any(|k| k.as_bytes() == word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TOKEN_CHARS;
+
+    #[test]
+    fn test_count_branches() {
+        let src = b"if (a && b) {\n  for (;;) x++;\n} else if (c || d) iffy();\n";
+        let (branches, tokens, lines) = count_branches(src, &TOKEN_CHARS);
+
+        assert_eq!(branches, 5);
+        assert_eq!(tokens, 10);
+        assert_eq!(lines, 3);
+    }
+}
@@ -0,0 +1,211 @@
+// Synthetic corpora for benchmarks, tests and the gen-corpus subcommand.
+// Everything is derived from `CorpusSpec::seed`, so the same spec always
+// writes the same bytes and a benchmark scenario can be reproduced exactly.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Size of each generated file in bytes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeDistribution {
+    // Every file has the same size
+    Fixed(usize),
+    // Uniform between `min` and `max` (inclusive)
+    Uniform { min: usize, max: usize },
+    // Heavy-tailed: most files near `min`, a few much larger, like real
+    // source trees. Smaller `alpha` means a heavier tail.
+    Pareto { min: usize, alpha: f64 },
+}
+
+// Shape of a generated corpus
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusSpec {
+    pub files: usize,
+    pub file_size: SizeDistribution,
+    // Distinct words to draw from
+    pub vocab: usize,
+    // Zipf exponent: the word of rank k is drawn with weight 1 / k^zipf
+    // (0 is uniform, ~1 resembles natural text and code)
+    pub zipf: f64,
+    // Words per line before a newline
+    pub words_per_line: usize,
+    // Spread files over subdirectories of at most this many files each
+    // (None puts every file in the root)
+    pub files_per_dir: Option<usize>,
+    pub extension: String,
+    pub seed: u64,
+}
+
+impl Default for CorpusSpec {
+    fn default() -> Self {
+        Self {
+            files: 100,
+            file_size: SizeDistribution::Fixed(10 * 1024),
+            vocab: 10_000,
+            zipf: 1.0,
+            words_per_line: 10,
+            files_per_dir: None,
+            extension: "c".to_string(),
+            seed: 0,
+        }
+    }
+}
+
+impl CorpusSpec {
+    // Write the corpus under `dir`, returning the file paths in creation order
+    pub fn generate(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut rng = SplitMix64(self.seed);
+        let vocab: Vec<String> = (0..self.vocab.max(1)).map(word).collect();
+        let sampler = ZipfSampler::new(vocab.len(), self.zipf);
+
+        let mut paths = Vec::with_capacity(self.files);
+        for i in 0..self.files {
+            let parent = match self.files_per_dir {
+                Some(n) => dir.join(format!("dir_{:04}", i / n.max(1))),
+                None => dir.to_path_buf(),
+            };
+            fs::create_dir_all(&parent)?;
+            let path = parent.join(format!("file_{:06}.{}", i, self.extension));
+
+            let target = self.file_size.sample(&mut rng);
+            let mut content = String::with_capacity(target + 32);
+            let mut on_line = 0;
+            while content.len() < target {
+                content.push_str(&vocab[sampler.sample(&mut rng)]);
+                on_line += 1;
+                if on_line >= self.words_per_line.max(1) {
+                    content.push('\n');
+                    on_line = 0;
+                } else {
+                    content.push(' ');
+                }
+            }
+            fs::write(&path, content)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+impl SizeDistribution {
+    fn sample(&self, rng: &mut SplitMix64) -> usize {
+        match *self {
+            SizeDistribution::Fixed(size) => size,
+            SizeDistribution::Uniform { min, max } => {
+                min + (rng.next() % (max.saturating_sub(min) as u64 + 1)) as usize
+            }
+            SizeDistribution::Pareto { min, alpha } => {
+                // Inverse CDF; 1 - u keeps the base in (0, 1]
+                let u = 1.0 - rng.next_f64();
+                (min as f64 / u.powf(1.0 / alpha.max(f64::EPSILON))) as usize
+            }
+        }
+    }
+}
+
+// The `k`th vocabulary word: lowercase letters counting up from "aaa", so
+// words are valid identifiers and distinct for every `k`
+pub fn word(mut k: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push(b'a' + (k % 26) as u8);
+        k /= 26;
+        if k == 0 && letters.len() >= 3 {
+            break;
+        }
+    }
+    letters.reverse();
+    String::from_utf8(letters).unwrap()
+}
+
+// Draws ranks 0..n with probability proportional to 1 / (rank + 1)^s
+struct ZipfSampler {
+    cumulative: Vec<f64>,
+}
+
+impl ZipfSampler {
+    fn new(n: usize, s: f64) -> Self {
+        let mut total = 0.0;
+        let cumulative = (1..=n)
+            .map(|rank| {
+                total += 1.0 / (rank as f64).powf(s);
+                total
+            })
+            .collect();
+        Self { cumulative }
+    }
+
+    fn sample(&self, rng: &mut SplitMix64) -> usize {
+        let target = rng.next_f64() * self.cumulative.last().copied().unwrap_or(0.0);
+        self.cumulative
+            .partition_point(|&c| c <= target)
+            .min(self.cumulative.len() - 1)
+    }
+}
+
+// Small, fast, seedable PRNG (SplitMix64); statistical quality is ample for
+// test data and it keeps the crate free of an RNG dependency
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_corpus_is_reproducible_and_skewed() -> anyhow::Result<()> {
+        let spec = CorpusSpec {
+            files: 6,
+            file_size: SizeDistribution::Uniform {
+                min: 2000,
+                max: 4000,
+            },
+            vocab: 500,
+            zipf: 1.2,
+            files_per_dir: Some(4),
+            seed: 7,
+            ..CorpusSpec::default()
+        };
+        let a = tempfile::TempDir::new()?;
+        let b = tempfile::TempDir::new()?;
+        let paths = spec.generate(a.path())?;
+        spec.generate(b.path())?;
+
+        assert_eq!(paths.len(), 6);
+        assert!(a.path().join("dir_0001/file_000005.c").exists());
+        let mut counts = ahash::AHashMap::new();
+        for path in &paths {
+            let text = std::fs::read_to_string(path)?;
+            let twin = b.path().join(path.strip_prefix(a.path())?);
+            assert_eq!(text, std::fs::read_to_string(twin)?);
+            assert!((2000..4100).contains(&text.len()));
+            for w in text.split_whitespace() {
+                *counts.entry(w.to_string()).or_insert(0u64) += 1;
+            }
+        }
+        // Rank 0 dominates a word far down the distribution
+        assert!(counts[&word(0)] > 10 * counts.get(&word(100)).copied().unwrap_or(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_words_are_distinct_identifiers() {
+        assert_eq!(word(0), "aaa");
+        assert_eq!(word(27), "abb");
+        assert_ne!(word(26 * 26 * 26), word(0));
+    }
+}
@@ -0,0 +1,421 @@
+use crate::Config;
+use crate::archive;
+use crate::input::COMPRESSED_EXTENSIONS;
+use ahash::AHashSet;
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Counters collected while walking the input tree
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiscoveryStats {
+    pub dirs_visited: u64,
+    pub files_seen: u64,
+    pub files_matched: u64,
+    pub filtered_by_extension: u64,
+    pub filtered_by_glob: u64,
+    pub broken_symlinks: u64,
+    // Symlinks pointing back at one of their own ancestors (followed links only)
+    pub symlink_loops: u64,
+    // Files reached again through another symlinked path (followed links only)
+    pub duplicate_files: u64,
+    pub walk_errors: u64,
+}
+
+impl fmt::Display for DiscoveryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "visited {} dirs, saw {} files, matched {}, {} filtered by extension",
+            self.dirs_visited, self.files_seen, self.files_matched, self.filtered_by_extension
+        )?;
+        if self.filtered_by_glob > 0 {
+            write!(
+                f,
+                ", {} filtered by include/exclude globs",
+                self.filtered_by_glob
+            )?;
+        }
+        if self.broken_symlinks > 0 {
+            write!(f, ", {} broken symlinks", self.broken_symlinks)?;
+        }
+        if self.symlink_loops > 0 {
+            write!(f, ", {} symlink loops", self.symlink_loops)?;
+        }
+        if self.duplicate_files > 0 {
+            write!(f, ", {} duplicate paths", self.duplicate_files)?;
+        }
+        if self.walk_errors > 0 {
+            write!(f, ", {} unreadable entries", self.walk_errors)?;
+        }
+        Ok(())
+    }
+}
+
+impl DiscoveryStats {
+    // Accumulate another walk's counters, e.g. for several input paths
+    pub fn add(&mut self, other: &DiscoveryStats) {
+        self.dirs_visited += other.dirs_visited;
+        self.files_seen += other.files_seen;
+        self.files_matched += other.files_matched;
+        self.filtered_by_extension += other.filtered_by_extension;
+        self.filtered_by_glob += other.filtered_by_glob;
+        self.broken_symlinks += other.broken_symlinks;
+        self.symlink_loops += other.symlink_loops;
+        self.duplicate_files += other.duplicate_files;
+        self.walk_errors += other.walk_errors;
+    }
+}
+
+// Compile glob patterns; `*` does not cross `/`, `**` does
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("invalid glob '{}'", pattern))?;
+        builder.add(glob);
+    }
+    Ok(Some(builder.build()?))
+}
+
+// Compiled include/exclude globs, matched against paths relative to the
+// walked directory
+pub(crate) struct GlobFilter {
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+}
+
+impl GlobFilter {
+    pub(crate) fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            include: build_globs(&config.include_globs)?,
+            exclude: build_globs(&config.exclude_globs)?,
+        })
+    }
+
+    // Whether `path`, found under `dir`, passes the globs
+    pub(crate) fn matches(&self, path: &Path, dir: &Path) -> bool {
+        let relative = path.strip_prefix(dir).unwrap_or(path);
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|set| set.is_match(relative));
+        let excluded = self
+            .exclude
+            .as_ref()
+            .is_some_and(|set| set.is_match(relative));
+        included && !excluded
+    }
+}
+
+// Walk `dir` and collect files with the configured extensions (and, with
+// `archives`, .tar/.zip archives) that pass the include/exclude globs.
+// Symlinks are skipped unless `follow_symlinks` is set; then links that
+// loop back to an ancestor are reported rather than walked, and a file
+// reachable through several links is counted once.
+pub(crate) fn discover_files(
+    dir: &Path,
+    config: &Config,
+) -> Result<(Vec<PathBuf>, DiscoveryStats)> {
+    walk_files(dir, config, |path| {
+        (config.archives && archive::is_archive(path)) || has_extension(path, config)
+    })
+}
+
+// Walk `dir` like `discover_files`, keeping the files `wanted` accepts in
+// place of the extension filter (rejected files count as filtered by
+// extension)
+pub(crate) fn walk_files<F: Fn(&Path) -> bool>(
+    dir: &Path,
+    config: &Config,
+    wanted: F,
+) -> Result<(Vec<PathBuf>, DiscoveryStats)> {
+    let globs = GlobFilter::new(config)?;
+    let mut files = Vec::new();
+    let mut stats = DiscoveryStats::default();
+    let mut seen = AHashSet::new();
+
+    let mut walker = WalkDir::new(dir).follow_links(config.follow_symlinks);
+    if let Some(depth) = config.max_depth {
+        walker = walker.max_depth(depth);
+    }
+
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => {
+                stats.symlink_loops += 1;
+                continue;
+            }
+            Err(e) if e.path().is_some_and(|p| p.is_symlink() && !p.exists()) => {
+                stats.broken_symlinks += 1;
+                continue;
+            }
+            Err(_) => {
+                stats.walk_errors += 1;
+                continue;
+            }
+        };
+
+        let file_type = entry.file_type();
+        if file_type.is_dir() {
+            stats.dirs_visited += 1;
+            continue;
+        }
+        if file_type.is_symlink() {
+            if std::fs::metadata(entry.path()).is_err() {
+                stats.broken_symlinks += 1;
+            }
+            continue;
+        }
+        if !file_type.is_file() {
+            continue;
+        }
+        stats.files_seen += 1;
+
+        if !wanted(entry.path()) {
+            stats.filtered_by_extension += 1;
+            continue;
+        }
+
+        if !globs.matches(entry.path(), dir) {
+            stats.filtered_by_glob += 1;
+        } else if config.follow_symlinks
+            && !seen.insert(
+                std::fs::canonicalize(entry.path()).unwrap_or_else(|_| entry.path().to_path_buf()),
+            )
+        {
+            stats.duplicate_files += 1;
+        } else {
+            files.push(entry.into_path());
+        }
+    }
+
+    stats.files_matched = files.len() as u64;
+    Ok((files, stats))
+}
+
+// Whether a walk of `dir` would reach `path` as a file: no deeper than
+// `max_depth`, and, unless `follow_symlinks` is set, through no symlinks.
+// `walk_files` leaves these checks to `WalkDir`; this applies them to a path
+// found some other way, such as a file-change event.
+pub(crate) fn within_walk(path: &Path, dir: &Path, config: &Config) -> bool {
+    let Ok(relative) = path.strip_prefix(dir) else {
+        return false;
+    };
+    if config
+        .max_depth
+        .is_some_and(|depth| relative.components().count() > depth)
+    {
+        return false;
+    }
+    if !config.follow_symlinks {
+        let mut at = dir.to_path_buf();
+        for component in relative.components() {
+            at.push(component);
+            if at.symlink_metadata().is_ok_and(|meta| meta.is_symlink()) {
+                return false;
+            }
+        }
+    }
+    path.is_file()
+}
+
+// Whether `path` has one of the configured extensions. With `decompress`,
+// a compressed file counts by the extension under its `.gz`/`.zst`.
+pub(crate) fn has_extension(path: &Path, config: &Config) -> bool {
+    matched_extension(path, config).is_some()
+}
+
+// The configured extension (without its dot) that `path` has, as in
+// `has_extension`
+pub(crate) fn matched_extension<'c>(path: &Path, config: &'c Config) -> Option<&'c str> {
+    let wanted = |ext: &OsStr| {
+        config
+            .extensions
+            .iter()
+            .map(|wanted| wanted.trim_start_matches('.'))
+            .find(|wanted| ext == *wanted)
+    };
+    let ext = path.extension()?;
+    if let Some(found) = wanted(ext) {
+        return Some(found);
+    }
+    if config.decompress && COMPRESSED_EXTENSIONS.iter().any(|c| ext == *c) {
+        return path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).extension())
+            .and_then(wanted);
+    }
+    None
+}
+
+// Names of `files` and of the directories between them and whichever of
+// `roots` they were found under, each distinct path once. Files outside
+// every root (or named as a root) contribute just their file name.
+pub(crate) fn path_names<'a>(roots: &[PathBuf], files: &'a [PathBuf]) -> Vec<&'a OsStr> {
+    let mut seen = AHashSet::new();
+    let mut names = Vec::new();
+    for file in files {
+        let relative = roots
+            .iter()
+            .filter_map(|root| file.strip_prefix(root).ok())
+            .find(|relative| !relative.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new(file.file_name().unwrap_or(file.as_os_str())));
+        // Ancestors shared with an earlier file were already named
+        for path in relative.ancestors() {
+            if path.as_os_str().is_empty() || !seen.insert(path) {
+                break;
+            }
+            names.extend(path.file_name());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discovery_stats() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a.c"), "int a;").unwrap();
+        std::fs::write(dir.path().join("sub/b.h"), "int b;").unwrap();
+        std::fs::write(dir.path().join("sub/readme.md"), "docs").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("missing.c"), dir.path().join("dangling.c"))
+            .unwrap();
+
+        let (files, stats) = discover_files(dir.path(), &Config::default()).unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(stats.dirs_visited, 2);
+        assert_eq!(stats.files_seen, 3);
+        assert_eq!(stats.filtered_by_extension, 1);
+        #[cfg(unix)]
+        assert_eq!(stats.broken_symlinks, 1);
+    }
+
+    #[test]
+    fn test_compressed_files_match_inner_extension() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a.c.gz", "b.h.zst", "c.txt.gz", "d.gz"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+
+        let matched = |decompress| {
+            let config = Config {
+                decompress,
+                ..Config::default()
+            };
+            discover_files(dir.path(), &config).unwrap().0.len()
+        };
+        assert_eq!(matched(true), 2);
+        assert_eq!(matched(false), 0);
+    }
+
+    #[test]
+    fn test_max_depth_limits_descent() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("gen/deep")).unwrap();
+        std::fs::write(dir.path().join("top.c"), "int a;").unwrap();
+        std::fs::write(dir.path().join("gen/mid.c"), "int b;").unwrap();
+        std::fs::write(dir.path().join("gen/deep/low.c"), "int c;").unwrap();
+
+        let depth = |max_depth| {
+            let config = Config {
+                max_depth,
+                ..Config::default()
+            };
+            discover_files(dir.path(), &config).unwrap().0.len()
+        };
+        assert_eq!(depth(None), 3);
+        assert_eq!(depth(Some(1)), 1);
+        assert_eq!(depth(Some(2)), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_detects_loops_and_duplicates() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/real.c"), "int a;").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("sub"), dir.path().join("vendor")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+
+        let (files, _) = discover_files(dir.path(), &Config::default()).unwrap();
+        assert_eq!(files.len(), 1);
+
+        let config = Config {
+            follow_symlinks: true,
+            ..Config::default()
+        };
+        let (files, stats) = discover_files(dir.path(), &config).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(stats.duplicate_files, 1);
+        assert!(stats.symlink_loops >= 1);
+    }
+
+    #[test]
+    fn test_configured_extensions() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "fn main() {}").unwrap();
+        std::fs::write(dir.path().join("lib.py"), "pass").unwrap();
+        std::fs::write(dir.path().join("a.c"), "int a;").unwrap();
+
+        let config = Config {
+            extensions: vec!["rs".into(), ".py".into()],
+            ..Config::default()
+        };
+        let (mut files, stats) = discover_files(dir.path(), &config).unwrap();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![dir.path().join("lib.py"), dir.path().join("main.rs")]
+        );
+        assert_eq!(stats.filtered_by_extension, 1);
+    }
+
+    #[test]
+    fn test_include_exclude_globs() {
+        let dir = TempDir::new().unwrap();
+        for sub in ["src/net", "vendor/zlib"] {
+            std::fs::create_dir_all(dir.path().join(sub)).unwrap();
+        }
+        for file in ["main.c", "src/a.c", "src/net/b.c", "vendor/zlib/z.c"] {
+            std::fs::write(dir.path().join(file), "int x;").unwrap();
+        }
+
+        let config = Config {
+            include_globs: vec!["src/**/*.c".into(), "*.c".into()],
+            exclude_globs: vec!["src/net/**".into()],
+            ..Config::default()
+        };
+        let (mut files, stats) = discover_files(dir.path(), &config).unwrap();
+        files.sort();
+
+        assert_eq!(
+            files,
+            vec![dir.path().join("main.c"), dir.path().join("src/a.c")]
+        );
+        assert_eq!(stats.filtered_by_glob, 2);
+
+        let bad = Config {
+            include_globs: vec!["src/[".into()],
+            ..Config::default()
+        };
+        assert!(discover_files(dir.path(), &bad).is_err());
+    }
+}
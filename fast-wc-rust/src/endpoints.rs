@@ -0,0 +1,118 @@
+use ahash::AHashMap;
+
+#[inline]
+fn is_scheme_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'+' || b == b'-' || b == b'.'
+}
+
+#[inline]
+fn is_url_char(b: u8) -> bool {
+    b.is_ascii_graphic() && !matches!(b, b'"' | b'\'' | b'<' | b'>' | b'`' | b'\\')
+}
+
+#[inline]
+fn is_local_part_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'.' | b'_' | b'%' | b'+' | b'-')
+}
+
+#[inline]
+fn is_domain_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'.' || b == b'-'
+}
+
+// Count URLs (`scheme://...`) and email addresses in `data`, each as a single
+// token. Trailing punctuation such as `.`, `,` or `)` is not part of a match.
+pub fn count_endpoints(data: &[u8], counts: &mut AHashMap<String, u64>) -> u64 {
+    let mut found = 0;
+    let mut i = 0;
+
+    while i < data.len() {
+        let matched = if data[i..].starts_with(b"://") {
+            match_url(data, i)
+        } else if data[i] == b'@' {
+            match_email(data, i)
+        } else {
+            None
+        };
+
+        match matched {
+            Some((start, end)) => {
+                let token = String::from_utf8_lossy(&data[start..end]).into_owned();
+                *counts.entry(token).or_insert(0) += 1;
+                found += 1;
+                i = end;
+            }
+            None => i += 1,
+        }
+    }
+
+    found
+}
+
+// Expand around a `://` at `sep` into a full URL span
+fn match_url(data: &[u8], sep: usize) -> Option<(usize, usize)> {
+    let mut start = sep;
+    while start > 0 && is_scheme_char(data[start - 1]) {
+        start -= 1;
+    }
+    if start == sep || !data[start].is_ascii_alphabetic() {
+        return None;
+    }
+
+    let mut end = sep + 3;
+    while end < data.len() && is_url_char(data[end]) {
+        end += 1;
+    }
+    end = trim_trailing(data, sep + 3, end);
+    (end > sep + 3).then_some((start, end))
+}
+
+// Expand around an `@` at `at` into a full email span
+fn match_email(data: &[u8], at: usize) -> Option<(usize, usize)> {
+    let mut start = at;
+    while start > 0 && is_local_part_char(data[start - 1]) {
+        start -= 1;
+    }
+    let mut end = at + 1;
+    while end < data.len() && is_domain_char(data[end]) {
+        end += 1;
+    }
+    end = trim_trailing(data, at + 1, end);
+
+    let domain = &data[at + 1..end];
+    let valid = start < at
+        && domain.contains(&b'.')
+        && domain.first().is_some_and(|b| b.is_ascii_alphanumeric());
+    valid.then_some((start, end))
+}
+
+// Drop sentence punctuation from the end of a match
+fn trim_trailing(data: &[u8], min: usize, mut end: usize) -> usize {
+    while end > min
+        && matches!(
+            data[end - 1],
+            b'.' | b',' | b';' | b':' | b')' | b']' | b'-'
+        )
+    {
+        end -= 1;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_endpoints() {
+        let data =
+            b"see https://example.com/api?v=1. mail dev@example.org, or \"ftp://x.y\" @ home";
+        let mut counts = AHashMap::new();
+        let found = count_endpoints(data, &mut counts);
+
+        assert_eq!(found, 3);
+        assert_eq!(counts.get("https://example.com/api?v=1"), Some(&1));
+        assert_eq!(counts.get("dev@example.org"), Some(&1));
+        assert_eq!(counts.get("ftp://x.y"), Some(&1));
+    }
+}
@@ -0,0 +1,262 @@
+use crate::PathEncoding;
+use crate::discovery::DiscoveryStats;
+use crate::output::encode_path;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Number of example paths kept per category in the summary
+const SUMMARY_EXAMPLES: usize = 3;
+
+// Category of a per-file failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileErrorKind {
+    PermissionDenied,
+    NotFound,
+    MmapFailed,
+    DecodeFailed,
+    Timeout,
+    Other,
+}
+
+impl FileErrorKind {
+    pub const ALL: [FileErrorKind; 6] = [
+        FileErrorKind::PermissionDenied,
+        FileErrorKind::NotFound,
+        FileErrorKind::MmapFailed,
+        FileErrorKind::DecodeFailed,
+        FileErrorKind::Timeout,
+        FileErrorKind::Other,
+    ];
+
+    // Classify an I/O error raised while opening or reading a file
+    pub fn from_io(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => FileErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound => FileErrorKind::NotFound,
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => FileErrorKind::Timeout,
+            io::ErrorKind::InvalidData => FileErrorKind::DecodeFailed,
+            _ => FileErrorKind::Other,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileErrorKind::PermissionDenied => "permission denied",
+            FileErrorKind::NotFound => "not found",
+            FileErrorKind::MmapFailed => "mmap failed",
+            FileErrorKind::DecodeFailed => "decode failed",
+            FileErrorKind::Timeout => "timeout",
+            FileErrorKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for FileErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// A failure to process a single file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileError {
+    pub path: PathBuf,
+    pub kind: FileErrorKind,
+    pub message: String,
+}
+
+impl FileError {
+    pub fn new(path: &Path, kind: FileErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            kind,
+            message: message.into(),
+        }
+    }
+
+    pub fn io(path: &Path, err: &io::Error) -> Self {
+        Self::new(path, FileErrorKind::from_io(err), err.to_string())
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): {}",
+            self.path.display(),
+            self.kind,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for FileError {}
+
+// Discovery found no files to count; carries what was rejected and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoFilesMatched {
+    pub root: PathBuf,
+    pub stats: DiscoveryStats,
+}
+
+impl fmt::Display for NoFilesMatched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no matching files found under {}", self.root.display())?;
+        if !self.root.exists() {
+            return write!(f, " (path does not exist)");
+        }
+        write!(f, " ({})", self.stats)
+    }
+}
+
+impl std::error::Error for NoFilesMatched {}
+
+// Files failed to load in a run with `ErrorPolicy::Abort`
+#[derive(Debug, Clone)]
+pub struct FilesFailed {
+    pub count: usize,
+    pub first: FileError,
+}
+
+impl fmt::Display for FilesFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} file(s) could not be read; first: {}",
+            self.count, self.first
+        )
+    }
+}
+
+impl std::error::Error for FilesFailed {}
+
+// The run was stopped through `CountHandle::cancel` or a `CancellationToken`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "count cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+// Another process holds the lock on a shared state directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locked {
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is held by another run; wait for it or retry later",
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for Locked {}
+
+// A persisted artifact was written by a newer, incompatible format version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsupportedVersion {
+    pub path: PathBuf,
+    pub kind: String,
+    pub found: u32,
+    pub supported: u32,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is a {} file in format v{}, but this build reads up to v{}; regenerate it or upgrade",
+            self.path.display(),
+            self.kind,
+            self.found,
+            self.supported
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedVersion {}
+
+// Per-category counts and example paths for a set of file errors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorSummaryRow {
+    pub kind: FileErrorKind,
+    pub count: usize,
+    pub examples: Vec<PathBuf>,
+}
+
+// Group errors by category, in the declaration order of `FileErrorKind`
+pub fn summarize_errors(errors: &[FileError]) -> Vec<ErrorSummaryRow> {
+    FileErrorKind::ALL
+        .iter()
+        .filter_map(|&kind| {
+            let matching: Vec<&FileError> = errors.iter().filter(|e| e.kind == kind).collect();
+            if matching.is_empty() {
+                return None;
+            }
+            Some(ErrorSummaryRow {
+                kind,
+                count: matching.len(),
+                examples: matching
+                    .iter()
+                    .take(SUMMARY_EXAMPLES)
+                    .map(|e| e.path.clone())
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+// Render the summary as a plain-text table
+pub fn format_error_summary(rows: &[ErrorSummaryRow], encoding: PathEncoding) -> String {
+    let total: usize = rows.iter().map(|r| r.count).sum();
+    let mut out = format!("{} file(s) failed:\n", total);
+    for row in rows {
+        let examples: Vec<_> = row
+            .examples
+            .iter()
+            .map(|p| encode_path(p, encoding))
+            .collect();
+        out.push_str(&format!(
+            "{:>18} | {:>6} | {}\n",
+            row.kind.as_str(),
+            row.count,
+            examples.join(", ")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_groups_by_kind() {
+        let errors = vec![
+            FileError::new(Path::new("a.c"), FileErrorKind::NotFound, "gone"),
+            FileError::new(Path::new("b.c"), FileErrorKind::PermissionDenied, "nope"),
+            FileError::new(Path::new("c.c"), FileErrorKind::NotFound, "gone"),
+        ];
+
+        let rows = summarize_errors(&errors);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].kind, FileErrorKind::PermissionDenied);
+        assert_eq!(rows[1].kind, FileErrorKind::NotFound);
+        assert_eq!(rows[1].count, 2);
+        assert_eq!(
+            rows[1].examples,
+            vec![PathBuf::from("a.c"), PathBuf::from("c.c")]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+// Per-extension file counts and sizes for the `filetypes` subcommand: a
+// quick overview of a tree from file metadata alone, nothing is read
+use crate::error::FileError;
+use ahash::AHashMap;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+// Files sharing one extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileTypeStats {
+    // Extension without its dot, as found (empty for files without one)
+    pub extension: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+// Group `files` by extension, looking up sizes in parallel. Files whose
+// metadata cannot be read go to `on_error` and are left out. Sorted by
+// total bytes descending, then by extension.
+pub(crate) fn tally<F>(files: &[PathBuf], on_error: F) -> Vec<FileTypeStats>
+where
+    F: Fn(FileError) + Sync,
+{
+    let totals = files
+        .par_iter()
+        .fold(
+            AHashMap::new,
+            |mut totals: AHashMap<String, (u64, u64)>, path| {
+                match std::fs::metadata(path) {
+                    Ok(meta) => {
+                        let total = totals.entry(extension(path)).or_default();
+                        total.0 += 1;
+                        total.1 += meta.len();
+                    }
+                    Err(e) => on_error(FileError::io(path, &e)),
+                }
+                totals
+            },
+        )
+        .reduce(AHashMap::new, |mut into, from| {
+            for (ext, (files, bytes)) in from {
+                let total = into.entry(ext).or_default();
+                total.0 += files;
+                total.1 += bytes;
+            }
+            into
+        });
+
+    let mut stats: Vec<FileTypeStats> = totals
+        .into_iter()
+        .map(|(extension, (files, bytes))| FileTypeStats {
+            extension,
+            files,
+            bytes,
+        })
+        .collect();
+    stats.sort_unstable_by(|a, b| {
+        b.bytes
+            .cmp(&a.bytes)
+            .then_with(|| a.extension.cmp(&b.extension))
+    });
+    stats
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Config, FastWordCounter};
+    use anyhow::Result;
+
+    #[test]
+    fn test_file_types_group_by_extension() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("a.c"), "int a;")?;
+        std::fs::write(dir.path().join("sub/b.c"), "int b;")?;
+        std::fs::write(dir.path().join("sub/notes.md"), "# a longer readme")?;
+        std::fs::write(dir.path().join("Makefile"), "all:")?;
+        std::fs::write(dir.path().join("sub/skip.log"), "ignored")?;
+
+        let counter = FastWordCounter::new(Config {
+            exclude_globs: vec!["**/*.log".to_string()],
+            show_progress: false,
+            ..Config::default()
+        });
+        let types: Vec<(String, u64, u64)> = counter
+            .file_types(dir.path())?
+            .into_iter()
+            .map(|t| (t.extension, t.files, t.bytes))
+            .collect();
+        assert_eq!(
+            types,
+            [
+                ("md".to_string(), 1, 17),
+                ("c".to_string(), 2, 12),
+                (String::new(), 1, 4)
+            ]
+        );
+        assert_eq!(counter.discovery_stats().filtered_by_glob, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+// Word/count pairs for the `fold` subcommand, which merges the outputs of
+// several runs, e.g. as the reduce step of a shell map-reduce. Each line is
+// either TSV (`word<TAB>count`) or an NDJSON object
+// (`{"word": "int", "count": 3}`); blank lines are skipped.
+use crate::accumulate::Accumulate;
+use ahash::AHashMap;
+use anyhow::{Result, anyhow};
+use rayon::prelude::*;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Layout of pairs written by `fold`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PairFormat {
+    #[default]
+    Tsv,
+    Ndjson,
+}
+
+impl std::str::FromStr for PairFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tsv" => Ok(PairFormat::Tsv),
+            "ndjson" | "json" => Ok(PairFormat::Ndjson),
+            other => Err(format!(
+                "unknown pair format '{}' (expected tsv or ndjson)",
+                other
+            )),
+        }
+    }
+}
+
+// Write one pair in `format`, readable again by `fold`
+pub fn write_pair<W: Write>(
+    writer: &mut W,
+    format: PairFormat,
+    word: &str,
+    count: u64,
+) -> io::Result<()> {
+    match format {
+        PairFormat::Tsv => writeln!(writer, "{}\t{}", word, count),
+        PairFormat::Ndjson => writeln!(
+            writer,
+            "{}",
+            serde_json::json!({ "word": word, "count": count })
+        ),
+    }
+}
+
+// Parse `data` in line-aligned chunks of about `chunk_bytes`, one map per
+// chunk, ready for the counter's merge. Counts for a word repeated within a
+// chunk saturate at u64::MAX, bumping `saturated`.
+pub(crate) fn parse_chunks(
+    data: &[u8],
+    chunk_bytes: usize,
+    saturated: &AtomicU64,
+) -> Result<Vec<AHashMap<String, u64>>> {
+    // Number the first line of each chunk so errors can point at the input
+    let mut first_line = 1;
+    let chunks: Vec<(Range<usize>, usize)> = line_chunks(data, chunk_bytes)
+        .into_iter()
+        .map(|range| {
+            let numbered = (range.clone(), first_line);
+            first_line += data[range].iter().filter(|&&b| b == b'\n').count();
+            numbered
+        })
+        .collect();
+
+    chunks
+        .into_par_iter()
+        .map(|(range, first_line)| {
+            let mut counts = AHashMap::new();
+            for (number, line) in (first_line..).zip(data[range].split(|&b| b == b'\n')) {
+                let Some((word, count)) =
+                    parse_line(line).map_err(|e| anyhow!("fold input line {}: {}", number, e))?
+                else {
+                    continue;
+                };
+                if counts.entry(word).or_insert(0u64).merge(count) {
+                    saturated.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Ok(counts)
+        })
+        .collect()
+}
+
+// Split `data` after a newline roughly every `chunk_bytes`
+fn line_chunks(data: &[u8], chunk_bytes: usize) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + chunk_bytes.max(1)).min(data.len());
+        let end = match data[end - 1..].iter().position(|&b| b == b'\n') {
+            Some(newline) => end + newline,
+            None => data.len(),
+        };
+        chunks.push(start..end);
+        start = end;
+    }
+    chunks
+}
+
+// One pair, or None for a blank line
+fn parse_line(line: &[u8]) -> Result<Option<(String, u64)>, String> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    if line.iter().all(u8::is_ascii_whitespace) {
+        return Ok(None);
+    }
+    if line.trim_ascii_start().starts_with(b"{") {
+        let value: serde_json::Value =
+            serde_json::from_slice(line).map_err(|e| format!("invalid JSON: {}", e))?;
+        let word = value["word"]
+            .as_str()
+            .ok_or("expected a string \"word\" field")?;
+        let count = value["count"]
+            .as_u64()
+            .ok_or("expected a non-negative integer \"count\" field")?;
+        return Ok(Some((word.to_string(), count)));
+    }
+
+    let line = std::str::from_utf8(line).map_err(|_| "not valid UTF-8".to_string())?;
+    let (word, count) = line
+        .rsplit_once('\t')
+        .ok_or("expected word<TAB>count or a JSON object")?;
+    let count = count
+        .trim()
+        .parse()
+        .map_err(|_| format!("bad count '{}'", count.trim()))?;
+    Ok(Some((word.to_string(), count)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, FastWordCounter};
+
+    fn counter() -> FastWordCounter {
+        FastWordCounter::new(Config {
+            parallel_merge: true,
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn test_fold_merges_tsv_and_ndjson() -> Result<()> {
+        let input = "int\t3\nchar\t1\n\n{\"word\": \"int\", \"count\": 2}\r\nx\t18446744073709551615\nx\t1\n";
+        let results = counter().fold_reader(input.as_bytes())?;
+        assert_eq!(
+            results,
+            [
+                ("x".to_string(), u64::MAX),
+                ("int".to_string(), 5),
+                ("char".to_string(), 1)
+            ]
+        );
+
+        // Chunking never changes the result
+        let saturated = AtomicU64::new(0);
+        for chunk in [1, 5, 16, 1024] {
+            let maps = parse_chunks(input.as_bytes(), chunk, &saturated)?;
+            let total: u64 = maps.iter().filter_map(|m| m.get("int")).sum();
+            assert_eq!(total, 5, "chunk {}", chunk);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_fold_reports_bad_lines() {
+        let err = counter()
+            .fold_reader("int\t3\nchar 1\n".as_bytes())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "fold input line 2: expected word<TAB>count or a JSON object"
+        );
+        let err = counter()
+            .fold_reader("{\"word\": \"a\", \"count\": -1}\n".as_bytes())
+            .unwrap_err();
+        assert!(err.to_string().contains("non-negative"));
+
+        let mut out = Vec::new();
+        write_pair(&mut out, PairFormat::Ndjson, "a\"b", 2).unwrap();
+        assert_eq!(out, b"{\"count\":2,\"word\":\"a\\\"b\"}\n");
+    }
+}
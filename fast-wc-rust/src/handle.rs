@@ -0,0 +1,208 @@
+use crate::error::Cancelled;
+use crate::{FastWordCounter, Stats};
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+
+// Snapshot of a background count's progress
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    // Files matched by discovery; 0 until discovery completes
+    pub files_total: u64,
+    pub files_processed: u64,
+    pub bytes_processed: u64,
+    pub tokens_processed: u64,
+    // HyperLogLog estimate of the distinct words seen so far, within a few
+    // percent; the exact figure is `FastWordCounter::unique_words` after
+    // the run
+    pub unique_words_estimate: u64,
+}
+
+// Shared flag for stopping a run from another thread, from
+// `FastWordCounter::cancellation_token`
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+// Whether a run counted every file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    Complete,
+    // Stopped early; the results cover only the files and chunks finished
+    Cancelled,
+}
+
+// Results of `FastWordCounter::count_directory_partial`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialCount {
+    pub results: Vec<(String, u64)>,
+    pub status: RunStatus,
+}
+
+// A word count running on background threads, from
+// `FastWordCounter::spawn_count`
+pub struct CountHandle {
+    thread: JoinHandle<Result<Vec<(String, u64)>>>,
+    stats: Arc<Stats>,
+}
+
+impl CountHandle {
+    pub(crate) fn spawn(counter: FastWordCounter, dir: PathBuf) -> Self {
+        let stats = Arc::clone(&counter.stats);
+        stats.cancelled.reset();
+        let thread = std::thread::spawn(move || counter.count_directory(&dir));
+        Self { thread, stats }
+    }
+
+    pub fn progress(&self) -> Progress {
+        progress(&self.stats)
+    }
+
+    // Ask the workers to stop after their current file; `join` then fails
+    // with `Cancelled`
+    pub fn cancel(&self) {
+        self.stats.cancelled.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    // Wait for the count to finish and return its results
+    pub fn join(self) -> Result<Vec<(String, u64)>> {
+        self.thread
+            .join()
+            .map_err(|_| anyhow!("background count panicked"))?
+    }
+}
+
+pub(crate) fn progress(stats: &Stats) -> Progress {
+    Progress {
+        files_total: stats.discovery.lock().unwrap().files_matched,
+        files_processed: stats.files_processed.load(Ordering::Relaxed),
+        bytes_processed: stats.bytes_processed.load(Ordering::Relaxed),
+        tokens_processed: stats.tokens_processed.load(Ordering::Relaxed),
+        unique_words_estimate: stats.unique_estimate.estimate(),
+    }
+}
+
+// Whether the run sharing `stats` was cancelled
+pub(crate) fn check_cancelled(stats: &Stats) -> Result<()> {
+    if stats.cancelled.is_cancelled() {
+        return Err(Cancelled.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, TokenTransform};
+    use ahash::AHashMap;
+    use std::borrow::Cow;
+    use std::sync::OnceLock;
+    use std::time::{Duration, Instant};
+
+    fn quiet() -> Config {
+        Config {
+            show_progress: false,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_join_and_cancel() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        for i in 0..4 {
+            std::fs::write(dir.path().join(format!("{}.c", i)), "int x;")?;
+        }
+
+        let counter = FastWordCounter::new(quiet());
+        let handle = counter.spawn_count(dir.path());
+        let results = handle.join()?;
+        assert_eq!(results[0], ("int".to_string(), 4));
+        assert_eq!(counter.stats.files_processed.load(Ordering::Relaxed), 4);
+        let progress = counter.progress();
+        assert_eq!(progress.tokens_processed, 8);
+        assert_eq!(progress.unique_words_estimate, 2);
+
+        // Cancelled before the workers start, so no file is scanned
+        let counter = FastWordCounter::new(quiet());
+        counter.cancellation_token().cancel();
+        let err = counter.count_directory(dir.path()).unwrap_err();
+        assert!(err.downcast_ref::<Cancelled>().is_some());
+        assert_eq!(counter.stats.files_processed.load(Ordering::Relaxed), 0);
+
+        // The partial variant reports the cancellation instead of failing
+        let partial = counter.count_directory_partial(dir.path())?;
+        assert_eq!(partial.status, RunStatus::Cancelled);
+        assert!(partial.results.is_empty());
+
+        let counter = FastWordCounter::new(quiet());
+        let partial = counter.count_directory_partial(dir.path())?;
+        assert_eq!(partial.status, RunStatus::Complete);
+        assert_eq!(partial.results[0], ("int".to_string(), 4));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_mid_run_returns_a_subset() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        for i in 0..200 {
+            let text = if i == 10 {
+                "int stop;"
+            } else {
+                "int x; int y;"
+            };
+            std::fs::write(dir.path().join(format!("{:03}.c", i)), text)?;
+        }
+        let full = FastWordCounter::new(quiet()).count_directory(dir.path())?;
+
+        // Cancel from a worker once it reaches the eleventh file (in path
+        // order, per `stable`)
+        let token: Arc<OnceLock<CancellationToken>> = Arc::default();
+        let trigger = Arc::clone(&token);
+        let counter = FastWordCounter::new(Config {
+            num_threads: 2,
+            stable: true,
+            transform: Some(TokenTransform::new(move |word| {
+                if word == "stop" {
+                    trigger.get().unwrap().cancel();
+                }
+                Some(Cow::Borrowed(word))
+            })),
+            ..quiet()
+        });
+        token.set(counter.cancellation_token()).unwrap();
+
+        let start = Instant::now();
+        let partial = counter.count_directory_partial(dir.path())?;
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(partial.status, RunStatus::Cancelled);
+        let processed = counter.progress().files_processed;
+        assert!((11..200).contains(&processed), "{} files", processed);
+
+        let full: AHashMap<_, _> = full.into_iter().collect();
+        for (word, count) in &partial.results {
+            assert!(full.get(word).is_some_and(|total| count <= total));
+        }
+        let tokens = |results: &[(String, u64)]| results.iter().map(|(_, c)| c).sum::<u64>();
+        assert!(tokens(&partial.results) < full.values().sum::<u64>());
+        Ok(())
+    }
+}
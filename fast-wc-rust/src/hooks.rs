@@ -0,0 +1,138 @@
+use crate::Logger;
+use anyhow::{Context, Result, bail};
+use serde_json::json;
+use std::process::Command;
+
+// Shell commands run around a counting run, from the config file's
+// `[hooks]` table:
+//
+//   [hooks]
+//   pre = ["git fetch"]
+//   post = "scripts/upload.sh"
+//
+// `pre` commands run before discovery and `post` commands after results are
+// printed. Commands run in order through the shell; the first failure aborts
+// the run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub pre: Vec<String>,
+    pub post: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Pre,
+    Post,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::Pre => "pre",
+            Stage::Post => "post",
+        }
+    }
+}
+
+impl Hooks {
+    // Parse a `[hooks]` table; each stage is a command string or an array
+    // of them
+    pub fn from_table(table: &toml::Table) -> Result<Self> {
+        let mut hooks = Hooks::default();
+        for (key, value) in table {
+            let commands = match value {
+                toml::Value::String(s) => vec![s.clone()],
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| {
+                        item.as_str()
+                            .map(str::to_string)
+                            .ok_or_else(|| anyhow::anyhow!("hooks.{} entries must be strings", key))
+                    })
+                    .collect::<Result<_>>()?,
+                _ => bail!("hooks.{} must be a string or an array of strings", key),
+            };
+            match key.as_str() {
+                "pre" => hooks.pre = commands,
+                "post" => hooks.post = commands,
+                _ => bail!("unknown hook '{}' (expected pre or post)", key),
+            }
+        }
+        Ok(hooks)
+    }
+
+    pub fn commands(&self, stage: Stage) -> &[String] {
+        match stage {
+            Stage::Pre => &self.pre,
+            Stage::Post => &self.post,
+        }
+    }
+
+    // Run every command for `stage`, failing on the first one that cannot
+    // be started or exits unsuccessfully. Each command is logged when a
+    // logger is given.
+    pub fn run(&self, stage: Stage, logger: Option<&Logger>) -> Result<()> {
+        for command in self.commands(stage) {
+            if let Some(logger) = logger {
+                logger.info(
+                    "hook",
+                    &format!("Running {} hook: {}", stage.as_str(), command),
+                    &[
+                        ("stage", json!(stage.as_str())),
+                        ("command", json!(command)),
+                    ],
+                );
+            }
+            let status = shell(command).status().with_context(|| {
+                format!("Failed to start {} hook `{}`", stage.as_str(), command)
+            })?;
+            if !status.success() {
+                bail!("{} hook `{}` failed ({})", stage.as_str(), command, status);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.args(["/C", command]);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.args(["-c", command]);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_table_accepts_string_or_array() -> Result<()> {
+        let table: toml::Table = "pre = [\"git fetch\", \"make\"]\npost = \"upload\"\n".parse()?;
+        let hooks = Hooks::from_table(&table)?;
+        assert_eq!(hooks.pre, vec!["git fetch", "make"]);
+        assert_eq!(hooks.post, vec!["upload"]);
+
+        let bad: toml::Table = "during = \"x\"\n".parse()?;
+        assert!(Hooks::from_table(&bad).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_hook_aborts() {
+        let hooks = Hooks {
+            pre: vec!["true".into(), "exit 3".into()],
+            post: vec![],
+        };
+        let err = hooks.run(Stage::Pre, None).unwrap_err();
+        assert!(err.to_string().contains("exit 3"));
+        assert!(hooks.run(Stage::Post, None).is_ok());
+    }
+}
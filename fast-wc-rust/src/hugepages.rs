@@ -0,0 +1,96 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Allocations at least this large are huge-page backed: a 2 MiB huge page
+// is the smallest unit the kernel can promote
+pub const HUGE_PAGE_THRESHOLD: usize = 2 * 1024 * 1024;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+// Global allocator that backs large allocations (the counting maps' tables
+// once vocabularies grow past a few hundred thousand words) with anonymous
+// mappings advised as MADV_HUGEPAGE, cutting TLB misses on random probes.
+// Smaller allocations go to the system allocator unchanged. Install with
+// `#[global_allocator]`; transparent huge pages must be enabled in `madvise`
+// or `always` mode for the advice to take effect
+pub struct HugePageAlloc;
+
+impl HugePageAlloc {
+    // Toggle the huge-page advice for subsequent large allocations (e.g. to
+    // compare both in one benchmark binary); they are mmap-backed either way
+    pub fn set_enabled(enabled: bool) {
+        ENABLED.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    // Mappings are page aligned, so larger alignments stay with System
+    fn is_huge(layout: Layout) -> bool {
+        layout.size() >= HUGE_PAGE_THRESHOLD && layout.align() <= page_size()
+    }
+}
+
+fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+unsafe impl GlobalAlloc for HugePageAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !Self::is_huge(layout) {
+            return unsafe { System.alloc(layout) };
+        }
+        unsafe {
+            let mapping = libc::mmap(
+                ptr::null_mut(),
+                layout.size(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if mapping == libc::MAP_FAILED {
+                return ptr::null_mut();
+            }
+            // Advisory only: without THP support the mapping still works
+            if Self::enabled() {
+                libc::madvise(mapping, layout.size(), libc::MADV_HUGEPAGE);
+            }
+            mapping.cast()
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if Self::is_huge(layout) {
+            unsafe { libc::munmap(ptr.cast(), layout.size()) };
+        } else {
+            unsafe { System.dealloc(ptr, layout) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_large_allocations_are_mapped() {
+        let alloc = HugePageAlloc;
+        let layout = Layout::from_size_align(HUGE_PAGE_THRESHOLD * 2, 8).unwrap();
+        unsafe {
+            let block = alloc.alloc(layout);
+            assert!(!block.is_null());
+            assert_eq!(block as usize % page_size(), 0);
+            block.write_bytes(0xAB, layout.size());
+            assert_eq!(*block.add(layout.size() - 1), 0xAB);
+            alloc.dealloc(block, layout);
+
+            let small = Layout::from_size_align(64, 8).unwrap();
+            let block = alloc.alloc(small);
+            assert!(!block.is_null());
+            alloc.dealloc(block, small);
+        }
+    }
+}
@@ -0,0 +1,295 @@
+use crate::error::{FileError, FileErrorKind};
+use crate::{Config, IoMode};
+use memmap2::{Mmap, MmapOptions};
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+
+// O_DIRECT transfers must be aligned to the device's logical block size;
+// 4 KiB covers every common SSD
+const DIRECT_ALIGN: usize = 4096;
+// Bytes requested per direct read (a multiple of DIRECT_ALIGN)
+const DIRECT_CHUNK: usize = 1024 * 1024;
+// Spare small-file buffers kept per thread
+const POOL_BUFFERS: usize = 4;
+
+thread_local! {
+    // Buffers for small files, reused across files on the same worker
+    static POOL: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+}
+
+// File contents, either memory-mapped or read into memory. `Pooled`
+// buffers return to the thread's pool when dropped.
+pub(crate) enum FileData {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+    Pooled(Vec<u8>),
+}
+
+impl Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Mapped(mmap) => mmap,
+            FileData::Owned(bytes) | FileData::Pooled(bytes) => bytes,
+        }
+    }
+}
+
+impl Drop for FileData {
+    fn drop(&mut self) {
+        if let FileData::Pooled(bytes) = self {
+            let mut buffer = std::mem::take(bytes);
+            buffer.clear();
+            POOL.with_borrow_mut(|pool| {
+                if pool.len() < POOL_BUFFERS {
+                    pool.push(buffer);
+                }
+            });
+        }
+    }
+}
+
+// Extensions of compressed files, which discovery matches by the extension
+// underneath (`foo.c.gz` is a `.c` file)
+pub(crate) const COMPRESSED_EXTENSIONS: [&str; 2] = ["gz", "zst"];
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// Load a file's bytes the way `config` asks: O_DIRECT reads for
+// `IoMode::Direct`, otherwise mmap or a plain read per `use_mmap`. With
+// `decompress`, gzip and zstd contents are inflated in memory.
+pub(crate) fn load(path: &Path, config: &Config) -> Result<FileData, FileError> {
+    let data = load_raw(path, config)?;
+    if !config.decompress {
+        return Ok(data);
+    }
+    match decompress(&data) {
+        Some(Ok(inflated)) => Ok(FileData::Owned(inflated)),
+        Some(Err(e)) => Err(FileError::new(
+            path,
+            FileErrorKind::DecodeFailed,
+            format!("decompression failed: {}", e),
+        )),
+        None => Ok(data),
+    }
+}
+
+// Inflate `data` if it starts with a gzip or zstd magic number; None if it
+// is not compressed. Concatenated gzip members and zstd frames are all read.
+pub(crate) fn decompress(data: &[u8]) -> Option<std::io::Result<Vec<u8>>> {
+    let mut inflated = Vec::new();
+    let result = if data.starts_with(GZIP_MAGIC) {
+        flate2::read::MultiGzDecoder::new(data).read_to_end(&mut inflated)
+    } else if data.starts_with(ZSTD_MAGIC) {
+        zstd::stream::read::Decoder::new(data).and_then(|mut d| d.read_to_end(&mut inflated))
+    } else {
+        return None;
+    };
+    Some(result.map(|_| inflated))
+}
+
+fn load_raw(path: &Path, config: &Config) -> Result<FileData, FileError> {
+    match config.io_mode {
+        IoMode::Direct => read_direct(path).map(FileData::Owned),
+        IoMode::Cached if config.use_mmap => map(path, config.prefault, config.small_file_bytes),
+        IoMode::Cached => std::fs::read(path)
+            .map(FileData::Owned)
+            .map_err(|e| FileError::io(path, &e)),
+    }
+}
+
+// Memory-map a file. With `prefault`, pages are faulted in up front
+// (MAP_POPULATE on Linux, a WILLNEED hint on other Unixes) instead of during
+// tokenization. Files of at most `small_file_bytes` are read into a pooled
+// buffer instead: for them the mmap/munmap syscalls and page faults cost
+// more than copying. Empty files are never mapped (a zero-length mapping is
+// an error on Windows and pointless elsewhere).
+fn map(path: &Path, prefault: bool, small_file_bytes: usize) -> Result<FileData, FileError> {
+    let mut file = File::open(path).map_err(|e| FileError::io(path, &e))?;
+    let len = file.metadata().map(|meta| meta.len()).ok();
+    if len == Some(0) {
+        return Ok(FileData::Owned(Vec::new()));
+    }
+    if let Some(len) = len
+        && len <= small_file_bytes as u64
+    {
+        let mut buffer = POOL.with_borrow_mut(|pool| pool.pop()).unwrap_or_default();
+        buffer.reserve(len as usize);
+        // Wrap first so the buffer goes back to the pool on error too
+        let mut data = FileData::Pooled(buffer);
+        if let FileData::Pooled(buffer) = &mut data {
+            file.read_to_end(buffer)
+                .map_err(|e| FileError::io(path, &e))?;
+        }
+        return Ok(data);
+    }
+    let mut options = MmapOptions::new();
+    if prefault {
+        options.populate();
+    }
+    let mmap = match unsafe { options.map(&file) } {
+        Ok(mmap) => mmap,
+        // Windows refuses to map files locked by other processes (and some
+        // network shares); read those instead of failing them
+        #[cfg(windows)]
+        Err(_) => {
+            return std::fs::read(path)
+                .map(FileData::Owned)
+                .map_err(|e| FileError::io(path, &e));
+        }
+        #[cfg(not(windows))]
+        Err(e) => {
+            return Err(FileError::new(
+                path,
+                FileErrorKind::MmapFailed,
+                e.to_string(),
+            ));
+        }
+    };
+
+    #[cfg(all(unix, not(target_os = "linux")))]
+    if prefault {
+        let _ = mmap.advise(memmap2::Advice::WillNeed);
+    }
+
+    Ok(FileData::Mapped(mmap))
+}
+
+// Read a file bypassing the page cache (O_DIRECT) through an aligned bounce
+// buffer. Filesystems that refuse O_DIRECT (tmpfs, some overlays) and
+// non-Linux targets fall back to a plain read.
+fn read_direct(path: &Path) -> Result<Vec<u8>, FileError> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path);
+        if let Ok(mut file) = file {
+            let mut buffer = AlignedBuffer::new(DIRECT_CHUNK);
+            let mut contents = Vec::new();
+            loop {
+                match file.read(buffer.as_mut_slice()) {
+                    Ok(0) => return Ok(contents),
+                    Ok(n) => contents.extend_from_slice(&buffer.as_mut_slice()[..n]),
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) if e.raw_os_error() == Some(libc::EINVAL) => break,
+                    Err(e) => return Err(FileError::io(path, &e)),
+                }
+            }
+        }
+    }
+
+    std::fs::read(path).map_err(|e| FileError::io(path, &e))
+}
+
+// Heap buffer aligned to DIRECT_ALIGN, as O_DIRECT requires
+#[cfg(target_os = "linux")]
+struct AlignedBuffer {
+    ptr: std::ptr::NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+#[cfg(target_os = "linux")]
+impl AlignedBuffer {
+    fn new(len: usize) -> Self {
+        let layout = std::alloc::Layout::from_size_align(len, DIRECT_ALIGN)
+            .expect("direct I/O buffer layout");
+        let ptr = unsafe { std::alloc::alloc_zeroed(layout) };
+        let ptr =
+            std::ptr::NonNull::new(ptr).unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        Self { ptr, layout }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_compressed_files_are_inflated() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let text = b"int main(void) { return 0; }\n".repeat(100);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&text)?;
+        std::fs::write(dir.path().join("a.c.gz"), gz.finish()?)?;
+        // Magic bytes are checked, not the name
+        std::fs::write(
+            dir.path().join("b.c"),
+            zstd::encode_all(text.as_slice(), 0)?,
+        )?;
+        std::fs::write(dir.path().join("bad.c.gz"), b"\x1f\x8bnot gzip")?;
+
+        let config = Config::default();
+        assert_eq!(
+            &*load(&dir.path().join("a.c.gz"), &config)?,
+            text.as_slice()
+        );
+        assert_eq!(&*load(&dir.path().join("b.c"), &config)?, text.as_slice());
+        let bad = load(&dir.path().join("bad.c.gz"), &config);
+        assert!(bad.is_err_and(|e| e.kind == FileErrorKind::DecodeFailed));
+
+        let raw = Config {
+            decompress: false,
+            ..Config::default()
+        };
+        assert_ne!(&*load(&dir.path().join("b.c"), &raw)?, text.as_slice());
+        Ok(())
+    }
+
+    #[test]
+    fn test_direct_read_matches_buffered() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("odd.c");
+        // Not a multiple of the block size, and spanning several chunks
+        let contents: Vec<u8> = (0..DIRECT_CHUNK * 2 + 123)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        std::fs::write(&path, &contents)?;
+
+        let config = Config {
+            io_mode: IoMode::Direct,
+            ..Config::default()
+        };
+        assert_eq!(&*load(&path, &config)?, contents.as_slice());
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_files_use_pooled_buffers() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let small = dir.path().join("small.c");
+        let large = dir.path().join("large.c");
+        std::fs::write(&small, "int x;")?;
+        std::fs::write(&large, vec![b'a'; 64])?;
+
+        let data = map(&small, false, 16)?;
+        assert!(matches!(data, FileData::Pooled(_)));
+        assert_eq!(&*data, b"int x;");
+        drop(data);
+        assert_eq!(POOL.with_borrow(|pool| pool.len()), 1);
+
+        assert!(matches!(map(&large, false, 16)?, FileData::Mapped(_)));
+        Ok(())
+    }
+}
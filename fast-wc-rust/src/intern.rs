@@ -0,0 +1,178 @@
+use ahash::AHashMap;
+use std::sync::{Arc, RwLock};
+
+// log2 of the shard count; the low bits of a symbol ID name its shard
+const SHARD_BITS: u32 = 6;
+const SHARDS: usize = 1 << SHARD_BITS;
+
+// ID of an interned word, unique within its `SymbolTable`
+pub type Symbol = u32;
+
+#[derive(Default)]
+struct Shard {
+    ids: AHashMap<Arc<str>, Symbol>,
+    words: Vec<Arc<str>>,
+}
+
+// Sharded, thread-safe interner: every distinct word is stored once and
+// named by a `u32`. Lookups of known words take only a read lock on one of
+// 64 shards, so workers rarely contend.
+pub struct SymbolTable {
+    hasher: ahash::RandomState,
+    shards: Vec<RwLock<Shard>>,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            hasher: ahash::RandomState::new(),
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+        }
+    }
+
+    // The symbol for `word`, interning it on first sight
+    pub fn intern(&self, word: &str) -> Symbol {
+        let shard_index = self.shard_of(word);
+        let shard = &self.shards[shard_index];
+        if let Some(&id) = shard.read().unwrap().ids.get(word) {
+            return id;
+        }
+
+        let mut shard = shard.write().unwrap();
+        if let Some(&id) = shard.ids.get(word) {
+            return id;
+        }
+        let local = u32::try_from(shard.words.len())
+            .ok()
+            .filter(|&n| n < u32::MAX >> SHARD_BITS)
+            .expect("symbol table shard overflow");
+        let id = (local << SHARD_BITS) | shard_index as u32;
+        let word: Arc<str> = Arc::from(word);
+        shard.words.push(Arc::clone(&word));
+        shard.ids.insert(word, id);
+        id
+    }
+
+    // The word for `id`, if it was interned here
+    pub fn resolve(&self, id: Symbol) -> Option<Arc<str>> {
+        let shard = self.shards[id as usize & (SHARDS - 1)].read().unwrap();
+        shard.words.get((id >> SHARD_BITS) as usize).cloned()
+    }
+
+    // The symbol for `word` if it was interned, without interning it
+    pub fn get(&self, word: &str) -> Option<Symbol> {
+        let shard = self.shards[self.shard_of(word)].read().unwrap();
+        shard.ids.get(word).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| s.read().unwrap().words.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn shard_of(&self, word: &str) -> usize {
+        self.hasher.hash_one(word) as usize & (SHARDS - 1)
+    }
+}
+
+// Counts keyed by symbol, with the table that names them. Entries are
+// sorted by count descending, then alphabetically.
+pub struct CountResult {
+    entries: Vec<(Symbol, u64)>,
+    symbols: Arc<SymbolTable>,
+}
+
+impl CountResult {
+    pub fn new(counts: AHashMap<Symbol, u64>, symbols: Arc<SymbolTable>) -> Self {
+        let mut keyed: Vec<_> = counts
+            .into_iter()
+            .map(|(id, count)| (symbols.resolve(id).unwrap_or_default(), id, count))
+            .collect();
+        keyed.sort_unstable_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        Self {
+            entries: keyed
+                .into_iter()
+                .map(|(_, id, count)| (id, count))
+                .collect(),
+            symbols,
+        }
+    }
+
+    // `(symbol, count)` pairs in result order
+    pub fn entries(&self) -> &[(Symbol, u64)] {
+        &self.entries
+    }
+
+    // The table mapping symbols back to words
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    // Count for `word`, looked up through the symbol table
+    pub fn get(&self, word: &str) -> Option<u64> {
+        let id = self.symbols.get(word)?;
+        self.entries
+            .iter()
+            .find(|&&(entry, _)| entry == id)
+            .map(|&(_, count)| count)
+    }
+
+    // Resolve every symbol, e.g. for printing
+    pub fn to_words(&self) -> Vec<(String, u64)> {
+        self.entries
+            .iter()
+            .map(|&(id, count)| {
+                let word = self.symbols.resolve(id).unwrap_or_default();
+                (word.to_string(), count)
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_is_stable_across_threads() {
+        let table = SymbolTable::new();
+        let ids: Vec<Vec<Symbol>> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|_| {
+                    s.spawn(|| {
+                        ["int", "main", "x", "int"]
+                            .map(|w| table.intern(w))
+                            .to_vec()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert!(ids.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(ids[0][0], ids[0][3]);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.resolve(ids[0][1]).as_deref(), Some("main"));
+        assert_eq!(table.get("absent"), None);
+    }
+}
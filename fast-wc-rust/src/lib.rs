@@ -1,15 +1,143 @@
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::{Context, Result};
-use crossbeam::channel::bounded;
-use memmap2::Mmap;
+use crossbeam::channel::{Receiver, bounded};
 use rayon::prelude::*;
-use std::fs::File;
+use serde_json::json;
+use std::borrow::Cow;
+use std::collections::BinaryHeap;
+use std::io::{BufWriter, Read, Write};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use walkdir::WalkDir;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use unicode_segmentation::UnicodeSegmentation;
 
-const TOKEN_CHARS: [bool; 256] = {
+pub mod accumulate;
+pub mod aliases;
+pub mod archive;
+pub mod artifact;
+#[cfg(feature = "testing")]
+pub mod baseline;
+pub mod boundary;
+pub mod builder;
+pub mod cache;
+pub mod casing;
+pub mod classify;
+pub mod code;
+pub mod complexity;
+pub mod corpus;
+pub mod discovery;
+pub mod endpoints;
+pub mod error;
+pub mod filetypes;
+pub mod fold;
+pub mod handle;
+pub mod hooks;
+#[cfg(feature = "huge-pages")]
+pub mod hugepages;
+mod input;
+pub mod intern;
+pub mod keywords;
+pub mod license;
+pub mod lines;
+pub mod literal;
+pub mod lock;
+pub mod log;
+pub mod merge;
+#[cfg(feature = "native-plugins")]
+pub mod native;
+pub mod operators;
+pub mod output;
+pub mod ownership;
+pub mod pattern;
+#[cfg(feature = "wasm")]
+pub mod plugin;
+pub mod profile;
+pub mod report;
+pub mod rpc;
+pub mod sample;
+pub mod seen;
+pub mod session;
+pub mod settings;
+pub mod sketch;
+pub mod snapshot;
+pub mod snapshot_file;
+pub mod spill;
+pub mod subtoken;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod tune;
+pub mod watch;
+pub mod wc;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+pub use accumulate::Accumulate;
+pub use builder::CounterBuilder;
+pub use casing::{Casing, CasingReport};
+pub use classify::{ClassifiedCounts, TokenCategory};
+pub use complexity::BranchStats;
+pub use discovery::DiscoveryStats;
+pub use error::{
+    Cancelled, ErrorSummaryRow, FileError, FileErrorKind, FilesFailed, Locked, NoFilesMatched,
+    UnsupportedVersion,
+};
+pub use filetypes::FileTypeStats;
+pub use handle::{CancellationToken, CountHandle, PartialCount, Progress, RunStatus};
+#[cfg(feature = "huge-pages")]
+pub use hugepages::HugePageAlloc;
+pub use intern::{CountResult, Symbol, SymbolTable};
+pub use license::LicenseReport;
+pub use lines::LineStats;
+pub use literal::LiteralMatcher;
+pub use lock::DirLock;
+pub use log::{LogFormat, Logger};
+#[cfg(feature = "native-plugins")]
+pub use native::NativePlugin;
+pub use output::AtomicFile;
+pub use ownership::Ownership;
+pub use pattern::PatternReport;
+#[cfg(feature = "wasm")]
+pub use plugin::WasmPlugin;
+pub use profile::ProfileReport;
+pub use report::{CaseGroup, CountReport, FrequencyBucket, WordChange, WordShare};
+pub use sample::{Occurrence, Reservoir};
+pub use seen::{SeenFiles, SeenOrder};
+pub use session::Session;
+pub use settings::Settings;
+pub use sketch::{OTHER_BUCKET, SpaceSaving, UniqueEstimate};
+pub use snapshot::{CountEvent, Snapshot, SnapshotStore};
+pub use tune::Tuning;
+pub use watch::IncrementalCounts;
+pub use wc::{WcCounts, WcReport};
+
+// Shards per worker thread for the partitioned parallel merge
+const MERGE_SHARDS_PER_THREAD: usize = 4;
+
+// Default for `Config::use_mmap`. Mapping is slower than a plain read on
+// Windows (section objects, and mapping fails while another process holds a
+// conflicting lock), so it is off there
+pub const DEFAULT_USE_MMAP: bool = cfg!(not(windows));
+
+// Default for `Config::small_file_bytes`; most files in a typical C tree
+// fall under it
+pub const DEFAULT_SMALL_FILE_BYTES: usize = 16 * 1024;
+
+// Default for `Config::chunk_bytes`
+pub const DEFAULT_CHUNK_BYTES: usize = 64 * 1024 * 1024;
+
+// Input parsed per task by `fold_reader`; small enough to spread a few
+// hundred MB of pairs over every thread
+const FOLD_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+// Leading bytes searched for a NUL by `Config::skip_binary` (as git does)
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+// Byte classification table: `true` for bytes that belong to a token
+pub type TokenTable = [bool; 256];
+
+pub const TOKEN_CHARS: TokenTable = {
     let mut chars = [false; 256];
     let valid = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
     let mut i = 0;
@@ -25,22 +153,646 @@ pub fn is_token_char(c: u8) -> bool {
     TOKEN_CHARS[c as usize]
 }
 
-// Configuration for the word counter
+// Parse a character-class spec such as "a-zA-Z0-9_-" into a token table.
+// Ranges are written `x-y`; a `-` at either end (or `\-`) is literal, and
+// `\\` is a literal backslash.
+pub fn parse_token_chars(spec: &str) -> Result<TokenTable> {
+    let bytes = spec.as_bytes();
+    let mut table = [false; 256];
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let mut start = bytes[i];
+        if start == b'\\' {
+            i += 1;
+            start = *bytes
+                .get(i)
+                .ok_or_else(|| anyhow::anyhow!("dangling escape in token chars '{}'", spec))?;
+        }
+
+        if bytes.get(i + 1) == Some(&b'-') && i + 2 < bytes.len() {
+            let end = bytes[i + 2];
+            if end < start {
+                anyhow::bail!(
+                    "invalid range '{}-{}' in token chars",
+                    start as char,
+                    end as char
+                );
+            }
+            for b in start..=end {
+                table[b as usize] = true;
+            }
+            i += 3;
+        } else {
+            table[start as usize] = true;
+            i += 1;
+        }
+    }
+
+    if !table.iter().any(|&b| b) {
+        anyhow::bail!("token chars '{}' selects no characters", spec);
+    }
+    Ok(table)
+}
+
+// How `extract_words` splits input into tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TokenMode {
+    // Runs of bytes in the token table (ASCII `[A-Za-z0-9_]` by default)
+    #[default]
+    Bytes,
+    // UAX #29 word boundaries over UTF-8 (invalid sequences are replaced),
+    // for non-English comments and docs
+    Unicode,
+}
+
+impl std::str::FromStr for TokenMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "bytes" => Ok(TokenMode::Bytes),
+            "unicode" => Ok(TokenMode::Unicode),
+            other => Err(format!(
+                "unknown token mode '{}' (expected bytes or unicode)",
+                other
+            )),
+        }
+    }
+}
+
+// Whether the names of discovered files and directories are tokenized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathWords {
+    // File contents only
+    #[default]
+    Off,
+    // File contents plus every file and directory name
+    Include,
+    // Names only; no file is read
+    Only,
+}
+
+impl std::str::FromStr for PathWords {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(PathWords::Off),
+            "include" => Ok(PathWords::Include),
+            "only" => Ok(PathWords::Only),
+            other => Err(format!(
+                "unknown path words mode '{}' (expected off, include or only)",
+                other
+            )),
+        }
+    }
+}
+
+// How per-worker counts are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    // Each worker fills its own map; maps are merged after scanning
+    // (sequentially or in parallel, per `parallel_merge`)
+    #[default]
+    PerWorker,
+    // Workers add their maps into shared hash-prefix shards as they finish,
+    // so there is no merge phase; for huge vocabularies. Ignored with
+    // `max_unique`
+    Sharded,
+    // Like `PerWorker`, but the other maps are merged in place into the
+    // largest one, reusing its keys. Ignores `parallel_merge` and
+    // `max_unique`
+    Arena,
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "per-worker" | "per_worker" => Ok(MergeStrategy::PerWorker),
+            "sharded" => Ok(MergeStrategy::Sharded),
+            "arena" => Ok(MergeStrategy::Arena),
+            other => Err(format!(
+                "unknown merge strategy '{}' (expected per-worker, sharded or arena)",
+                other
+            )),
+        }
+    }
+}
+
+// Layout of the CLI's `--output` file (see `Config::output_format`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Bin,
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "bin" => Ok(OutputFormat::Bin),
+            #[cfg(feature = "xlsx")]
+            "xlsx" => Ok(OutputFormat::Xlsx),
+            #[cfg(not(feature = "xlsx"))]
+            "xlsx" => Err("xlsx output needs a build with --features xlsx".to_string()),
+            other => Err(format!(
+                "unknown output format '{}' (expected text, bin or xlsx)",
+                other
+            )),
+        }
+    }
+}
+
+// Where chunked files are cut (see `Config::chunk_bytes`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChunkBoundaryPolicy {
+    // Move each cut forward to the end of the token it lands in, so every
+    // token is counted exactly once
+    #[default]
+    Exact,
+    // Cut every `chunk_bytes` bytes; a token straddling a cut is counted as
+    // two fragments. Skips the boundary scan, which only matters for long
+    // runs of token bytes such as minified or binary data.
+    Split,
+}
+
+impl std::str::FromStr for ChunkBoundaryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "exact" => Ok(ChunkBoundaryPolicy::Exact),
+            "split" => Ok(ChunkBoundaryPolicy::Split),
+            other => Err(format!(
+                "unknown chunk boundary policy '{}' (expected exact or split)",
+                other
+            )),
+        }
+    }
+}
+
+// What a run does about files that cannot be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    // Skip them and print a summary at the end
+    #[default]
+    Skip,
+    // Fail the run with `FilesFailed`
+    Abort,
+    // Skip them without printing anything; callers read them from
+    // `FastWordCounter::errors` or `CountReport::errors`
+    Collect,
+}
+
+impl std::str::FromStr for ErrorPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(ErrorPolicy::Skip),
+            "abort" => Ok(ErrorPolicy::Abort),
+            "collect" => Ok(ErrorPolicy::Collect),
+            other => Err(format!(
+                "unknown error policy '{}' (expected skip, abort or collect)",
+                other
+            )),
+        }
+    }
+}
+
+// Order of the sorted results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    // Most frequent first
+    #[default]
+    CountDesc,
+    // Least frequent first
+    CountAsc,
+    // Alphabetical (byte order)
+    Alpha,
+    // Longest words first
+    Length,
+    // Words appearing in the most files first. Tracking this re-tokenizes
+    // each file into a fresh map, so it roughly doubles tokenizing time.
+    FileFrequency,
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "count" | "count-desc" => Ok(SortOrder::CountDesc),
+            "count-asc" => Ok(SortOrder::CountAsc),
+            "alpha" => Ok(SortOrder::Alpha),
+            "length" => Ok(SortOrder::Length),
+            "files" => Ok(SortOrder::FileFrequency),
+            other => Err(format!(
+                "unknown sort order '{}' (expected count, count-asc, alpha, length or files)",
+                other
+            )),
+        }
+    }
+}
+
+impl SortOrder {
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::CountDesc => "count",
+            SortOrder::CountAsc => "count-asc",
+            SortOrder::Alpha => "alpha",
+            SortOrder::Length => "length",
+            SortOrder::FileFrequency => "files",
+        }
+    }
+}
+
+// How words ranking equal under `SortOrder` are ordered. Every variant is a
+// total order over the words, so results do not depend on thread count,
+// merge strategy or hash seeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreak {
+    // Alphabetical (byte order)
+    #[default]
+    Alpha,
+    // By the word's first occurrence: files in path order, then position
+    // within the file
+    FirstSeen,
+    // By the first file (in path order) containing the word, then
+    // alphabetically
+    FileOrder,
+}
+
+impl std::str::FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "alpha" => Ok(TieBreak::Alpha),
+            "first-seen" => Ok(TieBreak::FirstSeen),
+            "file-order" => Ok(TieBreak::FileOrder),
+            other => Err(format!(
+                "unknown tie-break '{}' (expected alpha, first-seen or file-order)",
+                other
+            )),
+        }
+    }
+}
+
+impl TieBreak {
+    pub fn name(self) -> &'static str {
+        match self {
+            TieBreak::Alpha => "alpha",
+            TieBreak::FirstSeen => "first-seen",
+            TieBreak::FileOrder => "file-order",
+        }
+    }
+}
+
+// How file paths are written in output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathEncoding {
+    // As UTF-8, with bytes that are not valid UTF-8 replaced by U+FFFD
+    #[default]
+    Lossy,
+    // Invalid UTF-8 bytes, `%` and control characters written as `%XX`, so
+    // the exact name can be recovered (e.g. Latin-1 names in old archives)
+    Percent,
+}
+
+impl std::str::FromStr for PathEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lossy" => Ok(PathEncoding::Lossy),
+            "percent" => Ok(PathEncoding::Percent),
+            other => Err(format!(
+                "unknown path encoding '{}' (expected lossy or percent)",
+                other
+            )),
+        }
+    }
+}
+
+// How file contents are read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IoMode {
+    // Through the page cache: mmap or a plain read, per `use_mmap`
+    #[default]
+    Cached,
+    // O_DIRECT reads into aligned buffers, bypassing the page cache; for
+    // cold-cache benchmarking on fast SSDs. Overrides `use_mmap`
+    Direct,
+}
+
+impl std::str::FromStr for IoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "cached" => Ok(IoMode::Cached),
+            "direct" => Ok(IoMode::Direct),
+            other => Err(format!(
+                "unknown io mode '{}' (expected cached or direct)",
+                other
+            )),
+        }
+    }
+}
+
+// A named token classification, counted into its own map by
+// `count_directory_multi`
+#[derive(Debug, Clone)]
+pub struct TokenClass {
+    pub name: String,
+    pub table: TokenTable,
+}
+
+impl TokenClass {
+    pub fn new(name: impl Into<String>, table: TokenTable) -> Self {
+        Self {
+            name: name.into(),
+            table,
+        }
+    }
+
+    // Parse "name=SPEC", e.g. "numbers=0-9"
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (name, chars) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected NAME=CHARS, got '{}'", spec))?;
+        Ok(Self::new(name, parse_token_chars(chars)?))
+    }
+}
+
+// Per-token rewrite function for `Config::transform`
+pub type TransformFn = dyn Fn(&str) -> Option<Cow<'_, str>> + Send + Sync;
+
+// Shared handle to a `TransformFn`, so `Config` stays `Clone` and `Debug`
+#[derive(Clone)]
+pub struct TokenTransform(pub Arc<TransformFn>);
+
+impl TokenTransform {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&str) -> Option<Cow<'_, str>> + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for TokenTransform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TokenTransform(..)")
+    }
+}
+
+// Configuration for the word counter. Outside this crate it cannot be
+// built as a struct literal, so new options can be added; start from
+// `Config::default()` or a preset and assign fields, or use
+// `FastWordCounter::builder()`.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Config {
     pub num_threads: usize,
     pub use_mmap: bool,
-    pub silent: bool,
+    pub show_progress: bool,
+    pub print_results: bool,
+    // Add each word's percent of all counted tokens, and the cumulative
+    // percent through it, as columns of the results table
+    pub shares: bool,
+    // Layout of the CLI's `--output` file; None picks a snapshot for a
+    // `.bin` path and text otherwise
+    pub output_format: Option<OutputFormat>,
     pub parallel_merge: bool,
+    pub log_format: LogFormat,
+    pub collect_file_stats: bool,
+    // Don't tokenize files with a NUL byte in their first 8 KiB; they are
+    // listed as skipped in per-file stats
+    pub skip_binary: bool,
+    // Tokens longer than this are skipped and the file flagged
+    pub max_token_len: Option<usize>,
+    // Cap on new words a single file may add to a worker's map
+    pub max_unique_per_file: Option<usize>,
+    // Keep only this many words (SpaceSaving), lumping the rest into `<other>`
+    pub max_unique: Option<usize>,
+    // Keep a random sample of this many occurrence locations per word
+    pub sample_occurrences: Option<usize>,
+    // Override the default `[A-Za-z0-9_]` token classification
+    pub token_chars: Option<TokenTable>,
+    // Measure line lengths per file (requires `collect_file_stats`)
+    pub line_stats: bool,
+    // mmap workers hand their partial map to the merger after this many
+    // files or once it holds this many words, so merging overlaps scanning
+    pub flush_every_files: Option<usize>,
+    pub flush_every_entries: Option<usize>,
+    // Pick threads, mmap, merge strategy and flushing from the discovered
+    // files, overriding the fields above (see `FastWordCounter::tuning`)
+    pub auto_tune: bool,
+    // File extensions to count, without or with the leading dot
+    pub extensions: Vec<String>,
+    // How unreadable files are handled
+    pub error_policy: ErrorPolicy,
+    // Descend into symlinked directories and count symlinked files during
+    // discovery (loops are detected and skipped)
+    pub follow_symlinks: bool,
+    // Deepest directory level to walk: 1 counts only files directly in the
+    // root, 2 also their subdirectories, and so on (None is unlimited)
+    pub max_depth: Option<usize>,
+    // Glob patterns matched against paths relative to the root; a file must
+    // match an include glob (if any) and no exclude glob
+    pub include_globs: Vec<String>,
+    pub exclude_globs: Vec<String>,
+    // `Unicode` ignores `token_chars` and segments by UAX #29 word boundaries
+    pub token_mode: TokenMode,
+    // `write_directory` sorts results above this many words on disk, in runs
+    // of this size, instead of in one in-memory vector
+    pub spill_threshold: Option<usize>,
+    // Skip C/C++ comments and string/char literals while tokenizing
+    pub code_aware: bool,
+    // Fault mapped pages in when a file is mapped rather than while it is
+    // tokenized (MAP_POPULATE); can help on fast NVMe
+    pub prefault: bool,
+    // Drop C/C++ keywords (`keywords::C_KEYWORDS`) from the results
+    pub exclude_keywords: bool,
+    // Extra words to drop from the results
+    pub stopwords: Vec<String>,
+    // Alias -> canonical word; aliases are counted as their canonical word
+    pub aliases: AHashMap<String, String>,
+    // Count the lowercased sub-tokens of each token (see `subtoken`), so
+    // `parseHttpHeader` and `parse_http_header` both count parse, http and
+    // header. Stop-words and aliases then apply to the sub-tokens.
+    pub split_identifiers: bool,
+    // Lowercase abbreviation -> expansion applied to each sub-token with
+    // `split_identifiers`, e.g. cfg -> config, so `cfgPath` and
+    // `config_path` count the same words
+    pub expansions: AHashMap<String, String>,
+    // Page-cache reads or O_DIRECT
+    pub io_mode: IoMode,
+    // With mmap, files of at most this many bytes are read into a pooled
+    // buffer instead of mapped (0 maps everything)
+    pub small_file_bytes: usize,
+    // Hash each file's contents (xxh3-64) while counting, for per-file stats
+    // and `Session` cache validation
+    pub content_hash: bool,
+    // Inflate gzip and zstd files (detected by magic bytes) before
+    // tokenizing, and let `foo.c.gz` match the `c` extension. Byte counts
+    // and content hashes are of the decompressed text.
+    pub decompress: bool,
+    // Count the members of .tar, .tar.gz, .tgz, .tar.zst and .zip archives
+    // found by discovery, as `<archive>/<member>`, filtered by extension.
+    // Word counts only; other modes leave archives out.
+    pub archives: bool,
+    // Tokenize the names of the files found (and of the directories between
+    // them and the root), each name counted once, e.g. for naming-convention
+    // audits. Applies to `count_directory` and `count_paths`; other modes
+    // count contents only.
+    pub path_words: PathWords,
+    // Mapped files larger than this are split into chunks at token
+    // boundaries and tokenized in parallel, so one huge file uses every core
+    // (None or 0 disables). Not applied with `code_aware`, the Unicode
+    // tokenizer or `max_unique_per_file`, which need the whole file in one pass
+    pub chunk_bytes: Option<usize>,
+    pub chunk_boundary_policy: ChunkBoundaryPolicy,
+    pub merge_strategy: MergeStrategy,
+    // Order of the returned results; ties fall back to count, then
+    // `tie_break`
+    pub sort: SortOrder,
+    // Order of words that `sort` ranks equal. `FirstSeen` and `FileOrder`
+    // re-tokenize each file to find first occurrences, roughly doubling
+    // tokenizing time.
+    pub tie_break: TieBreak,
+    // Fully deterministic processing for reproducible snapshots: files are
+    // counted in path order, `auto_tune` is ignored, `max_unique` merges
+    // exact counts before pruning, and `max_unique_per_file` counts each
+    // file into its own map. Exact counts are already the same across runs
+    // and thread counts; this extends that to the approximate guards, at
+    // the cost of their memory savings.
+    pub stable: bool,
+    // Track the first and last file (in this order) each word appears in,
+    // shown under each word in the results table (see
+    // `FastWordCounter::seen`). Re-tokenizes each file like `tie_break`.
+    pub seen_order: Option<SeenOrder>,
+    // How paths are written in per-file output, grep lines and diagnostics
+    pub path_encoding: PathEncoding,
+    // Drop words counted fewer than `min_count` or more than `max_count`
+    // times from the results, before sorting and `top`, e.g. to trim rare
+    // noise tokens or ubiquitous keywords
+    pub min_count: Option<u64>,
+    pub max_count: Option<u64>,
+    // Return only the first N results. In count order they are picked with a
+    // bounded heap, without sorting the whole vocabulary.
+    pub top: Option<usize>,
+    // Library hook applied to every token before stop-words and aliases:
+    // return a replacement (borrowed to keep it, owned to rewrite it) or None
+    // to drop the token. Costs one dynamic call per token occurrence, plus
+    // an allocation whenever an owned word is returned, so it suits
+    // normalization rather than anything heavy.
+    pub transform: Option<TokenTransform>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             num_threads: num_cpus::get(),
+            use_mmap: DEFAULT_USE_MMAP,
+            show_progress: true,
+            print_results: true,
+            shares: false,
+            output_format: None,
+            parallel_merge: true,
+            log_format: LogFormat::Text,
+            collect_file_stats: false,
+            skip_binary: false,
+            max_token_len: None,
+            max_unique_per_file: None,
+            max_unique: None,
+            sample_occurrences: None,
+            token_chars: None,
+            line_stats: false,
+            flush_every_files: None,
+            flush_every_entries: None,
+            auto_tune: false,
+            extensions: vec!["c".to_string(), "h".to_string()],
+            error_policy: ErrorPolicy::Skip,
+            follow_symlinks: false,
+            max_depth: None,
+            include_globs: Vec::new(),
+            exclude_globs: Vec::new(),
+            token_mode: TokenMode::Bytes,
+            spill_threshold: None,
+            code_aware: false,
+            prefault: false,
+            exclude_keywords: false,
+            stopwords: Vec::new(),
+            aliases: AHashMap::new(),
+            split_identifiers: false,
+            expansions: AHashMap::new(),
+            io_mode: IoMode::Cached,
+            small_file_bytes: DEFAULT_SMALL_FILE_BYTES,
+            content_hash: false,
+            decompress: true,
+            archives: false,
+            path_words: PathWords::Off,
+            chunk_bytes: Some(DEFAULT_CHUNK_BYTES),
+            chunk_boundary_policy: ChunkBoundaryPolicy::Exact,
+            merge_strategy: MergeStrategy::PerWorker,
+            sort: SortOrder::CountDesc,
+            tie_break: TieBreak::Alpha,
+            stable: false,
+            seen_order: None,
+            min_count: None,
+            max_count: None,
+            path_encoding: PathEncoding::Lossy,
+            top: None,
+            transform: None,
+        }
+    }
+}
+
+impl Config {
+    // Whether files are memory-mapped: `use_mmap`, unless direct I/O is on
+    pub fn uses_mmap(&self) -> bool {
+        self.use_mmap && self.io_mode == IoMode::Cached
+    }
+
+    // Throughput-first preset: mmap with pre-faulted pages (even where the
+    // default is plain reads), every core, workers adding into shared shards
+    // instead of a merge phase, and no approximate pruning (`max_unique`) or
+    // guards
+    pub fn fast() -> Self {
+        Self {
             use_mmap: true,
-            silent: false,
+            prefault: true,
             parallel_merge: true,
+            merge_strategy: MergeStrategy::Sharded,
+            max_unique: None,
+            max_token_len: None,
+            max_unique_per_file: None,
+            ..Self::default()
+        }
+    }
+
+    // Correctness-first preset: plain reads (a file truncated while mapped
+    // can crash the process), sequential merge, any unreadable file fails
+    // the run, and `stable` processing, so output does not depend on thread
+    // scheduling.
+    pub fn strict() -> Self {
+        Self {
+            use_mmap: false,
+            parallel_merge: false,
+            error_policy: ErrorPolicy::Abort,
+            max_unique: None,
+            stable: true,
+            ..Self::default()
         }
     }
 }
@@ -49,293 +801,3550 @@ impl Default for Config {
 pub struct FastWordCounter {
     config: Config,
     stats: Arc<Stats>,
+    logger: Logger,
+    token_table: TokenTable,
+    // Words never counted: keywords and user stop-words
+    stopwords: AHashSet<String>,
 }
 
 #[derive(Debug, Default)]
 pub struct Stats {
     files_processed: AtomicU64,
     bytes_processed: AtomicU64,
+    errors: Mutex<Vec<FileError>>,
+    discovery: Mutex<DiscoveryStats>,
+    file_stats: Mutex<Vec<FileStats>>,
+    flagged_files: Mutex<Vec<FlaggedFile>>,
+    saturated_counts: AtomicU64,
+    // Distinct words in the most recent run's merged counts, and their total
+    // (before `min_count`, `max_count` and `top`), the denominator of shares
+    unique_words: AtomicU64,
+    total_count: AtomicU64,
+    // Words counted so far, and a live estimate of how many are distinct,
+    // both updated by workers before their partial counts are merged
+    tokens_processed: AtomicU64,
+    unique_estimate: UniqueEstimate,
+    samples: Mutex<AHashMap<String, Reservoir>>,
+    // File frequency, first occurrence and first/last file of each word, as
+    // far as sorting and `seen_order` need them
+    word_meta: Mutex<AHashMap<String, accumulate::WordMeta>>,
+    tuning: Mutex<Option<Tuning>>,
+    // Set by `CountHandle::cancel` or a `CancellationToken`; workers stop
+    // picking up files and chunks
+    cancelled: CancellationToken,
+}
+
+// Outcome of tokenizing one buffer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Extracted {
+    pub tokens: u64,
+    pub oversized_tokens: u64,
+    pub dropped_new_words: u64,
+}
+
+impl Extracted {
+    fn add(&mut self, other: Extracted) {
+        self.tokens += other.tokens;
+        self.oversized_tokens += other.oversized_tokens;
+        self.dropped_new_words += other.dropped_new_words;
+    }
+}
+
+// Group name for archives in `ExtensionCounts`
+pub const ARCHIVE_GROUP: &str = "archive";
+
+// Word counts overall and per extension, from `count_directory_by_extension`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionCounts {
+    pub totals: Vec<(String, u64)>,
+    // Extensions with at least one matching file, in `Config::extensions`
+    // order; archives (with `Config::archives`) come last as `ARCHIVE_GROUP`
+    pub by_extension: Vec<(String, Vec<(String, u64)>)>,
+}
+
+// A file on which `max_token_len` or `max_unique_per_file` tripped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlaggedFile {
+    pub path: PathBuf,
+    pub oversized_tokens: u64,
+    pub dropped_new_words: u64,
+}
+
+// What happened to a file in a run, as listed in per-file stats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Ok,
+    // Not tokenized because it looked binary (`Config::skip_binary`)
+    SkippedBinary,
+    // Could not be read; nothing was counted from it
+    Failed(FileErrorKind),
+}
+
+impl FileStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            FileStatus::Ok => "ok",
+            FileStatus::SkippedBinary => "skipped-binary",
+            FileStatus::Failed(_) => "error",
+        }
+    }
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileStatus::Failed(kind) => write!(f, "error: {}", kind.as_str()),
+            status => f.write_str(status.name()),
+        }
+    }
+}
+
+// Per-file contribution to the final counts. Skipped and failed files are
+// listed too, with zero tokens, so they can be told apart from files that
+// were read but held no tokens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStats {
+    pub path: PathBuf,
+    pub status: FileStatus,
+    pub bytes: u64,
+    pub tokens: u64,
+    // Present when `Config::line_stats` is set
+    pub lines: Option<LineStats>,
+    // xxh3-64 of the contents, present when `Config::content_hash` is set
+    pub hash: Option<u64>,
 }
 
 impl FastWordCounter {
     pub fn new(config: Config) -> Self {
+        let logger = Logger::new(config.log_format);
+        let token_table = config.token_chars.unwrap_or(TOKEN_CHARS);
+        let mut stopwords: AHashSet<String> = config.stopwords.iter().cloned().collect();
+        if config.exclude_keywords {
+            stopwords.extend(keywords::C_KEYWORDS.iter().map(|k| k.to_string()));
+        }
         Self {
             config,
             stats: Arc::new(Stats::default()),
+            logger,
+            token_table,
+            stopwords,
         }
     }
 
-    // Count words in all .c and .h files in a directory
+    // Fluent, validated construction starting from `Config::default()`
+    pub fn builder() -> CounterBuilder {
+        CounterBuilder::default()
+    }
+
+    // A counter with a different config that records into this one's stats
+    fn sharing_stats(&self, config: Config) -> FastWordCounter {
+        let mut counter = FastWordCounter::new(config);
+        counter.stats = Arc::clone(&self.stats);
+        counter
+    }
+
+    // Settings chosen by the last auto-tuned run, if `Config::auto_tune` is set
+    pub fn tuning(&self) -> Option<Tuning> {
+        self.stats.tuning.lock().unwrap().clone()
+    }
+
+    // Logger used for all diagnostics emitted by this counter
+    pub fn logger(&self) -> &Logger {
+        &self.logger
+    }
+
+    // Count words in all files with the configured extensions (.c and .h by
+    // default) in a directory. Fails with
+    // `NoFilesMatched` if discovery finds nothing to count.
     pub fn count_directory(&self, dir: &Path) -> Result<Vec<(String, u64)>> {
-        let files = self.discover_files(dir)?;
+        let files = self.discover_inputs(dir)?;
+        self.count_discovered(&[dir.to_path_buf()], files)
+    }
 
-        if !self.config.silent {
-            println!("Found {} files to process", files.len());
+    // Count words in several files and/or directories. Directories are
+    // walked like `count_directory`; files named directly are counted
+    // whatever their extension. A file reached through more than one of the
+    // paths is counted once.
+    pub fn count_paths(&self, paths: &[PathBuf]) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_paths(paths)?;
+        self.count_discovered(paths, files)
+    }
+
+    // Log discovery, auto-tune if asked, and count `files`, found under
+    // `roots`, plus their names with `path_words`
+    fn count_discovered(
+        &self,
+        roots: &[PathBuf],
+        files: Vec<PathBuf>,
+    ) -> Result<Vec<(String, u64)>> {
+        if self.config.show_progress {
+            self.logger.info(
+                "discovery",
+                &format!("Found {} files to process", files.len()),
+                &[("files", json!(files.len()))],
+            );
         }
 
-        let word_counts = if self.config.use_mmap {
-            self.count_with_mmap(files)?
-        } else {
-            self.count_with_read(files)?
+        let names = match self.config.path_words {
+            PathWords::Off => None,
+            PathWords::Include => Some(self.count_path_names(roots, &files)),
+            PathWords::Only => return self.finish_run(self.count_path_names(roots, &files)),
         };
 
-        let sorted_counts = self.sort_results(word_counts);
+        if self.config.auto_tune && !self.config.stable {
+            let tuning = Tuning::for_files(&files);
+            if self.config.show_progress {
+                self.logger.info(
+                    "auto_tune",
+                    &format!("Auto-tuned: {}", tuning),
+                    &[
+                        ("threads", json!(tuning.threads)),
+                        ("mmap", json!(tuning.use_mmap)),
+                        ("parallel_merge", json!(tuning.parallel_merge)),
+                        ("flush_every_files", json!(tuning.flush_every_files)),
+                        ("flush_every_entries", json!(tuning.flush_every_entries)),
+                    ],
+                );
+            }
+            let mut config = self.config.clone();
+            tuning.apply(&mut config);
+            *self.stats.tuning.lock().unwrap() = Some(tuning);
 
-        if !self.config.silent {
-            self.print_stats();
+            // Share stats so results and diagnostics land on this counter
+            return self.sharing_stats(config).count_files(files, names);
         }
 
-        Ok(sorted_counts)
+        self.count_files(files, names)
     }
 
-    // Discover files with specified extensions
-    fn discover_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
-        let files: Vec<PathBuf> = WalkDir::new(dir)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| {
-                if let Some(ext) = entry.path().extension() {
-                    ext == "c" || ext == "h"
-                } else {
-                    false
-                }
-            })
-            .map(|entry| entry.path().to_path_buf())
-            .collect();
-
-        Ok(files)
+    // Word counts of the file and directory names under `roots`
+    fn count_path_names(&self, roots: &[PathBuf], files: &[PathBuf]) -> AHashMap<String, u64> {
+        let mut counts = AHashMap::new();
+        for name in discovery::path_names(roots, files) {
+            self.extract_words(name.as_encoded_bytes(), &mut counts);
+        }
+        counts
     }
 
-    // Count words using memory-mapped files
-    fn count_with_mmap(&self, files: Vec<PathBuf>) -> Result<AHashMap<String, u64>> {
-        let (file_tx, file_rx) = bounded(self.config.num_threads * 2);
-        let (result_tx, result_rx) = bounded(self.config.num_threads);
+    // Count an explicit list of files, e.g. from `git ls-files`, without
+    // discovery: extensions and globs are not applied
+    pub fn count_file_list(&self, files: Vec<PathBuf>) -> Result<Vec<(String, u64)>> {
+        if files.is_empty() {
+            anyhow::bail!("no files to count");
+        }
+        self.count_files(files, None)
+    }
 
-        // send files to workders
-        let _producer_stats = Arc::clone(&self.stats);
-        std::thread::spawn(move || {
-            for file in files {
-                if file_tx.send(file).is_err() {
-                    break;
-                }
-            }
-        });
+    // Merge the word/count pairs `reader` yields (TSV or NDJSON lines, see
+    // `fold`), e.g. the outputs of several runs. Chunks of input are parsed
+    // in parallel and merged like per-worker maps.
+    pub fn fold_reader<R: Read>(&self, mut reader: R) -> Result<Vec<(String, u64)>> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .context("Failed to read word/count pairs")?;
+        let maps = fold::parse_chunks(&data, FOLD_CHUNK_BYTES, &self.stats.saturated_counts)?;
+        let counts = self.merge_results(maps);
+        self.finish_run(counts)
+    }
 
-        // process files
-        Ok(crossbeam::scope(|s| {
-            for _ in 0..self.config.num_threads {
-                let rx = file_rx.clone();
-                let tx = result_tx.clone();
-                let stats = Arc::clone(&self.stats);
+    // Merge the counts saved in snapshot files (see `snapshot_file`), e.g.
+    // from other machines or runs, into `results`. Snapshots are loaded in
+    // parallel and merged like per-worker maps; the totals are sorted and
+    // limited like a count's results.
+    pub fn merge_snapshots(
+        &self,
+        results: Vec<(String, u64)>,
+        paths: &[PathBuf],
+    ) -> Result<Vec<(String, u64)>> {
+        let mut maps: Vec<AHashMap<String, u64>> = paths
+            .par_iter()
+            .map(|path| Ok(snapshot_file::load(path)?.into_iter().collect()))
+            .collect::<Result<_>>()?;
+        maps.push(results.into_iter().collect());
+        let counts = self.merge_results(maps);
+        self.record_vocabulary(&counts);
+        Ok(self.select_results(counts, self.config.top))
+    }
 
-                s.spawn(move |_| {
-                    let mut local_counts = AHashMap::with_capacity(1024);
+    // Count everything `reader` yields as a single file, e.g. stdin
+    pub fn count_reader<R: Read>(&self, mut reader: R) -> Result<Vec<(String, u64)>> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if self.config.decompress
+            && let Some(inflated) = input::decompress(&data)
+        {
+            data = inflated.context("Failed to decompress input")?;
+        }
 
-                    while let Ok(file_path) = rx.recv() {
-                        if let Err(e) =
-                            self.process_file_mmap(&file_path, &mut local_counts, &stats)
-                        {
-                            eprintln!("Error processing {}: {}", file_path.display(), e);
-                        }
-                    }
+        let mut counts = AHashMap::new();
+        let extracted = match self.chunk_ranges(&data) {
+            Some(chunks) => self.extract_chunked(&data, chunks, &mut counts),
+            None => self.extract_words(&data, &mut counts),
+        };
+        self.record_file_stats(Path::new("-"), &data, &extracted);
+        self.record_word_meta(Path::new("-"), &data);
+        self.stats
+            .bytes_processed
+            .fetch_add(data.len() as u64, Ordering::Relaxed);
+        self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
 
-                    let _ = tx.send(local_counts);
-                });
-            }
+        self.finish_run(counts)
+    }
 
-            drop(result_tx);
+    // Count words in already-discovered files, adding `names` (from
+    // `count_path_names`) if given
+    fn count_files(
+        &self,
+        files: Vec<PathBuf>,
+        names: Option<AHashMap<String, u64>>,
+    ) -> Result<Vec<(String, u64)>> {
+        let mut word_counts = self.count_words_in(files)?;
+        handle::check_cancelled(&self.stats)?;
+        if let Some(names) = names {
+            word_counts = self.merge_results(vec![word_counts, names]);
+        }
 
-            // Collect all results from workers
-            let all_results: Vec<AHashMap<String, u64>> = result_rx.iter().collect();
+        self.finish_run(word_counts)
+    }
 
-            // Merge using parallel or sequential strategy
-            self.merge_results(all_results)
+    // `count_directory` that stops early when cancelled (see
+    // `cancellation_token`), returning the words counted in the files and
+    // chunks finished by then with `RunStatus::Cancelled`
+    pub fn count_directory_partial(&self, dir: &Path) -> Result<PartialCount> {
+        let files = self.discover_inputs(dir)?;
+        let counts = self.count_words_in(files)?;
+        let status = if self.stats.cancelled.is_cancelled() {
+            RunStatus::Cancelled
+        } else {
+            RunStatus::Complete
+        };
+        Ok(PartialCount {
+            results: self.finish_run(counts)?,
+            status,
         })
-        .unwrap())
     }
 
-    // Process a single file using memory mapping
-    fn process_file_mmap(
-        &self,
-        file_path: &Path,
-        counts: &mut AHashMap<String, u64>,
-        stats: &Stats,
-    ) -> Result<()> {
-        let file = File::open(file_path)
-            .with_context(|| format!("Failed to open {}", file_path.display()))?;
+    // A token that cancels this counter's runs (and those of its
+    // `spawn_count` handles) from any thread. Workers check it between
+    // files and between the chunks of a split file; once set it stays set.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.stats.cancelled.clone()
+    }
 
-        let mmap = unsafe { Mmap::map(&file) }
-            .with_context(|| format!("Failed to mmap {}", file_path.display()))?;
+    // Run `count_directory` on background threads. The handle reports
+    // progress from this counter's stats and can cancel or join the run.
+    pub fn spawn_count(&self, dir: &Path) -> CountHandle {
+        let counter = self.sharing_stats(self.config.clone());
+        CountHandle::spawn(counter, dir.to_path_buf())
+    }
 
-        stats
-            .bytes_processed
-            .fetch_add(mmap.len() as u64, Ordering::Relaxed);
+    // A `Session` recording into this counter's stats, e.g. to count with an
+    // on-disk cache and report through this counter
+    pub fn session(&self) -> Result<Session> {
+        Session::with_counter(self.sharing_stats(self.config.clone()))
+    }
 
-        self.extract_words(&mmap, counts);
+    // Run one instrumented word count over `dir` and return per-phase timings
+    // and throughput. Results are discarded and nothing is printed; errors
+    // and stats are recorded as usual.
+    pub fn profile_run(&self, dir: &Path) -> Result<ProfileReport> {
+        let files_before = self.stats.files_processed.load(Ordering::Relaxed);
+        let bytes_before = self.stats.bytes_processed.load(Ordering::Relaxed);
 
-        stats.files_processed.fetch_add(1, Ordering::Relaxed);
-        Ok(())
-    }
+        let start = Instant::now();
+        let files = self.discover_inputs(dir)?;
+        let discovery = start.elapsed();
 
-    // Extract words from byte buffer using optimized parsing
-    fn extract_words(&self, data: &[u8], counts: &mut AHashMap<String, u64>) {
-        let mut word_start = None;
+        let start = Instant::now();
+        let worker_maps = if self.config.uses_mmap() {
+            self.scan_with_mmap(files)
+        } else {
+            self.scan_with_read(files)
+        };
+        let scan = start.elapsed();
 
-        for (i, &byte) in data.iter().enumerate() {
-            if is_token_char(byte) {
-                if word_start.is_none() {
-                    word_start = Some(i);
-                }
-            } else if let Some(start) = word_start {
-                if let Ok(word) = std::str::from_utf8(&data[start..i])
-                    && !word.is_empty()
-                {
-                    *counts.entry(word.to_string()).or_insert(0) += 1;
-                }
-                word_start = None;
-            }
-        }
+        let start = Instant::now();
+        let counts = self.merge_results(worker_maps);
+        let merge = start.elapsed();
 
-        // End of file
-        if let Some(start) = word_start
-            && let Ok(word) = std::str::from_utf8(&data[start..])
-            && !word.is_empty()
-        {
-            *counts.entry(word.to_string()).or_insert(0) += 1;
-        }
+        let start = Instant::now();
+        let sorted = self.sort_results(counts);
+        let sort = start.elapsed();
+
+        Ok(ProfileReport {
+            files: self.stats.files_processed.load(Ordering::Relaxed) - files_before,
+            bytes: self.stats.bytes_processed.load(Ordering::Relaxed) - bytes_before,
+            unique_words: sorted.len(),
+            discovery,
+            scan,
+            merge,
+            sort,
+        })
     }
 
-    // Fallback impl. using regular file reads
-    fn count_with_read(&self, files: Vec<PathBuf>) -> Result<AHashMap<String, u64>> {
-        let all_results: Vec<AHashMap<String, u64>> = files
+    // Count words and write every `word | count` row to `sink` in report
+    // order, returning the number of unique words. With `spill_threshold`
+    // set, large result sets are sorted externally rather than materialized
+    // as one sorted vector.
+    pub fn write_directory<W: Write>(&self, dir: &Path, sink: W) -> Result<usize> {
+        let files = self.discover_inputs(dir)?;
+        let counts = self.count_words_in(files)?;
+        handle::check_cancelled(&self.stats)?;
+        self.report_run()?;
+
+        self.record_vocabulary(&counts);
+        let unique = counts.len();
+        let mut sink = BufWriter::new(sink);
+        let mut shares = report::Shares::new(self.total_count());
+        let run_len = self.config.spill_threshold.unwrap_or(usize::MAX);
+        spill::write_sorted(counts, run_len, |word, count| {
+            self.write_row(&mut sink, word, count, &mut shares)
+        })?;
+        sink.flush()?;
+        Ok(unique)
+    }
+
+    // `count_directory` wrapped in a `CountReport` for borrowed iteration
+    pub fn count_report(&self, dir: &Path) -> Result<CountReport> {
+        let results = self.count_directory(dir)?;
+        Ok(CountReport::from(results)
+            .with_errors(self.errors())
+            .with_total(self.total_count()))
+    }
+
+    // The `k` most frequent words, in result order. Selection runs over the
+    // merged map with a bounded min-heap, so the full vocabulary is never
+    // sorted or copied into a result vector.
+    pub fn count_directory_topk(&self, dir: &Path, k: usize) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_inputs(dir)?;
+        let counts = self.count_words_in(files)?;
+        handle::check_cancelled(&self.stats)?;
+        self.record_vocabulary(&counts);
+        self.report_run()?;
+        Ok(self.select_results(counts, Some(k)))
+    }
+
+    // Count words as interned symbols: every distinct word is stored once in
+    // a shared `SymbolTable` and workers count `u32` IDs. Honours the same
+    // tokenizer settings and guards as `count_directory`, except
+    // `max_unique_per_file` and `max_unique`.
+    pub fn count_directory_symbols(&self, dir: &Path) -> Result<CountResult> {
+        let files = self.discover_files(dir)?;
+        let symbols = Arc::new(SymbolTable::new());
+
+        let partials: Vec<AHashMap<Symbol, u64>> = files
             .into_par_iter()
-            .map(|file| {
-                let mut local_counts = AHashMap::new();
-                match std::fs::read(&file) {
-                    Ok(contents) => {
-                        self.extract_words(&contents, &mut local_counts);
+            .fold(AHashMap::new, |mut local_counts, file| {
+                if self.stats.cancelled.is_cancelled() {
+                    return local_counts;
+                }
+                match input::load(&file, &self.config) {
+                    Ok(data) if self.skips_binary(&file, &data) => {}
+                    Ok(data) => {
+                        let mut extracted = Extracted::default();
+                        self.for_each_word(&data, |token| {
+                            if let Some(word) = self.accept_token(token, &mut extracted) {
+                                let count =
+                                    local_counts.entry(symbols.intern(&word)).or_insert(0u64);
+                                *count = count.saturating_add(1);
+                                extracted.tokens += 1;
+                            }
+                        });
+                        self.record_file_stats(&file, &data, &extracted);
                         self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
                         self.stats
                             .bytes_processed
-                            .fetch_add(contents.len() as u64, Ordering::Relaxed);
+                            .fetch_add(data.len() as u64, Ordering::Relaxed);
                     }
-                    Err(e) => eprintln!("Error reading {}: {}", file.display(), e),
+                    Err(e) => self.record_error(e),
                 }
                 local_counts
             })
             .collect();
+        handle::check_cancelled(&self.stats)?;
 
-        Ok(self.merge_results(all_results))
+        let mut counts = AHashMap::new();
+        for local in partials {
+            for (id, count) in local {
+                if counts.entry(id).or_insert(0u64).merge(count) {
+                    self.stats.saturated_counts.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+        self.report_run()?;
+        Ok(CountResult::new(counts, symbols))
     }
 
-    // Merge multiple hashmaps either sequentially or in parallel
-    fn merge_results(&self, results: Vec<AHashMap<String, u64>>) -> AHashMap<String, u64> {
-        if self.config.parallel_merge && results.len() > 2 {
-            // Use parallel reduction for multiple results
-            results.into_par_iter().reduce(
-                || AHashMap::with_capacity(4096),
-                |mut acc, local| {
-                    for (word, count) in local {
-                        *acc.entry(word).or_insert(0) += count;
+    // Like `count_directory`, but adds the counts into `counts` instead of
+    // returning a new sorted vector, so repeated calls can aggregate in one
+    // map. Worker maps are merged straight into `counts`; with `max_unique`
+    // set, each call's contribution is bounded before it is added. A
+    // cancelled run adds nothing.
+    pub fn count_directory_into(
+        &self,
+        dir: &Path,
+        counts: &mut AHashMap<String, u64>,
+    ) -> Result<()> {
+        let files = self.discover_inputs(dir)?;
+
+        let worker_maps = if self.config.uses_mmap() {
+            self.scan_with_mmap(files)
+        } else {
+            self.scan_with_read(files)
+        };
+        handle::check_cancelled(&self.stats)?;
+
+        if self.config.max_unique.is_some() {
+            self.merge_into(counts, self.merge_results(worker_maps));
+        } else {
+            for local in worker_maps {
+                self.merge_into(counts, local);
+            }
+        }
+
+        self.report_run()
+    }
+
+    // Count only `words`, matching them straight in the file bytes with a
+    // `LiteralMatcher` instead of tokenizing everything. Much cheaper than a
+    // full count when only a known word list matters, but stop-words,
+    // aliases, the transform hook and `code_aware` do not apply.
+    pub fn count_directory_literals(
+        &self,
+        dir: &Path,
+        words: &[String],
+    ) -> Result<Vec<(String, u64)>> {
+        let matcher = LiteralMatcher::new(words, &self.token_table)?;
+        let files = self.discover_files(dir)?;
+        let slots = matcher.words().len();
+
+        let totals = files
+            .into_par_iter()
+            .fold(
+                || vec![0u64; slots],
+                |mut local_counts, file| {
+                    match input::load(&file, &self.config) {
+                        Ok(data) => {
+                            matcher.count(&data, &self.token_table, &mut local_counts);
+                            self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                            self.stats
+                                .bytes_processed
+                                .fetch_add(data.len() as u64, Ordering::Relaxed);
+                        }
+                        Err(e) => self.record_error(e),
                     }
-                    acc
+                    local_counts
                 },
             )
-        } else {
-            // Fall back to sequential merge
-            results
-                .into_iter()
-                .fold(AHashMap::with_capacity(4096), |mut acc, local| {
-                    for (word, count) in local {
-                        *acc.entry(word).or_insert(0) += count;
+            .reduce(
+                || vec![0u64; slots],
+                |mut a, b| {
+                    for (total, count) in a.iter_mut().zip(b) {
+                        *total = total.saturating_add(count);
                     }
-                    acc
-                })
+                    a
+                },
+            );
+
+        let counts = matcher
+            .words()
+            .iter()
+            .zip(totals)
+            .filter(|(_, count)| *count > 0)
+            .map(|(word, count)| (word.clone(), count))
+            .collect();
+        self.finish_run(counts)
+    }
+
+    // Count matches of each regex in `patterns` over every file's raw bytes.
+    // Per-file counts are kept only with `per_file`.
+    pub fn count_directory_regex(
+        &self,
+        dir: &Path,
+        patterns: &[String],
+        per_file: bool,
+    ) -> Result<PatternReport> {
+        let regexes = pattern::compile(patterns)?;
+        let files = self.discover_files(dir)?;
+
+        let scanned: Vec<(PathBuf, Vec<u64>)> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let mut counts = vec![0u64; regexes.len()];
+                    pattern::count_matches(&regexes, &data, &mut counts);
+                    self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                    self.stats
+                        .bytes_processed
+                        .fetch_add(data.len() as u64, Ordering::Relaxed);
+                    Some((file, counts))
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+
+        self.report_run()?;
+        Ok(PatternReport::from_files(patterns, scanned, per_file))
+    }
+
+    // Print every line matching any of `patterns` to `sink` as
+    // `path:line:text`, like grep, while counting matches per pattern and
+    // per file. Files are scanned in parallel; each file's lines are written
+    // together, but files appear in no particular order.
+    pub fn grep_directory<W: Write + Send>(
+        &self,
+        dir: &Path,
+        patterns: &[String],
+        sink: W,
+    ) -> Result<PatternReport> {
+        let regexes = pattern::compile(patterns)?;
+        let files = self.discover_files(dir)?;
+        let sink = Mutex::new(sink);
+
+        let scanned = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let mut counts = vec![0u64; regexes.len()];
+                    let mut out = Vec::new();
+                    let path = self.display_path(&file).into_owned();
+                    pattern::grep_lines(&regexes, &data, &mut counts, |line_no, line| {
+                        out.extend_from_slice(path.as_bytes());
+                        out.extend_from_slice(format!(":{}:", line_no).as_bytes());
+                        out.extend_from_slice(line);
+                        out.push(b'\n');
+                    });
+                    self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                    self.stats
+                        .bytes_processed
+                        .fetch_add(data.len() as u64, Ordering::Relaxed);
+                    let written = if out.is_empty() {
+                        Ok(())
+                    } else {
+                        sink.lock().unwrap().write_all(&out)
+                    };
+                    Some(written.map(|()| (file, counts)))
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        sink.into_inner().unwrap().flush()?;
+
+        self.report_run()?;
+        Ok(PatternReport::from_files(patterns, scanned, true))
+    }
+
+    // Classic `wc`: newline, word and byte counts per file and in total,
+    // each file counted in a single pass
+    pub fn count_directory_wc(&self, dir: &Path) -> Result<WcReport> {
+        let files = self.discover_files(dir)?;
+
+        let mut counted: Vec<(PathBuf, WcCounts)> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let counts = WcCounts::from_bytes(&data);
+                    self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                    self.stats
+                        .bytes_processed
+                        .fetch_add(data.len() as u64, Ordering::Relaxed);
+                    Some((file, counts))
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+        counted.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let mut total = WcCounts::default();
+        for (_, counts) in &counted {
+            total.add(counts);
         }
+
+        self.report_run()?;
+        Ok(WcReport {
+            files: counted,
+            total,
+        })
     }
 
-    // Sort results by count (descending) then alphabetically (ascending)
-    fn sort_results(&self, counts: AHashMap<String, u64>) -> Vec<(String, u64)> {
-        let mut pairs: Vec<_> = counts.into_iter().collect();
+    // Count C operators and punctuators (`->`, `==`, `<<=`, ...) instead of words
+    pub fn count_directory_operators(&self, dir: &Path) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_files(dir)?;
+        let counts = self.count_files_with(files, |_, data, counts| {
+            operators::count_operators(data, counts);
+        });
+        self.finish_run(counts)
+    }
 
-        pairs.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    // Count branching keywords and operators per file and return the `n` most
+    // branch-dense files (branches per 100 lines)
+    pub fn branch_density(&self, dir: &Path, n: usize) -> Result<Vec<BranchStats>> {
+        let files = self.discover_files(dir)?;
 
-        pairs
+        let mut stats: Vec<BranchStats> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let (branches, tokens, lines) =
+                        complexity::count_branches(&data, &self.token_table);
+                    Some(BranchStats {
+                        path: file,
+                        branches,
+                        tokens,
+                        lines,
+                    })
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+
+        stats.sort_unstable_by(|a, b| {
+            b.density()
+                .total_cmp(&a.density())
+                .then_with(|| b.branches.cmp(&a.branches))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        stats.truncate(n);
+
+        self.print_error_summary();
+        Ok(stats)
     }
 
-    // Print performance statistics
-    fn print_stats(&self) {
-        let files = self.stats.files_processed.load(Ordering::Relaxed);
-        let bytes = self.stats.bytes_processed.load(Ordering::Relaxed);
+    // Classify identifier occurrences by naming convention (snake_case,
+    // camelCase, SCREAMING_CASE or mixed) per directory, and list the `n`
+    // most frequent identifiers breaking the dominant convention. Numbers
+    // and C/C++ keywords are left out.
+    pub fn casing_audit(&self, dir: &Path, n: usize) -> Result<CasingReport> {
+        let files = self.discover_files(dir)?;
+        let keywords: AHashSet<&str> = keywords::C_KEYWORDS.iter().copied().collect();
+
+        let scanned: Vec<(PathBuf, AHashMap<String, u64>)> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let mut counts: AHashMap<String, u64> = AHashMap::new();
+                    self.for_each_token(&data, |token| {
+                        let Ok(word) = std::str::from_utf8(token) else {
+                            return;
+                        };
+                        if keywords.contains(word) || self.stopwords.contains(word) {
+                            return;
+                        }
+                        match counts.get_mut(word) {
+                            Some(count) => *count += 1,
+                            None => {
+                                counts.insert(word.to_string(), 1);
+                            }
+                        }
+                    });
+                    let dir = file.parent().map(Path::to_path_buf).unwrap_or_default();
+                    Some((dir, counts))
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut directories: AHashMap<PathBuf, AHashMap<String, u64>> = AHashMap::new();
+        for (dir, counts) in scanned {
+            let merged = directories.entry(dir).or_default();
+            for (word, count) in counts {
+                *merged.entry(word).or_insert(0) += count;
+            }
+        }
 
-        println!("Processed {} files, {} bytes", files, bytes);
+        self.print_error_summary();
+        Ok(CasingReport::from_directories(directories, n))
     }
 
-    // Print results in formatted table
-    pub fn print_results(&self, results: &[(String, u64)]) {
-        if self.config.silent {
-            return;
+    // For each of the `n` most frequent identifiers, the file holding the
+    // largest share of its occurrences. Words are tokenized and filtered as
+    // in `count_directory`; numbers are left out.
+    pub fn identifier_ownership(&self, dir: &Path, n: usize) -> Result<Vec<Ownership>> {
+        let files = self.discover_files(dir)?;
+
+        let owners = files
+            .par_iter()
+            .enumerate()
+            .fold(ownership::Owners::default, |mut owners, (index, file)| {
+                match input::load(file, &self.config) {
+                    Ok(data) if self.skips_binary(file, &data) => {}
+                    Ok(data) => {
+                        let mut counts: AHashMap<String, u64> = AHashMap::new();
+                        let mut extracted = Extracted::default();
+                        self.for_each_word(&data, |token| {
+                            let Some(word) = self.accept_token(token, &mut extracted) else {
+                                return;
+                            };
+                            if classify::classify(&word) == TokenCategory::Number {
+                                return;
+                            }
+                            match counts.get_mut(&*word) {
+                                Some(count) => *count += 1,
+                                None => {
+                                    counts.insert(word.into_owned(), 1);
+                                }
+                            }
+                        });
+                        owners.add_file(index, counts);
+                        self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => self.record_error(e),
+                }
+                owners
+            })
+            .reduce(ownership::Owners::default, ownership::Owners::merge);
+
+        self.print_error_summary();
+        Ok(owners.top(n, &files))
+    }
+
+    // Tally SPDX identifiers and license phrases, and list files carrying no
+    // license marker at all
+    pub fn scan_licenses(&self, dir: &Path) -> Result<LicenseReport> {
+        let files = self.discover_files(dir)?;
+
+        let scanned: Vec<(PathBuf, license::FileLicense)> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let found = license::scan_file(&data);
+                    Some((file, found))
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut spdx: AHashMap<String, u64> = AHashMap::new();
+        let mut phrases: AHashMap<String, u64> = AHashMap::new();
+        let mut unlicensed = Vec::new();
+        for (path, found) in scanned {
+            if !found.has_marker() {
+                unlicensed.push(path);
+                continue;
+            }
+            for id in found.spdx {
+                *spdx.entry(id).or_insert(0) += 1;
+            }
+            for (phrase, count) in found.phrases {
+                *phrases.entry(phrase).or_insert(0) += count;
+            }
         }
+        unlicensed.sort();
 
-        for (word, count) in results {
-            println!("{:>32} | {:>8}", word, count);
+        self.print_error_summary();
+        Ok(LicenseReport {
+            spdx: self.sort_results(spdx),
+            phrases: self.sort_results(phrases),
+            unlicensed,
+        })
+    }
+
+    // Count URLs and email addresses, each as a single token
+    pub fn count_directory_endpoints(&self, dir: &Path) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_files(dir)?;
+        let counts = self.count_files_with(files, |_, data, counts| {
+            endpoints::count_endpoints(data, counts);
+        });
+        self.finish_run(counts)
+    }
+
+    // Count with a WASM plugin: its `tokenize` export (if any) replaces the
+    // built-in tokenizer, and its `keep` export (if any) filters the words
+    #[cfg(feature = "wasm")]
+    pub fn count_directory_wasm(
+        &self,
+        dir: &Path,
+        plugin: &WasmPlugin,
+    ) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_files(dir)?;
+        let mut counts = if plugin.is_tokenizer() {
+            self.count_files_with(files, |path, data, counts| {
+                if let Err(e) = plugin
+                    .instantiate()
+                    .and_then(|mut instance| instance.tokenize(data, counts))
+                {
+                    self.record_error(FileError::new(
+                        path,
+                        FileErrorKind::Other,
+                        format!("WASM plugin failed: {:#}", e),
+                    ));
+                }
+            })
+        } else {
+            self.count_words_in(files)?
+        };
+
+        if plugin.is_filter() {
+            let mut instance = plugin.instantiate()?;
+            let mut dropped = Vec::new();
+            for word in counts.keys() {
+                if !instance.keep(word)? {
+                    dropped.push(word.clone());
+                }
+            }
+            for word in dropped {
+                counts.remove(&word);
+            }
         }
+
+        self.finish_run(counts)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    // Count with a native plugin: its `tokenize` callback (if any) replaces
+    // the built-in tokenizer, and its `keep` callback (if any) filters words
+    #[cfg(feature = "native-plugins")]
+    pub fn count_directory_native(
+        &self,
+        dir: &Path,
+        plugin: &NativePlugin,
+    ) -> Result<Vec<(String, u64)>> {
+        let files = self.discover_files(dir)?;
+        let mut counts = if plugin.is_tokenizer() {
+            self.count_files_with(files, |_, data, counts| plugin.tokenize(data, counts))
+        } else {
+            self.count_words_in(files)?
+        };
 
-    #[test]
-    fn test_token_char_classification() {
-        assert!(is_token_char(b'a'));
-        assert!(is_token_char(b'Z'));
-        assert!(is_token_char(b'0'));
-        assert!(is_token_char(b'_'));
-        assert!(!is_token_char(b' '));
-        assert!(!is_token_char(b'.'));
-        assert!(!is_token_char(b'\n'));
+        if plugin.is_filter() {
+            counts.retain(|word, _| plugin.keep(word));
+        }
+
+        self.finish_run(counts)
     }
 
-    #[test]
-    fn test_word_extraction() {
-        let counter = FastWordCounter::new(Config::default());
-        let mut counts = AHashMap::new();
+    // Run `scan` over every file in parallel and merge the per-file maps
+    fn count_files_with<F>(&self, files: Vec<PathBuf>, scan: F) -> AHashMap<String, u64>
+    where
+        F: Fn(&Path, &[u8], &mut AHashMap<String, u64>) + Sync,
+    {
+        // Fold per rayon task, as in `scan_with_read`
+        let all_results: Vec<AHashMap<String, u64>> = files
+            .into_par_iter()
+            .fold(AHashMap::new, |mut local_counts, file| {
+                match input::load(&file, &self.config) {
+                    Ok(data) => {
+                        scan(&file, &data, &mut local_counts);
+                        self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                        self.stats
+                            .bytes_processed
+                            .fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                    Err(e) => self.record_error(e),
+                }
+                local_counts
+            })
+            .collect();
 
-        let data = b"hello world 123 test_var";
-        counter.extract_words(data, &mut counts);
+        self.merge_results(all_results)
+    }
 
-        assert_eq!(counts.get("hello"), Some(&1));
-        assert_eq!(counts.get("world"), Some(&1));
-        assert_eq!(counts.get("123"), Some(&1));
-        assert_eq!(counts.get("test_var"), Some(&1));
+    // Sort merged counts and print end-of-run stats and diagnostics
+    fn finish_run(&self, counts: AHashMap<String, u64>) -> Result<Vec<(String, u64)>> {
+        self.record_vocabulary(&counts);
+        let sorted_counts = self.select_results(counts, self.config.top);
+        self.report_run()?;
+        Ok(sorted_counts)
     }
 
-    #[test]
-    fn test_file_processing() -> Result<()> {
-        let mut temp_file = NamedTempFile::new()?;
-        writeln!(temp_file, "int main() {{")?;
-        writeln!(temp_file, "    printf(\"hello world\");")?;
-        writeln!(temp_file, "    return 0;")?;
-        writeln!(temp_file, "}}")?;
+    // Remember the size and total of a run's merged counts
+    fn record_vocabulary(&self, counts: &AHashMap<String, u64>) {
+        let total = counts
+            .values()
+            .fold(0u64, |total, &count| total.saturating_add(count));
+        self.stats
+            .unique_words
+            .store(counts.len() as u64, Ordering::Relaxed);
+        self.stats.total_count.store(total, Ordering::Relaxed);
+    }
 
-        let counter = FastWordCounter::new(Config::default());
-        let mut counts = AHashMap::new();
-        let stats = Arc::new(Stats::default());
+    // Print end-of-run stats and diagnostics. Fails with `FilesFailed` under
+    // `ErrorPolicy::Abort` if any file could not be read.
+    fn report_run(&self) -> Result<()> {
+        if self.config.show_progress {
+            self.print_stats();
+        }
 
-        counter.process_file_mmap(temp_file.path(), &mut counts, &stats)?;
+        self.print_flagged_files();
+        self.print_error_summary();
 
-        assert!(counts.contains_key("int"));
-        assert!(counts.contains_key("main"));
-        assert!(counts.contains_key("printf"));
-        assert!(counts.contains_key("hello"));
-        assert!(counts.contains_key("world"));
-        assert!(counts.contains_key("return"));
+        let saturated = self.saturated_counts();
+        if saturated > 0 {
+            self.logger.warn(
+                "count_saturated",
+                &format!(
+                    "warning: {} word count(s) overflowed u64 and were clamped",
+                    saturated
+                ),
+                &[("saturated_counts", json!(saturated))],
+            );
+        }
 
+        if self.config.error_policy == ErrorPolicy::Abort {
+            let errors = self.stats.errors.lock().unwrap();
+            if let Some(first) = errors.first() {
+                return Err(FilesFailed {
+                    count: errors.len(),
+                    first: first.clone(),
+                }
+                .into());
+            }
+        }
         Ok(())
     }
+
+    // Count words under `dir` with a separate table per configured
+    // extension, plus the totals. Every table is sorted and limited like
+    // `count_directory`'s results.
+    pub fn count_directory_by_extension(&self, dir: &Path) -> Result<ExtensionCounts> {
+        let mut groups: Vec<(&str, Vec<PathBuf>)> = Vec::new();
+        for file in self.discover_inputs(dir)? {
+            // Only archives pass discovery without a configured extension
+            let ext = discovery::matched_extension(&file, &self.config).unwrap_or(ARCHIVE_GROUP);
+            match groups.iter_mut().find(|(group, _)| *group == ext) {
+                Some((_, files)) => files.push(file),
+                None => groups.push((ext, vec![file])),
+            }
+        }
+        let position = |ext: &str| {
+            self.config
+                .extensions
+                .iter()
+                .position(|wanted| wanted.trim_start_matches('.') == ext)
+                .unwrap_or(usize::MAX)
+        };
+        groups.sort_by_key(|(ext, _)| position(ext));
+
+        let mut totals = AHashMap::new();
+        let mut by_extension = Vec::with_capacity(groups.len());
+        for (ext, files) in groups {
+            let counts = self.count_words_in(files)?;
+            handle::check_cancelled(&self.stats)?;
+            session::add_counts(&mut totals, &counts, self);
+            by_extension.push((
+                ext.to_string(),
+                self.select_results(counts, self.config.top),
+            ));
+        }
+        let totals = self.finish_run(totals)?;
+        Ok(ExtensionCounts {
+            totals,
+            by_extension,
+        })
+    }
+
+    // Count words as usual and split them into identifiers, numeric literals
+    // and all-caps macros
+    pub fn count_directory_classified(&self, dir: &Path) -> Result<ClassifiedCounts> {
+        let files = self.discover_inputs(dir)?;
+        let counts = self.count_words_in(files)?;
+        handle::check_cancelled(&self.stats)?;
+        self.report_run()?;
+        Ok(ClassifiedCounts::from_counts(counts))
+    }
+
+    // Count several token classes in a single pass over every file, returning
+    // sorted results keyed by class name
+    pub fn count_directory_multi(
+        &self,
+        dir: &Path,
+        classes: &[TokenClass],
+    ) -> Result<AHashMap<String, Vec<(String, u64)>>> {
+        let files = self.discover_files(dir)?;
+
+        let per_file: Vec<Vec<AHashMap<String, u64>>> = files
+            .into_par_iter()
+            .filter_map(|file| match input::load(&file, &self.config) {
+                Ok(data) => {
+                    let mut maps = vec![AHashMap::new(); classes.len()];
+                    extract_multi(&data, classes, &mut maps);
+                    self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                    self.stats
+                        .bytes_processed
+                        .fetch_add(data.len() as u64, Ordering::Relaxed);
+                    Some(maps)
+                }
+                Err(e) => {
+                    self.record_error(e);
+                    None
+                }
+            })
+            .collect();
+
+        // Transpose into one list of partial maps per class
+        let mut by_class: Vec<Vec<AHashMap<String, u64>>> = vec![Vec::new(); classes.len()];
+        for maps in per_file {
+            for (class_maps, map) in by_class.iter_mut().zip(maps) {
+                class_maps.push(map);
+            }
+        }
+
+        let results = classes
+            .iter()
+            .zip(by_class)
+            .map(|(class, maps)| {
+                let merged = self.merge_results(maps);
+                (
+                    class.name.clone(),
+                    self.select_results(merged, self.config.top),
+                )
+            })
+            .collect();
+
+        if self.config.show_progress {
+            self.print_stats();
+        }
+        self.print_error_summary();
+
+        Ok(results)
+    }
+
+    // Files and bytes per extension under `dir`, from metadata alone. Every
+    // file the walk reaches counts whatever its extension; globs, depth and
+    // symlink settings apply. Largest total size first.
+    pub fn file_types(&self, dir: &Path) -> Result<Vec<FileTypeStats>> {
+        let (files, stats) = discovery::walk_files(dir, &self.config, |_| true)?;
+        *self.stats.discovery.lock().unwrap() = stats.clone();
+        if files.is_empty() {
+            return Err(NoFilesMatched {
+                root: dir.to_path_buf(),
+                stats,
+            }
+            .into());
+        }
+        Ok(filetypes::tally(&files, |e| self.record_error(e)))
+    }
+
+    // Discover files with specified extensions, leaving out archives for
+    // modes that cannot read their members
+    fn discover_files(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        self.discover(dir, false)
+    }
+
+    // Discover files for a word count, including archives with `archives`
+    fn discover_inputs(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        self.discover(dir, true)
+    }
+
+    fn discover(&self, dir: &Path, keep_archives: bool) -> Result<Vec<PathBuf>> {
+        let (mut files, mut stats) = discovery::discover_files(dir, &self.config)?;
+        if self.config.archives && !keep_archives {
+            files.retain(|file| !archive::is_archive(file));
+            stats.filtered_by_extension += stats.files_matched - files.len() as u64;
+            stats.files_matched = files.len() as u64;
+        }
+        if self.config.stable {
+            files.sort_unstable();
+        }
+        *self.stats.discovery.lock().unwrap() = stats.clone();
+
+        if files.is_empty() {
+            return Err(NoFilesMatched {
+                root: dir.to_path_buf(),
+                stats,
+            }
+            .into());
+        }
+
+        Ok(files)
+    }
+
+    // Discover files under each of `paths`, keeping named files as given
+    fn discover_paths(&self, paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+        let Some(first) = paths.first() else {
+            anyhow::bail!("no paths to count");
+        };
+
+        let mut files = Vec::new();
+        let mut stats = DiscoveryStats::default();
+        for path in paths {
+            if path.is_dir() {
+                let (found, walk) = discovery::discover_files(path, &self.config)?;
+                stats.add(&walk);
+                files.extend(found);
+            } else if path.is_file() {
+                stats.files_seen += 1;
+                files.push(path.clone());
+            } else {
+                return Err(NoFilesMatched {
+                    root: path.clone(),
+                    stats,
+                }
+                .into());
+            }
+        }
+
+        // Overlapping arguments such as `src src/sub` yield the same paths
+        let found = files.len();
+        let mut seen = AHashSet::with_capacity(found);
+        files.retain(|file| seen.insert(file.clone()));
+        stats.duplicate_files += (found - files.len()) as u64;
+        stats.files_matched = files.len() as u64;
+        if self.config.stable {
+            files.sort_unstable();
+        }
+        *self.stats.discovery.lock().unwrap() = stats.clone();
+
+        if files.is_empty() {
+            return Err(NoFilesMatched {
+                root: first.clone(),
+                stats,
+            }
+            .into());
+        }
+        Ok(files)
+    }
+
+    // Count words in `files` with the configured I/O and merge strategy
+    fn count_words_in(&self, files: Vec<PathBuf>) -> Result<AHashMap<String, u64>> {
+        if self.config.merge_strategy == MergeStrategy::Sharded && self.config.max_unique.is_none()
+        {
+            Ok(self.count_sharded(files))
+        } else if self.config.uses_mmap() {
+            self.count_with_mmap(files)
+        } else {
+            self.count_with_read(files)
+        }
+    }
+
+    // Fold files into per-task maps and add each into shared shards as soon
+    // as its task finishes
+    fn count_sharded(&self, files: Vec<PathBuf>) -> AHashMap<String, u64> {
+        let shards = merge::ShardedCounts::new(self.config.num_threads * MERGE_SHARDS_PER_THREAD);
+        files
+            .into_par_iter()
+            .fold(AHashMap::new, |mut local_counts, file| {
+                if !self.stats.cancelled.is_cancelled()
+                    && let Err(e) = self.process_file_mmap(&file, &mut local_counts, &self.stats)
+                {
+                    self.record_error(e);
+                }
+                local_counts
+            })
+            .for_each(|local| shards.add(local, &self.stats.saturated_counts));
+        shards.into_counts()
+    }
+
+    // Count words using memory-mapped files
+    fn count_with_mmap(&self, files: Vec<PathBuf>) -> Result<AHashMap<String, u64>> {
+        if self.config.flush_every_files.is_some() || self.config.flush_every_entries.is_some() {
+            // Merge partial maps as workers flush them
+            Ok(self.run_mmap_workers(files, |rx| self.merge_stream(rx)))
+        } else {
+            Ok(self.merge_results(self.scan_with_mmap(files)))
+        }
+    }
+
+    // Scan files on mmap worker threads, returning every map they flushed
+    fn scan_with_mmap(&self, files: Vec<PathBuf>) -> Vec<AHashMap<String, u64>> {
+        self.run_mmap_workers(files, |rx| rx.iter().collect())
+    }
+
+    // Run the mmap workers while `consume` drains their flushed maps on the
+    // calling thread. Each worker flushes once at the end, and earlier if
+    // `flush_every_files`/`flush_every_entries` trips.
+    fn run_mmap_workers<R, C>(&self, files: Vec<PathBuf>, consume: C) -> R
+    where
+        C: FnOnce(Receiver<AHashMap<String, u64>>) -> R,
+    {
+        let (file_tx, file_rx) = bounded(self.config.num_threads * 2);
+        let (result_tx, result_rx) = bounded(self.config.num_threads);
+
+        // send files to workders
+        let _producer_stats = Arc::clone(&self.stats);
+        std::thread::spawn(move || {
+            for file in files {
+                if file_tx.send(file).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // process files
+        crossbeam::scope(|s| {
+            for _ in 0..self.config.num_threads {
+                let rx = file_rx.clone();
+                let tx = result_tx.clone();
+                let stats = Arc::clone(&self.stats);
+
+                s.spawn(move |_| {
+                    let mut local_counts = AHashMap::with_capacity(1024);
+                    let mut files_since_flush = 0;
+
+                    while let Ok(file_path) = rx.recv() {
+                        if stats.cancelled.is_cancelled() {
+                            break;
+                        }
+                        if let Err(e) =
+                            self.process_file_mmap(&file_path, &mut local_counts, &stats)
+                        {
+                            self.record_error(e);
+                        }
+
+                        files_since_flush += 1;
+                        let flush = self
+                            .config
+                            .flush_every_files
+                            .is_some_and(|n| files_since_flush >= n)
+                            || self
+                                .config
+                                .flush_every_entries
+                                .is_some_and(|n| local_counts.len() >= n);
+                        if flush {
+                            let _ = tx.send(std::mem::take(&mut local_counts));
+                            files_since_flush = 0;
+                        }
+                    }
+
+                    if !local_counts.is_empty() {
+                        let _ = tx.send(local_counts);
+                    }
+                });
+            }
+
+            drop(result_tx);
+
+            consume(result_rx)
+        })
+        .unwrap()
+    }
+
+    // Merge maps in arrival order, while workers are still scanning
+    fn merge_stream(&self, rx: Receiver<AHashMap<String, u64>>) -> AHashMap<String, u64> {
+        if let Some(capacity) = self.config.max_unique {
+            let mut sketch = SpaceSaving::new(capacity);
+            for local in rx {
+                for (word, count) in local {
+                    sketch.offer(word, count);
+                }
+            }
+            sketch.into_counts()
+        } else {
+            let mut acc = AHashMap::with_capacity(4096);
+            for local in rx {
+                self.merge_into(&mut acc, local);
+            }
+            acc
+        }
+    }
+
+    // Process a single file using memory mapping
+    fn process_file_mmap(
+        &self,
+        file_path: &Path,
+        counts: &mut AHashMap<String, u64>,
+        stats: &Stats,
+    ) -> Result<(), FileError> {
+        if self.config.archives && archive::is_archive(file_path) {
+            return self.process_archive(file_path, counts, stats);
+        }
+        let mmap = input::load(file_path, &self.config)?;
+        if self.skips_binary(file_path, &mmap) {
+            return Ok(());
+        }
+
+        stats
+            .bytes_processed
+            .fetch_add(mmap.len() as u64, Ordering::Relaxed);
+
+        let extracted = match self.chunk_ranges(&mmap) {
+            Some(chunks) => self.extract_chunked(&mmap, chunks, counts),
+            None => self.extract_words(&mmap, counts),
+        };
+        self.record_file_stats(file_path, &mmap, &extracted);
+        self.record_samples(file_path, &mmap);
+        self.record_word_meta(file_path, &mmap);
+
+        stats.files_processed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Count each matching member of an archive as if it were a file. The
+    // archive is one processed file for progress; members get their own
+    // per-file stats and samples.
+    fn process_archive(
+        &self,
+        archive_path: &Path,
+        counts: &mut AHashMap<String, u64>,
+        stats: &Stats,
+    ) -> Result<(), FileError> {
+        archive::for_each_member(archive_path, &self.config, |path, data| {
+            if self.skips_binary(path, data) {
+                return;
+            }
+            stats
+                .bytes_processed
+                .fetch_add(data.len() as u64, Ordering::Relaxed);
+            let extracted = self.extract_words(data, counts);
+            self.record_file_stats(path, data, &extracted);
+            self.record_samples(path, data);
+            self.record_word_meta(path, data);
+        })?;
+        stats.files_processed.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Split `data` into `chunk_bytes` ranges per `chunk_boundary_policy`, or
+    // None if the buffer is small or chunking does not apply
+    fn chunk_ranges(&self, data: &[u8]) -> Option<Vec<Range<usize>>> {
+        let chunk = self.config.chunk_bytes.filter(|&n| n > 0)?;
+        if data.len() <= chunk
+            || self.config.code_aware
+            || self.config.token_mode != TokenMode::Bytes
+            || self.config.max_unique_per_file.is_some()
+        {
+            return None;
+        }
+
+        Some(boundary::chunk_ranges(
+            data,
+            chunk,
+            &self.token_table,
+            self.config.chunk_boundary_policy,
+        ))
+    }
+
+    // Tokenize chunks on the rayon pool and merge them into `counts`
+    fn extract_chunked(
+        &self,
+        data: &[u8],
+        chunks: Vec<Range<usize>>,
+        counts: &mut AHashMap<String, u64>,
+    ) -> Extracted {
+        let partials: Vec<(AHashMap<String, u64>, Extracted)> = chunks
+            .into_par_iter()
+            .map(|range| {
+                let mut local = AHashMap::new();
+                if self.stats.cancelled.is_cancelled() {
+                    return (local, Extracted::default());
+                }
+                let extracted = self.extract_words(&data[range], &mut local);
+                (local, extracted)
+            })
+            .collect();
+
+        let mut extracted = Extracted::default();
+        for (local, chunk_extracted) in partials {
+            self.merge_into(counts, local);
+            extracted.add(chunk_extracted);
+        }
+        extracted
+    }
+
+    // Extract words from byte buffer using optimized parsing
+    fn extract_words(&self, data: &[u8], counts: &mut AHashMap<String, u64>) -> Extracted {
+        if self.config.stable && self.config.max_unique_per_file.is_some() && !counts.is_empty() {
+            // Cap the words new to this file, not to the worker's map
+            let mut local = AHashMap::new();
+            let extracted = self.extract_words(data, &mut local);
+            self.merge_into(counts, local);
+            return extracted;
+        }
+        if self.counts_borrowed_keys() {
+            return self.extract_words_borrowed(data, counts);
+        }
+        let mut extracted = Extracted::default();
+        let mut inserted = 0;
+        self.for_each_word(data, |token| {
+            self.count_token(token, counts, &mut inserted, &mut extracted)
+        });
+        self.stats
+            .tokens_processed
+            .fetch_add(extracted.tokens, Ordering::Relaxed);
+        extracted
+    }
+
+    // Whether `extract_words` can count under keys borrowed from the input:
+    // byte tokens that are counted as they are, so the checks on each token
+    // give the same answer once per distinct token
+    fn counts_borrowed_keys(&self) -> bool {
+        self.config.token_mode == TokenMode::Bytes
+            && !self.config.split_identifiers
+            && self.config.transform.is_none()
+            && self.config.max_unique_per_file.is_none()
+    }
+
+    // Count `data`'s tokens in a map keyed by slices of `data` itself, so
+    // repeated tokens are neither UTF-8 checked nor looked up in the larger
+    // worker map again, then fold each distinct token into `counts`. A
+    // `String` is allocated only for words new to `counts`.
+    fn extract_words_borrowed<'d>(
+        &self,
+        data: &'d [u8],
+        counts: &mut AHashMap<String, u64>,
+    ) -> Extracted {
+        let mut extracted = Extracted::default();
+        let mut local: AHashMap<&'d [u8], u64> = AHashMap::new();
+        let mut count = |token: &'d [u8]| {
+            if self
+                .config
+                .max_token_len
+                .is_some_and(|max| token.len() > max)
+            {
+                extracted.oversized_tokens += 1;
+            } else {
+                *local.entry(token).or_insert(0) += 1;
+            }
+        };
+        if self.config.code_aware {
+            code::for_each_code_span(data, |span| self.for_each_byte_token(span, &mut count));
+        } else {
+            self.for_each_byte_token(data, &mut count);
+        }
+
+        for (token, occurrences) in local {
+            let Some(word) = self.accept_token(token, &mut extracted) else {
+                continue;
+            };
+            match counts.get_mut(&*word) {
+                Some(count) => {
+                    if count.merge(occurrences) {
+                        self.stats.saturated_counts.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                None => {
+                    self.stats.unique_estimate.offer(&word);
+                    counts.insert(word.into_owned(), occurrences);
+                }
+            }
+            extracted.tokens += occurrences;
+        }
+        self.stats
+            .tokens_processed
+            .fetch_add(extracted.tokens, Ordering::Relaxed);
+        extracted
+    }
+
+    // Call `f` with every raw token in `data`, honouring `code_aware` and
+    // `token_mode`
+    #[inline(always)]
+    fn for_each_token<F: FnMut(&[u8])>(&self, data: &[u8], mut f: F) {
+        if self.config.code_aware {
+            code::for_each_code_span(data, |span| self.for_each_span_token(span, &mut f));
+        } else {
+            self.for_each_span_token(data, &mut f);
+        }
+    }
+
+    // `for_each_token`, handing out sub-tokens instead with
+    // `split_identifiers`: lowercased, then expanded via `expansions`
+    #[inline(always)]
+    fn for_each_word<F: FnMut(&[u8])>(&self, data: &[u8], mut f: F) {
+        if !self.config.split_identifiers {
+            return self.for_each_token(data, f);
+        }
+        let mut lowered = String::new();
+        self.for_each_token(data, |token| {
+            let Ok(word) = std::str::from_utf8(token) else {
+                return f(token);
+            };
+            for part in subtoken::split(word) {
+                lowered.clear();
+                lowered.extend(part.chars().flat_map(char::to_lowercase));
+                match self.config.expansions.get(&lowered) {
+                    Some(expansion) => f(expansion.as_bytes()),
+                    None => f(lowered.as_bytes()),
+                }
+            }
+        });
+    }
+
+    // Tokenize one contiguous span of input
+    #[inline(always)]
+    fn for_each_span_token<F: FnMut(&[u8])>(&self, data: &[u8], f: &mut F) {
+        if self.config.token_mode == TokenMode::Unicode {
+            // UAX #29 words, e.g. "größe" or "変数" rather than ASCII fragments
+            let text = String::from_utf8_lossy(data);
+            for word in text.unicode_words() {
+                f(word.as_bytes());
+            }
+            return;
+        }
+
+        self.for_each_byte_token(data, f);
+    }
+
+    // Tokenize one span into runs of token characters, borrowed from `data`
+    #[inline(always)]
+    fn for_each_byte_token<'d, F: FnMut(&'d [u8])>(&self, data: &'d [u8], f: &mut F) {
+        let mut word_start = None;
+
+        for (i, &byte) in data.iter().enumerate() {
+            if self.token_table[byte as usize] {
+                if word_start.is_none() {
+                    word_start = Some(i);
+                }
+            } else if let Some(start) = word_start {
+                f(&data[start..i]);
+                word_start = None;
+            }
+        }
+
+        // End of span
+        if let Some(start) = word_start {
+            f(&data[start..]);
+        }
+    }
+
+    // Apply the length guard, UTF-8 check, transform, stop-words and aliases
+    // to a raw token, returning the word to count
+    #[inline(always)]
+    fn accept_token<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        extracted: &mut Extracted,
+    ) -> Option<Cow<'a, str>> {
+        if self
+            .config
+            .max_token_len
+            .is_some_and(|max| bytes.len() > max)
+        {
+            extracted.oversized_tokens += 1;
+            return None;
+        }
+
+        let mut word = Cow::Borrowed(std::str::from_utf8(bytes).ok()?);
+        if let Some(transform) = &self.config.transform {
+            word = match (transform.0)(&word)? {
+                Cow::Borrowed(same) if same == &*word => word,
+                changed => Cow::Owned(changed.into_owned()),
+            };
+        }
+        if word.is_empty() || (!self.stopwords.is_empty() && self.stopwords.contains(&*word)) {
+            return None;
+        }
+        if !self.config.aliases.is_empty()
+            && let Some(canonical) = self.config.aliases.get(&*word)
+        {
+            return Some(Cow::Borrowed(canonical));
+        }
+        Some(word)
+    }
+
+    // Count a single token, enforcing the pathological-input guards
+    #[inline(always)]
+    fn count_token(
+        &self,
+        bytes: &[u8],
+        counts: &mut AHashMap<String, u64>,
+        inserted: &mut usize,
+        extracted: &mut Extracted,
+    ) {
+        let Some(word) = self.accept_token(bytes, extracted) else {
+            return;
+        };
+
+        if let Some(count) = counts.get_mut(&*word) {
+            *count = count.saturating_add(1);
+        } else if self
+            .config
+            .max_unique_per_file
+            .is_some_and(|max| *inserted >= max)
+        {
+            extracted.dropped_new_words += 1;
+            return;
+        } else {
+            // Only words new to this worker's map reach the shared sketch
+            self.stats.unique_estimate.offer(&word);
+            counts.insert(word.into_owned(), 1);
+            *inserted += 1;
+        }
+        extracted.tokens += 1;
+    }
+
+    // Fallback impl. using regular file reads
+    fn count_with_read(&self, files: Vec<PathBuf>) -> Result<AHashMap<String, u64>> {
+        Ok(self.merge_results(self.scan_with_read(files)))
+    }
+
+    // Scan files with regular reads in parallel, folding each rayon task's
+    // files into one map. A word's `String` key is allocated the first time
+    // that map sees it, not once per file it appears in.
+    fn scan_with_read(&self, files: Vec<PathBuf>) -> Vec<AHashMap<String, u64>> {
+        files
+            .into_par_iter()
+            .fold(AHashMap::new, |mut local_counts, file| {
+                if self.stats.cancelled.is_cancelled() {
+                    return local_counts;
+                }
+                if self.config.archives && archive::is_archive(&file) {
+                    if let Err(e) = self.process_archive(&file, &mut local_counts, &self.stats) {
+                        self.record_error(e);
+                    }
+                    return local_counts;
+                }
+                match input::load(&file, &self.config) {
+                    Ok(contents) if self.skips_binary(&file, &contents) => {}
+                    Ok(contents) => {
+                        let extracted = self.extract_words(&contents, &mut local_counts);
+                        self.record_file_stats(&file, &contents, &extracted);
+                        self.record_samples(&file, &contents);
+                        self.record_word_meta(&file, &contents);
+                        self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+                        self.stats
+                            .bytes_processed
+                            .fetch_add(contents.len() as u64, Ordering::Relaxed);
+                    }
+                    Err(e) => self.record_error(e),
+                }
+                local_counts
+            })
+            .collect()
+    }
+
+    // Merge multiple hashmaps either sequentially or in parallel
+    fn merge_results(&self, results: Vec<AHashMap<String, u64>>) -> AHashMap<String, u64> {
+        if let Some(capacity) = self.config.max_unique
+            && self.config.stable
+        {
+            // Prune the exact totals, offered in word order
+            let mut exact: Vec<_> = merge::merge_sequential(results, &self.stats.saturated_counts)
+                .into_iter()
+                .collect();
+            exact.sort_unstable();
+            let mut sketch = SpaceSaving::new(capacity);
+            for (word, count) in exact {
+                sketch.offer(word, count);
+            }
+            sketch.into_counts()
+        } else if let Some(capacity) = self.config.max_unique {
+            // Bounded merge: only the heaviest words survive
+            let mut sketch = SpaceSaving::new(capacity);
+            for local in results {
+                for (word, count) in local {
+                    sketch.offer(word, count);
+                }
+            }
+            sketch.into_counts()
+        } else if self.config.merge_strategy == MergeStrategy::Arena {
+            merge::merge_arena(results, &self.stats.saturated_counts)
+        } else if self.config.parallel_merge && results.len() > 2 {
+            // Hash-partitioned reduction; oversharding evens out skewed shards
+            merge::merge_partitioned(
+                results,
+                self.config.num_threads * MERGE_SHARDS_PER_THREAD,
+                &self.stats.saturated_counts,
+            )
+        } else {
+            merge::merge_sequential(results, &self.stats.saturated_counts)
+        }
+    }
+
+    // Add `local` into `acc`, saturating counts at u64::MAX and recording
+    // every word whose total overflowed
+    fn merge_into(&self, acc: &mut AHashMap<String, u64>, local: AHashMap<String, u64>) {
+        merge::merge_into(acc, local, &self.stats.saturated_counts);
+    }
+
+    // Sort results per `sort`, then by count (descending), then per
+    // `tie_break`
+    fn sort_results(&self, counts: AHashMap<String, u64>) -> Vec<(String, u64)> {
+        let mut pairs: Vec<_> = counts.into_iter().collect();
+
+        let meta = self.stats.word_meta.lock().unwrap();
+        // Words never seen in a file (e.g. from path names or merged
+        // snapshots) come after those that were
+        let seen = |word: &str| match meta.get(word).and_then(|meta| meta.first.as_ref()) {
+            Some(first) => (false, Some(&*first.path), first.position),
+            None => (true, None, 0),
+        };
+        let tie = |a: &String, b: &String| match self.config.tie_break {
+            TieBreak::Alpha => a.cmp(b),
+            TieBreak::FirstSeen => seen(a).cmp(&seen(b)).then_with(|| a.cmp(b)),
+            TieBreak::FileOrder => {
+                let (a_missing, a_path, _) = seen(a);
+                let (b_missing, b_path, _) = seen(b);
+                (a_missing, a_path)
+                    .cmp(&(b_missing, b_path))
+                    .then_with(|| a.cmp(b))
+            }
+        };
+        let by_count =
+            |a: &(String, u64), b: &(String, u64)| b.1.cmp(&a.1).then_with(|| tie(&a.0, &b.0));
+        match self.config.sort {
+            SortOrder::CountDesc => pairs.sort_unstable_by(by_count),
+            SortOrder::CountAsc => {
+                pairs.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| tie(&a.0, &b.0)))
+            }
+            SortOrder::Alpha => pairs.sort_unstable_by(|a, b| a.0.cmp(&b.0)),
+            SortOrder::Length => pairs
+                .sort_unstable_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| by_count(a, b))),
+            SortOrder::FileFrequency => {
+                let files = |word: &str| meta.get(word).map_or(0, |meta| meta.files);
+                pairs.sort_unstable_by(|a, b| {
+                    files(&b.0).cmp(&files(&a.0)).then_with(|| by_count(a, b))
+                });
+            }
+        }
+
+        pairs
+    }
+
+    // Sort `counts` per `sort` after dropping words outside `min_count` and
+    // `max_count`, keeping only the first `limit`
+    fn select_results(
+        &self,
+        mut counts: AHashMap<String, u64>,
+        limit: Option<usize>,
+    ) -> Vec<(String, u64)> {
+        let (min, max) = (self.config.min_count, self.config.max_count);
+        if min.is_some() || max.is_some() {
+            let (min, max) = (min.unwrap_or(0), max.unwrap_or(u64::MAX));
+            counts.retain(|_, count| (min..=max).contains(count));
+        }
+        match limit {
+            Some(k)
+                if self.config.sort == SortOrder::CountDesc
+                    && self.config.tie_break == TieBreak::Alpha =>
+            {
+                top_k(counts, k)
+            }
+            Some(k) => {
+                let mut sorted = self.sort_results(counts);
+                sorted.truncate(k);
+                sorted
+            }
+            None => self.sort_results(counts),
+        }
+    }
+
+    // Record a per-file failure for the end-of-run summary
+    fn record_error(&self, error: FileError) {
+        if self.logger.format() == LogFormat::Json
+            && self.config.error_policy != ErrorPolicy::Collect
+        {
+            self.logger.error(
+                "file_error",
+                &error.to_string(),
+                &[
+                    ("path", json!(self.display_path(&error.path))),
+                    ("kind", json!(error.kind.as_str())),
+                    ("error", json!(error.message)),
+                ],
+            );
+        }
+        if self.config.collect_file_stats {
+            self.stats.file_stats.lock().unwrap().push(FileStats {
+                path: error.path.clone(),
+                status: FileStatus::Failed(error.kind),
+                bytes: 0,
+                tokens: 0,
+                lines: None,
+                hash: None,
+            });
+        }
+        self.stats.errors.lock().unwrap().push(error);
+    }
+
+    // `path` as text for output, per `Config::path_encoding`
+    pub fn display_path<'p>(&self, path: &'p Path) -> Cow<'p, str> {
+        output::encode_path(path, self.config.path_encoding)
+    }
+
+    // All per-file failures recorded so far
+    pub fn errors(&self) -> Vec<FileError> {
+        self.stats.errors.lock().unwrap().clone()
+    }
+
+    // Print a table of failures grouped by category
+    fn print_error_summary(&self) {
+        let errors = self.stats.errors.lock().unwrap();
+        if errors.is_empty() || self.config.error_policy == ErrorPolicy::Collect {
+            return;
+        }
+
+        let rows = error::summarize_errors(&errors);
+        let table: Vec<_> = rows
+            .iter()
+            .map(|row| {
+                json!({
+                    "kind": row.kind.as_str(),
+                    "count": row.count,
+                    "examples": row
+                        .examples
+                        .iter()
+                        .map(|p| self.display_path(p))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+
+        self.logger.warn(
+            "error_summary",
+            error::format_error_summary(&rows, self.config.path_encoding).trim_end(),
+            &[
+                ("failed", json!(errors.len())),
+                ("categories", json!(table)),
+            ],
+        );
+    }
+
+    // Remember a file's contribution when per-file stats are enabled, and
+    // flag it if any pathological-input guard tripped
+    fn record_file_stats(&self, path: &Path, data: &[u8], extracted: &Extracted) {
+        if self.config.collect_file_stats {
+            let lines = self.config.line_stats.then(|| LineStats::from_bytes(data));
+            self.stats.file_stats.lock().unwrap().push(FileStats {
+                path: path.to_path_buf(),
+                status: FileStatus::Ok,
+                bytes: data.len() as u64,
+                tokens: extracted.tokens,
+                lines,
+                hash: self.content_hash(data),
+            });
+        }
+
+        if extracted.oversized_tokens > 0 || extracted.dropped_new_words > 0 {
+            self.stats.flagged_files.lock().unwrap().push(FlaggedFile {
+                path: path.to_path_buf(),
+                oversized_tokens: extracted.oversized_tokens,
+                dropped_new_words: extracted.dropped_new_words,
+            });
+        }
+    }
+
+    // With `skip_binary`, whether `data` looks binary. Such a file is
+    // recorded as processed and skipped; the caller must not tokenize it.
+    pub(crate) fn skips_binary(&self, path: &Path, data: &[u8]) -> bool {
+        if !self.config.skip_binary || !data[..data.len().min(BINARY_SNIFF_BYTES)].contains(&0) {
+            return false;
+        }
+        if self.config.collect_file_stats {
+            self.stats.file_stats.lock().unwrap().push(FileStats {
+                path: path.to_path_buf(),
+                status: FileStatus::SkippedBinary,
+                bytes: data.len() as u64,
+                tokens: 0,
+                lines: None,
+                hash: None,
+            });
+        }
+        self.stats.files_processed.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+
+    // Hash of a file's contents when `content_hash` is enabled
+    pub(crate) fn content_hash(&self, data: &[u8]) -> Option<u64> {
+        self.config
+            .content_hash
+            .then(|| xxhash_rust::xxh3::xxh3_64(data))
+    }
+
+    // Sample occurrence locations from a file when sampling is enabled
+    fn record_samples(&self, path: &Path, data: &[u8]) {
+        if let Some(k) = self.config.sample_occurrences {
+            let file_samples = sample::sample_file(path, data, k, &self.token_table);
+            sample::merge_samples(&mut self.stats.samples.lock().unwrap(), file_samples, k);
+        }
+    }
+
+    // Whether any per-word metadata layer is in use: file frequency when
+    // sorting by it, first occurrences when ties are broken by them, or
+    // first/last file with `seen_order`
+    pub(crate) fn tracks_word_meta(&self) -> bool {
+        self.config.sort == SortOrder::FileFrequency
+            || self.config.tie_break != TieBreak::Alpha
+            || self.config.seen_order.is_some()
+    }
+
+    // Merge a file's per-word metadata into the run's
+    fn record_word_meta(&self, path: &Path, data: &[u8]) {
+        if let Some(local) = self.word_meta(path, data) {
+            merge::merge_into(
+                &mut self.stats.word_meta.lock().unwrap(),
+                local,
+                &self.stats.saturated_counts,
+            );
+        }
+    }
+
+    // Re-tokenize a file for the metadata layers in use, or None if no layer
+    // is (see `tracks_word_meta`)
+    pub(crate) fn word_meta(
+        &self,
+        path: &Path,
+        data: &[u8],
+    ) -> Option<AHashMap<String, accumulate::WordMeta>> {
+        if !self.tracks_word_meta() {
+            return None;
+        }
+        let files = self.config.sort == SortOrder::FileFrequency;
+        let first = self.config.tie_break != TieBreak::Alpha;
+        let span = self.config.seen_order.map(|order| order.key(path));
+        let path: Arc<Path> = Arc::from(path);
+        let mut local: AHashMap<String, accumulate::WordMeta> = AHashMap::new();
+        let mut position = 0;
+        let mut extracted = Extracted::default();
+        self.for_each_word(data, |token| {
+            if let Some(word) = self.accept_token(token, &mut extracted) {
+                if !local.contains_key(&*word) {
+                    let meta = accumulate::WordMeta {
+                        files: files as u64,
+                        first: first.then(|| accumulate::FirstSeen {
+                            path: Arc::clone(&path),
+                            position,
+                        }),
+                        span: span.clone().map(seen::SeenSpan::new),
+                    };
+                    local.insert(word.into_owned(), meta);
+                }
+                position += 1;
+            }
+        });
+        Some(local)
+    }
+
+    // First and last file containing `word`, if `seen_order` is set
+    pub fn seen(&self, word: &str) -> Option<SeenFiles> {
+        let meta = self.stats.word_meta.lock().unwrap();
+        meta.get(word)?.span.as_ref().map(|span| span.files())
+    }
+
+    // Sampled occurrence locations for `word`, if sampling is enabled
+    pub fn samples(&self, word: &str) -> Option<Reservoir> {
+        self.stats.samples.lock().unwrap().get(word).cloned()
+    }
+
+    // Progress of the current run so far, readable from another thread while
+    // it counts
+    pub fn progress(&self) -> Progress {
+        handle::progress(&self.stats)
+    }
+
+    // Distinct words counted by the most recent run, including words that a
+    // top-k run did not return
+    pub fn unique_words(&self) -> u64 {
+        self.stats.unique_words.load(Ordering::Relaxed)
+    }
+
+    // Tokens counted by the most recent run, the denominator of `shares`
+    pub fn total_count(&self) -> u64 {
+        self.stats.total_count.load(Ordering::Relaxed)
+    }
+
+    // Number of merges where a word's total hit u64::MAX and was clamped
+    pub fn saturated_counts(&self) -> u64 {
+        self.stats.saturated_counts.load(Ordering::Relaxed)
+    }
+
+    // Check `results` of the most recent run with
+    // `CountReport::validate_with`, in the order this counter sorts results
+    // in (found by sorting a copy). Counts must add up to the tokens
+    // processed when the results hold every word counted exactly, so not
+    // for runs limited by `top`, approximate ones (`max_unique` or
+    // saturated counts), or ones counting path words alongside tokens.
+    pub fn verify(&self, results: &[(String, u64)]) -> Result<()> {
+        let complete = results.len() as u64 == self.unique_words()
+            && self.config.max_unique.is_none()
+            && self.config.path_words == PathWords::Off
+            && self.saturated_counts() == 0;
+        let tokens = self.stats.tokens_processed.load(Ordering::Relaxed);
+        let sorted = self.sort_results(results.iter().cloned().collect());
+        let rank: AHashMap<&str, usize> = sorted
+            .iter()
+            .enumerate()
+            .map(|(i, (word, _))| (word.as_str(), i))
+            .collect();
+        CountReport::from(results.to_vec())
+            .validate_with(complete.then_some(tokens), |a, b| {
+                rank[a.0.as_str()].cmp(&rank[b.0.as_str()])
+            })
+            .map_err(|e| e.context("Result verification failed"))
+    }
+
+    // Files on which a pathological-input guard tripped
+    pub fn flagged_files(&self) -> Vec<FlaggedFile> {
+        self.stats.flagged_files.lock().unwrap().clone()
+    }
+
+    // Warn about each file on which a guard tripped
+    fn print_flagged_files(&self) {
+        for file in self.stats.flagged_files.lock().unwrap().iter() {
+            self.logger.warn(
+                "guard_tripped",
+                &format!(
+                    "warning: {}: skipped {} oversized tokens, dropped {} new words over the per-file unique cap",
+                    self.display_path(&file.path),
+                    file.oversized_tokens,
+                    file.dropped_new_words
+                ),
+                &[
+                    ("path", json!(self.display_path(&file.path))),
+                    ("oversized_tokens", json!(file.oversized_tokens)),
+                    ("dropped_new_words", json!(file.dropped_new_words)),
+                ],
+            );
+        }
+    }
+
+    // Per-file stats recorded so far (empty unless `collect_file_stats` is set)
+    pub fn file_stats(&self) -> Vec<FileStats> {
+        self.stats.file_stats.lock().unwrap().clone()
+    }
+
+    // The `n` files contributing the most tokens, ties broken by bytes
+    pub fn largest_contributors(&self, n: usize) -> Vec<FileStats> {
+        let mut files = self.file_stats();
+        files.retain(|file| file.status == FileStatus::Ok);
+        files.sort_unstable_by(|a, b| {
+            b.tokens
+                .cmp(&a.tokens)
+                .then_with(|| b.bytes.cmp(&a.bytes))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        files.truncate(n);
+        files
+    }
+
+    // Print the largest contributors as a table
+    pub fn print_largest_contributors(&self, n: usize) {
+        for file in self.largest_contributors(n) {
+            println!(
+                "{:>10} tokens | {:>12} bytes | {}",
+                file.tokens,
+                file.bytes,
+                self.display_path(&file.path)
+            );
+        }
+    }
+
+    // Line-length stats summed over every recorded file
+    pub fn aggregate_line_stats(&self) -> LineStats {
+        let mut total = LineStats::default();
+        for file in self.stats.file_stats.lock().unwrap().iter() {
+            if let Some(lines) = &file.lines {
+                total.merge(lines);
+            }
+        }
+        total
+    }
+
+    // Print per-file sizes and line lengths followed by the aggregate
+    // line-length distribution
+    pub fn print_file_stats(&self) {
+        let mut files = self.file_stats();
+        files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        for file in &files {
+            let lines = file.lines.clone().unwrap_or_default();
+            let hash = file
+                .hash
+                .map(|hash| format!("{:016x} | ", hash))
+                .unwrap_or_default();
+            if self.logger.format() == LogFormat::Json {
+                let error_kind = match file.status {
+                    FileStatus::Failed(kind) => json!(kind.as_str()),
+                    _ => json!(null),
+                };
+                self.logger.info(
+                    "file_stats",
+                    &file.status.to_string(),
+                    &[
+                        ("path", json!(self.display_path(&file.path))),
+                        ("status", json!(file.status.name())),
+                        ("error_kind", error_kind),
+                        ("bytes", json!(file.bytes)),
+                        ("tokens", json!(file.tokens)),
+                        ("lines", json!(lines.lines)),
+                        (
+                            "hash",
+                            json!(file.hash.map(|hash| format!("{:016x}", hash))),
+                        ),
+                    ],
+                );
+                continue;
+            }
+            println!(
+                "{:>12} bytes | {:>8} lines | max {:>6} | mean {:>7.1} | {:<14} | {}{}",
+                file.bytes,
+                lines.lines,
+                lines.max_line_len,
+                lines.mean_line_len(),
+                file.status.to_string(),
+                hash,
+                self.display_path(&file.path)
+            );
+        }
+
+        let total = self.aggregate_line_stats();
+        let total_bytes: u64 = files.iter().map(|f| f.bytes).sum();
+        println!();
+        println!(
+            "Total: {} files, {} bytes, {}",
+            files.len(),
+            total_bytes,
+            total
+        );
+        for (label, count) in LineStats::bucket_labels().iter().zip(total.histogram) {
+            println!("{:>12} cols | {:>10}", label, count);
+        }
+    }
+
+    // Discovery-phase counters from the most recent run
+    pub fn discovery_stats(&self) -> DiscoveryStats {
+        self.stats.discovery.lock().unwrap().clone()
+    }
+
+    // Print performance statistics
+    fn print_stats(&self) {
+        let files = self.stats.files_processed.load(Ordering::Relaxed);
+        let bytes = self.stats.bytes_processed.load(Ordering::Relaxed);
+        let discovery = self.discovery_stats();
+
+        self.logger.info(
+            "discovery_stats",
+            &format!("Discovery: {}", discovery),
+            &[
+                ("dirs_visited", json!(discovery.dirs_visited)),
+                ("files_seen", json!(discovery.files_seen)),
+                ("files_matched", json!(discovery.files_matched)),
+                (
+                    "filtered_by_extension",
+                    json!(discovery.filtered_by_extension),
+                ),
+                ("filtered_by_glob", json!(discovery.filtered_by_glob)),
+                ("broken_symlinks", json!(discovery.broken_symlinks)),
+                ("symlink_loops", json!(discovery.symlink_loops)),
+                ("duplicate_files", json!(discovery.duplicate_files)),
+                ("walk_errors", json!(discovery.walk_errors)),
+            ],
+        );
+
+        self.logger.info(
+            "stats",
+            &format!("Processed {} files, {} bytes", files, bytes),
+            &[("files", json!(files)), ("bytes", json!(bytes))],
+        );
+    }
+
+    // Print results in formatted table
+    pub fn print_results(&self, results: &[(String, u64)]) {
+        if !self.config.print_results {
+            return;
+        }
+
+        let _ = self.write_results(results, std::io::stdout().lock());
+    }
+
+    // One row of the results table, with share columns under `shares`
+    fn write_row<W: Write>(
+        &self,
+        sink: &mut W,
+        word: &str,
+        count: u64,
+        shares: &mut report::Shares,
+    ) -> std::io::Result<()> {
+        if !self.config.shares {
+            return writeln!(sink, "{:>32} | {:>8}", word, count);
+        }
+        let (percent, cumulative) = shares.add(count);
+        writeln!(
+            sink,
+            "{:>32} | {:>8} | {:>6.2}% | {:>6.2}%",
+            word, count, percent, cumulative
+        )
+    }
+
+    // Write results (and any sampled locations) as the results table
+    pub fn write_results<W: Write>(&self, results: &[(String, u64)], sink: W) -> Result<()> {
+        let mut sink = BufWriter::new(sink);
+        let samples = self.stats.samples.lock().unwrap();
+        let meta = self.stats.word_meta.lock().unwrap();
+        // Results not from a run of this counter are their own total
+        let total = match self.total_count() {
+            0 => results
+                .iter()
+                .fold(0u64, |total, (_, count)| total.saturating_add(*count)),
+            total => total,
+        };
+        let mut shares = report::Shares::new(total);
+        for (word, count) in results {
+            self.write_row(&mut sink, word, *count, &mut shares)?;
+            let span = meta.get(word).and_then(|meta| meta.span.as_ref());
+            if let Some(files) = span.map(|span| span.files()) {
+                writeln!(
+                    sink,
+                    "{:>32}   first: {}, last: {}",
+                    "",
+                    self.display_path(&files.first),
+                    self.display_path(&files.last)
+                )?;
+            }
+            if let Some(reservoir) = samples.get(word) {
+                for occurrence in &reservoir.samples {
+                    writeln!(
+                        sink,
+                        "{:>32}   {}:{}:{}",
+                        "",
+                        self.display_path(&occurrence.path),
+                        occurrence.line,
+                        occurrence.column
+                    )?;
+                }
+            }
+        }
+        sink.flush()?;
+        Ok(())
+    }
+}
+
+// Tokenize `data` once, tracking a token run per class
+fn extract_multi(data: &[u8], classes: &[TokenClass], maps: &mut [AHashMap<String, u64>]) {
+    let mut starts: Vec<Option<usize>> = vec![None; classes.len()];
+
+    for (i, &byte) in data.iter().enumerate() {
+        for (c, class) in classes.iter().enumerate() {
+            if class.table[byte as usize] {
+                starts[c].get_or_insert(i);
+            } else if let Some(start) = starts[c].take()
+                && let Ok(word) = std::str::from_utf8(&data[start..i])
+            {
+                *maps[c].entry(word.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    for (c, start) in starts.into_iter().enumerate() {
+        if let Some(start) = start
+            && let Ok(word) = std::str::from_utf8(&data[start..])
+        {
+            *maps[c].entry(word.to_string()).or_insert(0) += 1;
+        }
+    }
+}
+
+// The `k` heaviest entries (count descending, then alphabetically) via a
+// min-heap holding at most `k + 1` entries
+fn top_k(counts: AHashMap<String, u64>, k: usize) -> Vec<(String, u64)> {
+    if k == 0 {
+        return Vec::new();
+    }
+    // Greater keys rank higher: bigger count, then alphabetically earlier
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for (word, count) in counts {
+        heap.push(std::cmp::Reverse((count, std::cmp::Reverse(word))));
+        if heap.len() > k {
+            heap.pop();
+        }
+    }
+    // Ascending order of Reverse(key) is descending rank
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|std::cmp::Reverse((count, std::cmp::Reverse(word)))| (word, count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_token_char_classification() {
+        assert!(is_token_char(b'a'));
+        assert!(is_token_char(b'Z'));
+        assert!(is_token_char(b'0'));
+        assert!(is_token_char(b'_'));
+        assert!(!is_token_char(b' '));
+        assert!(!is_token_char(b'.'));
+        assert!(!is_token_char(b'\n'));
+    }
+
+    #[test]
+    fn test_word_extraction() {
+        let counter = FastWordCounter::new(Config::default());
+        let mut counts = AHashMap::new();
+
+        let data = b"hello world 123 test_var";
+        counter.extract_words(data, &mut counts);
+
+        assert_eq!(counts.get("hello"), Some(&1));
+        assert_eq!(counts.get("world"), Some(&1));
+        assert_eq!(counts.get("123"), Some(&1));
+        assert_eq!(counts.get("test_var"), Some(&1));
+    }
+
+    #[test]
+    fn test_file_processing() -> Result<()> {
+        let mut temp_file = NamedTempFile::new()?;
+        writeln!(temp_file, "int main() {{")?;
+        writeln!(temp_file, "    printf(\"hello world\");")?;
+        writeln!(temp_file, "    return 0;")?;
+        writeln!(temp_file, "}}")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        let mut counts = AHashMap::new();
+        let stats = Arc::new(Stats::default());
+
+        counter.process_file_mmap(temp_file.path(), &mut counts, &stats)?;
+
+        assert!(counts.contains_key("int"));
+        assert!(counts.contains_key("main"));
+        assert!(counts.contains_key("printf"));
+        assert!(counts.contains_key("hello"));
+        assert!(counts.contains_key("world"));
+        assert!(counts.contains_key("return"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_files_matched() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("notes.txt"), "hello")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        let err = counter.count_directory(dir.path()).unwrap_err();
+        let no_files = err.downcast_ref::<NoFilesMatched>().unwrap();
+
+        assert_eq!(no_files.stats.files_seen, 1);
+        assert_eq!(no_files.stats.filtered_by_extension, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_largest_contributors() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("small.c"), "a b")?;
+        std::fs::write(dir.path().join("big.c"), "a b c d e")?;
+
+        let config = Config {
+            show_progress: false,
+            collect_file_stats: true,
+            ..Config::default()
+        };
+        let counter = FastWordCounter::new(config);
+        counter.count_directory(dir.path())?;
+
+        let top = counter.largest_contributors(1);
+        assert_eq!(top.len(), 1);
+        assert!(top[0].path.ends_with("big.c"));
+        assert_eq!(top[0].tokens, 5);
+        assert_eq!(top[0].lines, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pathological_input_guards() {
+        let config = Config {
+            max_token_len: Some(8),
+            max_unique_per_file: Some(2),
+            ..Config::default()
+        };
+        let counter = FastWordCounter::new(config);
+        let mut counts = AHashMap::new();
+
+        let extracted = counter.extract_words(b"a b a c averyveryverylongtoken d", &mut counts);
+
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts.get("a"), Some(&2));
+        assert_eq!(extracted.tokens, 3);
+        assert_eq!(extracted.oversized_tokens, 1);
+        assert_eq!(extracted.dropped_new_words, 2);
+    }
+
+    #[test]
+    fn test_merge_saturates_on_overflow() {
+        let counter = FastWordCounter::new(Config::default());
+        let mut first = AHashMap::new();
+        first.insert("w".to_string(), u64::MAX - 1);
+        let mut second = AHashMap::new();
+        second.insert("w".to_string(), 5);
+
+        let merged = counter.merge_results(vec![first, second]);
+
+        assert_eq!(merged.get("w"), Some(&u64::MAX));
+        assert_eq!(counter.saturated_counts(), 1);
+    }
+
+    #[test]
+    fn test_parse_token_chars() -> Result<()> {
+        let table = parse_token_chars("a-z_-")?;
+        assert!(table[b'q' as usize]);
+        assert!(table[b'_' as usize]);
+        assert!(table[b'-' as usize]);
+        assert!(!table[b'A' as usize]);
+
+        let counter = FastWordCounter::new(Config {
+            token_chars: Some(table),
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(b"foo-bar Baz", &mut counts);
+        assert_eq!(counts.get("foo-bar"), Some(&1));
+        assert_eq!(counts.get("az"), Some(&1));
+
+        assert!(parse_token_chars("z-a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_directory_multi() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "x1 = 42 + x1;")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            ..Config::default()
+        });
+        let classes = [
+            TokenClass::parse("ident=a-zA-Z0-9_")?,
+            TokenClass::parse("ops=+=;")?,
+        ];
+        let results = counter.count_directory_multi(dir.path(), &classes)?;
+
+        assert_eq!(results["ident"][0], ("x1".to_string(), 2));
+        assert!(results["ops"].contains(&("=".to_string(), 1)));
+        assert!(results["ops"].contains(&(";".to_string(), 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_directory_operators() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "p->x = a << 2; q->y = 1;")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            ..Config::default()
+        });
+        let results = counter.count_directory_operators(dir.path())?;
+
+        assert_eq!(results[0], ("->".to_string(), 2));
+        assert!(results.contains(&("<<".to_string(), 1)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_directory_into_accumulates() -> Result<()> {
+        let first = tempfile::TempDir::new()?;
+        std::fs::write(first.path().join("a.c"), "int x; int y;")?;
+        let second = tempfile::TempDir::new()?;
+        std::fs::write(second.path().join("b.h"), "int z;")?;
+        let mut tar = tar::Builder::new(std::fs::File::create(second.path().join("rel.tar"))?);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(6);
+        header.set_cksum();
+        tar.append_data(&mut header, "rel/c.c", "int w;".as_bytes())?;
+        tar.finish()?;
+
+        for use_mmap in [true, false] {
+            let counter = FastWordCounter::new(Config {
+                use_mmap,
+                archives: true,
+                show_progress: false,
+                ..Config::default()
+            });
+            let mut counts = AHashMap::new();
+            counter.count_directory_into(first.path(), &mut counts)?;
+            counter.count_directory_into(second.path(), &mut counts)?;
+
+            assert_eq!(counts.get("int"), Some(&4));
+            assert_eq!(counts.get("z"), Some(&1));
+            assert_eq!(counts.get("w"), Some(&1));
+
+            // A cancelled run leaves the totals as they were
+            counter.cancellation_token().cancel();
+            assert!(counter.count_directory_into(first.path(), &mut counts).is_err());
+            assert_eq!(counts.get("int"), Some(&4));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_early_flush_matches_final_merge() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        for i in 0..8 {
+            std::fs::write(
+                dir.path().join(format!("{}.c", i)),
+                format!("int x{} = y;", i),
+            )?;
+        }
+
+        let run = |flush_every_files| {
+            FastWordCounter::new(Config {
+                num_threads: 2,
+                show_progress: false,
+                flush_every_files,
+                ..Config::default()
+            })
+            .count_directory(dir.path())
+        };
+
+        let flushed = run(Some(1))?;
+        assert_eq!(flushed, run(None)?);
+        assert_eq!(flushed[0], ("int".to_string(), 8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_run_reports_phases() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int a; int b;")?;
+        std::fs::write(dir.path().join("b.h"), "int c;")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            ..Config::default()
+        });
+        let report = counter.profile_run(dir.path())?;
+
+        assert_eq!(report.files, 2);
+        assert_eq!(report.bytes, 19);
+        assert_eq!(report.unique_words, 4);
+        assert!(report.total() >= report.scan);
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_fails_on_file_errors() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int x;")?;
+        let quiet = |config: Config| Config {
+            show_progress: false,
+            ..config
+        };
+
+        let strict = FastWordCounter::new(quiet(Config::strict()));
+        assert!(strict.count_directory(dir.path()).is_ok());
+
+        let missing = dir.path().join("gone.c");
+        strict.record_error(FileError::new(&missing, FileErrorKind::NotFound, "gone"));
+        let err = strict.count_directory(dir.path()).unwrap_err();
+        assert_eq!(err.downcast_ref::<FilesFailed>().map(|e| e.count), Some(1));
+
+        let fast = FastWordCounter::new(quiet(Config::fast()));
+        fast.record_error(FileError::new(&missing, FileErrorKind::NotFound, "gone"));
+        assert!(fast.count_directory(dir.path()).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fast_preset_differs_from_default() -> Result<()> {
+        let (fast, default) = (Config::fast(), Config::default());
+        assert!(fast.use_mmap && fast.prefault && !default.prefault);
+        assert_eq!(fast.merge_strategy, MergeStrategy::Sharded);
+        assert_ne!(fast.merge_strategy, default.merge_strategy);
+
+        // Same counts, by a different route
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int x = y; int z;")?;
+        std::fs::write(dir.path().join("b.h"), "return x; int int")?;
+        let count = |config: Config| {
+            FastWordCounter::new(Config {
+                show_progress: false,
+                ..config
+            })
+            .count_directory(dir.path())
+        };
+        assert_eq!(count(fast)?, count(default)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_token_mode() {
+        let counter = FastWordCounter::new(Config {
+            token_mode: TokenMode::Unicode,
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(
+            "// Größe des Puffers: größe, test_var 変数".as_bytes(),
+            &mut counts,
+        );
+
+        assert_eq!(counts.get("Größe"), Some(&1));
+        assert_eq!(counts.get("größe"), Some(&1));
+        assert_eq!(counts.get("test_var"), Some(&1));
+        assert!(counts.contains_key("変"));
+        assert!(!counts.contains_key("Gr"));
+    }
+
+    #[test]
+    fn test_write_directory_spills_in_report_order() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "b a c a b a d")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            spill_threshold: Some(2),
+            ..Config::default()
+        });
+        let mut out = Vec::new();
+        assert_eq!(counter.write_directory(dir.path(), &mut out)?, 4);
+
+        let words: Vec<&str> = std::str::from_utf8(&out)?
+            .lines()
+            .map(|line| line.split('|').next().unwrap().trim())
+            .collect();
+        assert_eq!(words, ["a", "b", "c", "d"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_code_aware_skips_comments_and_strings() {
+        let counter = FastWordCounter::new(Config {
+            code_aware: true,
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(
+            b"/* the buffer */ int len = strlen(\"the buffer\"); // the end",
+            &mut counts,
+        );
+
+        assert_eq!(counts.get("strlen"), Some(&1));
+        assert_eq!(counts.get("len"), Some(&1));
+        assert!(!counts.contains_key("the"));
+        assert!(!counts.contains_key("buffer"));
+    }
+
+    #[test]
+    fn test_prefault_counts_match() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int a; int b;".repeat(1000))?;
+
+        let run = |prefault| {
+            FastWordCounter::new(Config {
+                show_progress: false,
+                prefault,
+                ..Config::default()
+            })
+            .count_directory(dir.path())
+        };
+        assert_eq!(run(true)?, run(false)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_keywords_and_stopwords() {
+        let counter = FastWordCounter::new(Config {
+            exclude_keywords: true,
+            stopwords: vec!["tmp".to_string()],
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(
+            b"static int parse_header(void) { int tmp; return tmp; }",
+            &mut counts,
+        );
+
+        let mut words: Vec<&str> = counts.keys().map(String::as_str).collect();
+        words.sort();
+        assert_eq!(words, ["parse_header"]);
+    }
+
+    #[test]
+    fn test_empty_file_with_mmap() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("empty.c"), "")?;
+        std::fs::write(temp_dir.path().join("main.c"), "int main")?;
+
+        let counter = FastWordCounter::new(Config {
+            use_mmap: true,
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let results = counter.count_directory(temp_dir.path())?;
+
+        assert_eq!(results.len(), 2);
+        assert!(counter.errors().is_empty());
+        assert_eq!(counter.stats.files_processed.load(Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_directory_classified() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join("buf.c"),
+            "#define BUF_LEN 64\nchar buf[BUF_LEN]; int n = 0x40;",
+        )?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let classified = counter.count_directory_classified(temp_dir.path())?;
+
+        assert_eq!(classified.macros.get("BUF_LEN"), Some(&2));
+        assert_eq!(classified.numbers.len(), 2);
+        assert_eq!(classified.total(TokenCategory::Number), 2);
+        assert!(classified.identifiers.contains_key("buf"));
+        assert!(!classified.identifiers.contains_key("BUF_LEN"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunked_file_matches_single_pass() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let text = "alpha beta_gamma delta42 ".repeat(200);
+        std::fs::write(temp_dir.path().join("big.c"), &text)?;
+
+        let run = |chunk_bytes, chunk_boundary_policy| {
+            let counter = FastWordCounter::new(Config {
+                use_mmap: true,
+                small_file_bytes: 0,
+                chunk_bytes,
+                chunk_boundary_policy,
+                show_progress: false,
+                print_results: false,
+                ..Config::default()
+            });
+            counter.count_directory(temp_dir.path())
+        };
+
+        // 7-byte chunks land mid-token, so boundaries must be extended
+        let chunked = run(Some(7), ChunkBoundaryPolicy::Exact)?;
+        assert_eq!(chunked, run(None, ChunkBoundaryPolicy::Exact)?);
+        assert_eq!(chunked[0].1, 200);
+
+        // Fixed cuts trade accuracy for skipping the boundary scan
+        let split = run(Some(7), ChunkBoundaryPolicy::Split)?;
+        assert!(split.iter().map(|(_, n)| n).sum::<u64>() > 800);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_directory_symbols_matches_words() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "int main() { int x; }")?;
+        std::fs::write(temp_dir.path().join("b.c"), "int y; return y;")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let interned = counter.count_directory_symbols(temp_dir.path())?;
+
+        assert_eq!(
+            interned.to_words(),
+            counter.count_directory(temp_dir.path())?
+        );
+        assert_eq!(interned.get("int"), Some(3));
+        assert_eq!(interned.symbols().len(), interned.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_strategies_match_per_worker() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for i in 0..8 {
+            std::fs::write(
+                temp_dir.path().join(format!("f{}.c", i)),
+                format!("int f{}(void) {{ return shared + {}; }}", i, i),
+            )?;
+        }
+
+        let run = |merge_strategy| {
+            FastWordCounter::new(Config {
+                num_threads: 3,
+                merge_strategy,
+                show_progress: false,
+                print_results: false,
+                ..Config::default()
+            })
+            .count_directory(temp_dir.path())
+        };
+        let per_worker = run(MergeStrategy::PerWorker)?;
+        assert_eq!(run(MergeStrategy::Sharded)?, per_worker);
+        assert_eq!(run(MergeStrategy::Arena)?, per_worker);
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliases_count_as_canonical_word() {
+        let counter = FastWordCounter::new(Config {
+            aliases: [("NULL", "nullptr"), ("u32", "uint32_t")]
+                .into_iter()
+                .map(|(alias, canonical)| (alias.to_string(), canonical.to_string()))
+                .collect(),
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(b"u32 a = NULL; uint32_t b = nullptr;", &mut counts);
+
+        assert_eq!(counts.get("uint32_t"), Some(&2));
+        assert_eq!(counts.get("nullptr"), Some(&2));
+        assert!(!counts.contains_key("NULL"));
+    }
+
+    #[test]
+    fn test_topk_matches_truncated_full_sort() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join("a.c"),
+            "d d d d c c c b b a e e f f g h h h",
+        )?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let full = counter.count_directory(temp_dir.path())?;
+        for k in [0, 1, 3, 4, 100] {
+            let top = counter.count_directory_topk(temp_dir.path(), k)?;
+            assert_eq!(top, full[..k.min(full.len())]);
+            assert_eq!(counter.unique_words(), full.len() as u64);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_literal_counts_match_full_count() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join("a.c"),
+            "int main(void) { int x = 0; return x; }",
+        )?;
+        std::fs::write(temp_dir.path().join("b.h"), "extern int x; int32_t y;")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        let full: AHashMap<String, u64> = counter
+            .count_directory(temp_dir.path())?
+            .into_iter()
+            .collect();
+        let words = ["int", "x", "return", "missing"].map(String::from);
+        let literal = counter.count_directory_literals(temp_dir.path(), &words)?;
+
+        assert_eq!(literal.len(), 3);
+        for (word, count) in literal {
+            assert_eq!(full.get(&word), Some(&count));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_counts_per_pattern_and_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "// TODO one\n// TODO two")?;
+        std::fs::write(temp_dir.path().join("b.c"), "int x = 0x10;")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        let patterns = ["TODO".to_string(), "0x[0-9a-f]+".to_string()];
+        let report = counter.count_directory_regex(temp_dir.path(), &patterns, true)?;
+
+        assert_eq!(
+            report.totals,
+            [("TODO".to_string(), 2), ("0x[0-9a-f]+".to_string(), 1)]
+        );
+        assert_eq!(report.files.len(), 2);
+        assert_eq!(report.files[0].1, [2, 0]);
+        assert!(
+            counter
+                .count_directory_regex(temp_dir.path(), &patterns, false)?
+                .files
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_orders() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "bb bb bb ccc a")?;
+        std::fs::write(temp_dir.path().join("b.c"), "a ccc")?;
+
+        let words = |sort| -> Result<Vec<String>> {
+            let counter = FastWordCounter::new(Config {
+                sort,
+                ..Config::default()
+            });
+            let results = counter.count_directory(temp_dir.path())?;
+            Ok(results.into_iter().map(|(word, _)| word).collect())
+        };
+
+        assert_eq!(words(SortOrder::CountDesc)?, ["bb", "a", "ccc"]);
+        assert_eq!(words(SortOrder::CountAsc)?, ["a", "ccc", "bb"]);
+        assert_eq!(words(SortOrder::Alpha)?, ["a", "bb", "ccc"]);
+        assert_eq!(words(SortOrder::Length)?, ["ccc", "bb", "a"]);
+        assert_eq!(words(SortOrder::FileFrequency)?, ["a", "ccc", "bb"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tie_breaks_and_stable_runs() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "zeta beta zeta")?;
+        std::fs::write(temp_dir.path().join("b.c"), "alpha gamma beta alpha")?;
+
+        let words = |tie_break, num_threads, top| -> Result<Vec<String>> {
+            let counter = FastWordCounter::new(Config {
+                tie_break,
+                num_threads,
+                top,
+                stable: true,
+                max_unique: Some(3),
+                show_progress: false,
+                ..Config::default()
+            });
+            let results = counter.count_directory(temp_dir.path())?;
+            Ok(results
+                .into_iter()
+                .map(|(word, _)| word)
+                .filter(|word| word != OTHER_BUCKET)
+                .collect())
+        };
+
+        // alpha, beta and zeta are counted twice; gamma (once) is pruned
+        assert_eq!(words(TieBreak::Alpha, 4, None)?, ["alpha", "beta", "zeta"]);
+        assert_eq!(
+            words(TieBreak::FirstSeen, 4, None)?,
+            ["zeta", "beta", "alpha"]
+        );
+        assert_eq!(
+            words(TieBreak::FileOrder, 4, None)?,
+            ["beta", "zeta", "alpha"]
+        );
+        assert_eq!(words(TieBreak::FirstSeen, 1, Some(2))?, ["zeta", "beta"]);
+        for threads in [1, 2, 8] {
+            assert_eq!(
+                words(TieBreak::FileOrder, threads, None)?,
+                ["beta", "zeta", "alpha"]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_thresholds_apply_before_top() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "int int int int x x x y y z")?;
+
+        let counter = FastWordCounter::new(Config {
+            min_count: Some(2),
+            max_count: Some(3),
+            top: Some(1),
+            show_progress: false,
+            ..Config::default()
+        });
+        assert_eq!(
+            counter.count_directory(temp_dir.path())?,
+            vec![("x".to_string(), 3)]
+        );
+
+        let counter = FastWordCounter::new(Config {
+            min_count: Some(2),
+            sort: SortOrder::Alpha,
+            show_progress: false,
+            ..Config::default()
+        });
+        let words: Vec<String> = counter
+            .count_directory(temp_dir.path())?
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect();
+        assert_eq!(words, ["int", "x", "y"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shares_use_the_whole_run_as_total() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "int int int x y")?;
+
+        let counter = FastWordCounter::new(Config {
+            shares: true,
+            top: Some(2),
+            show_progress: false,
+            ..Config::default()
+        });
+        let results = counter.count_directory(temp_dir.path())?;
+        assert_eq!(counter.total_count(), 5);
+
+        let mut table = Vec::new();
+        counter.write_results(&results, &mut table)?;
+        let rows: Vec<&str> = std::str::from_utf8(&table)?
+            .lines()
+            .map(str::trim_start)
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                "int |        3 |  60.00% |  60.00%",
+                "x |        1 |  20.00% |  80.00%"
+            ]
+        );
+        assert_eq!(
+            counter.count_report(temp_dir.path())?.shares()[1].cumulative,
+            80.0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_seen_order_tracks_first_and_last_file() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        for (name, text) in [("a.c", "x y"), ("b.c", "y"), ("c.c", "x z")] {
+            std::fs::write(temp_dir.path().join(name), text)?;
+        }
+
+        let counter = FastWordCounter::new(Config {
+            seen_order: Some(SeenOrder::Traversal),
+            show_progress: false,
+            ..Config::default()
+        });
+        counter.count_directory(temp_dir.path())?;
+
+        let files = |word| counter.seen(word).map(|seen| (seen.first, seen.last));
+        let path = |name| temp_dir.path().join(name);
+        assert_eq!(files("x"), Some((path("a.c"), path("c.c"))));
+        assert_eq!(files("y"), Some((path("a.c"), path("b.c"))));
+        assert_eq!(files("z"), Some((path("c.c"), path("c.c"))));
+        assert_eq!(files("w"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_prints_lines_and_counts() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let path = temp_dir.path().join("a.c");
+        std::fs::write(&path, "int a;\n// TODO fix TODO\nint b;\n")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        let mut out = Vec::new();
+        let report = counter.grep_directory(temp_dir.path(), &["TODO".to_string()], &mut out)?;
+
+        assert_eq!(
+            String::from_utf8(out)?,
+            format!("{}:2:// TODO fix TODO\n", path.display())
+        );
+        assert_eq!(report.totals, [("TODO".to_string(), 2)]);
+        assert_eq!(report.files, [(path, vec![2])]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_top_limits_results() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "a a a b b c d")?;
+
+        for sort in [SortOrder::CountDesc, SortOrder::Alpha] {
+            let counter = FastWordCounter::new(Config {
+                top: Some(2),
+                sort,
+                ..Config::default()
+            });
+            let mut full = FastWordCounter::new(Config {
+                sort,
+                ..Config::default()
+            })
+            .count_directory(temp_dir.path())?;
+            full.truncate(2);
+
+            assert_eq!(counter.count_directory(temp_dir.path())?, full);
+            assert_eq!(counter.unique_words(), 4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_policy_returns_errors_in_report() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int x;")?;
+        let counter = FastWordCounter::new(Config {
+            error_policy: ErrorPolicy::Collect,
+            ..Config::default()
+        });
+
+        let missing = dir.path().join("gone.c");
+        counter.record_error(FileError::new(&missing, FileErrorKind::NotFound, "gone"));
+        let report = counter.count_report(dir.path())?;
+
+        assert_eq!(report.len(), 2);
+        assert_eq!(report.errors().len(), 1);
+        assert_eq!(report.errors()[0].path, missing);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_file_list_and_reader() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let listed = dir.path().join("notes.txt");
+        std::fs::write(&listed, "alpha beta alpha")?;
+        std::fs::write(dir.path().join("skipped.c"), "gamma")?;
+
+        let counter = FastWordCounter::new(Config::default());
+        assert_eq!(
+            counter.count_file_list(vec![listed])?,
+            [("alpha".to_string(), 2), ("beta".to_string(), 1)]
+        );
+        assert!(counter.count_file_list(Vec::new()).is_err());
+
+        let counts = counter.count_reader(&b"int x; int y;"[..])?;
+        assert_eq!(counts[0], ("int".to_string(), 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_paths_mixes_files_and_directories() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        std::fs::write(dir.path().join("sub/a.c"), "alpha beta")?;
+        std::fs::write(dir.path().join("notes.txt"), "alpha")?;
+        std::fs::write(dir.path().join("other.txt"), "gamma")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        // The named .txt file is counted; the walk still filters by extension,
+        // and sub/a.c is reached twice but counted once
+        let results = counter.count_paths(&[
+            dir.path().to_path_buf(),
+            dir.path().join("sub"),
+            dir.path().join("notes.txt"),
+        ])?;
+        assert_eq!(results, [("alpha".to_string(), 2), ("beta".to_string(), 1)]);
+        assert_eq!(counter.discovery_stats().duplicate_files, 1);
+
+        let missing = counter.count_paths(&[dir.path().join("missing")]);
+        assert!(missing.unwrap_err().is::<NoFilesMatched>());
+        assert!(counter.count_paths(&[]).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_file_names_are_counted_and_encoded() -> Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = tempfile::TempDir::new()?;
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.c");
+        std::fs::write(dir.path().join(name), "int caf;\nint x;")?;
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            print_results: false,
+            path_encoding: PathEncoding::Percent,
+            collect_file_stats: true,
+            ..Config::default()
+        });
+        let mut grep = Vec::new();
+        counter.grep_directory(dir.path(), &["caf".to_string()], &mut grep)?;
+        let grep = String::from_utf8(grep)?;
+        assert!(grep.ends_with("/caf%E9.c:1:int caf;\n"), "{}", grep);
+        assert_eq!(
+            counter.count_directory(dir.path())?[0],
+            ("int".to_string(), 2)
+        );
+        assert_eq!(counter.file_stats()[0].path, dir.path().join(name));
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_stats_report_skipped_and_failed_files() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("empty.c"), "// ;")?;
+        std::fs::write(dir.path().join("blob.c"), b"ELF\0\0int x;")?;
+        std::fs::write(dir.path().join("code.c"), "int x;")?;
+
+        for use_mmap in [true, false] {
+            let counter = FastWordCounter::new(Config {
+                use_mmap,
+                skip_binary: true,
+                collect_file_stats: true,
+                show_progress: false,
+                print_results: false,
+                ..Config::default()
+            });
+            counter.count_file_list(vec![
+                dir.path().join("empty.c"),
+                dir.path().join("blob.c"),
+                dir.path().join("code.c"),
+                dir.path().join("missing.c"),
+            ])?;
+
+            let mut files = counter.file_stats();
+            files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+            let statuses: Vec<_> = files.iter().map(|f| (f.status, f.tokens)).collect();
+            assert_eq!(
+                statuses,
+                [
+                    (FileStatus::SkippedBinary, 0),
+                    (FileStatus::Ok, 2),
+                    (FileStatus::Ok, 0),
+                    (FileStatus::Failed(FileErrorKind::NotFound), 0),
+                ]
+            );
+            assert_eq!(counter.largest_contributors(10).len(), 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_members_are_counted_like_files() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("loose.c"), "int loose;")?;
+        let mut tar = tar::Builder::new(std::fs::File::create(dir.path().join("rel.tar"))?);
+        for (name, contents) in [("rel/a.c", "int a;"), ("rel/doc.txt", "int doc;")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_cksum();
+            tar.append_data(&mut header, name, contents.as_bytes())?;
+        }
+        tar.finish()?;
+
+        for use_mmap in [true, false] {
+            let counter = FastWordCounter::new(Config {
+                use_mmap,
+                archives: true,
+                collect_file_stats: true,
+                show_progress: false,
+                print_results: false,
+                ..Config::default()
+            });
+            let results = counter.count_directory(dir.path())?;
+            assert_eq!(results[0], ("int".to_string(), 2));
+            assert!(results.contains(&("a".to_string(), 1)));
+            assert!(!results.iter().any(|(w, _)| w == "doc"));
+            assert!(
+                counter
+                    .file_stats()
+                    .iter()
+                    .any(|f| f.path == dir.path().join("rel.tar/rel/a.c"))
+            );
+
+            // Modes that read files directly leave archives out
+            let wc = counter.count_directory_wc(dir.path())?;
+            assert_eq!(wc.files.len(), 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_counts_by_extension_add_up_to_totals() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.rs"), "fn main() {}")?;
+        std::fs::write(dir.path().join("b.c"), "int main;")?;
+        let mut gz = flate2::write::GzEncoder::new(
+            std::fs::File::create(dir.path().join("c.c.gz"))?,
+            flate2::Compression::default(),
+        );
+        gz.write_all(b"int x;")?;
+        gz.finish()?;
+
+        let counter = FastWordCounter::new(Config {
+            extensions: vec!["c".to_string(), "rs".to_string()],
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let counts = counter.count_directory_by_extension(dir.path())?;
+        // Groups follow `Config::extensions`; c.c.gz counts as c
+        let names: Vec<&str> = counts
+            .by_extension
+            .iter()
+            .map(|(e, _)| e.as_str())
+            .collect();
+        assert_eq!(names, ["c", "rs"]);
+        assert_eq!(counts.by_extension[0].1[0], ("int".to_string(), 2));
+        assert_eq!(counts.totals[1], ("main".to_string(), 2));
+        assert_eq!(counter.unique_words(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_words_count_names_once() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir_all(dir.path().join("net_util/tcp"))?;
+        std::fs::write(dir.path().join("net_util/tcp/send_buf.c"), "int send;")?;
+        std::fs::write(dir.path().join("net_util/tcp/recv_buf.c"), "int recv;")?;
+        std::fs::write(dir.path().join("net_util/notes.txt"), "unlisted")?;
+
+        let counter = |path_words| {
+            FastWordCounter::new(Config {
+                path_words,
+                show_progress: false,
+                print_results: false,
+                ..Config::default()
+            })
+        };
+        // Shared directories are named once; contents are never read
+        let only = counter(PathWords::Only);
+        let results = only.count_directory(dir.path())?;
+        assert_eq!(
+            results,
+            [
+                ("c".to_string(), 2),
+                ("net_util".to_string(), 1),
+                ("recv_buf".to_string(), 1),
+                ("send_buf".to_string(), 1),
+                ("tcp".to_string(), 1)
+            ]
+        );
+        assert_eq!(only.progress().files_processed, 0);
+
+        let results = counter(PathWords::Include).count_directory(dir.path())?;
+        assert_eq!(results[0], ("c".to_string(), 2));
+        assert!(results.contains(&("send".to_string(), 1)));
+        assert!(results.contains(&("tcp".to_string(), 1)));
+
+        // A file named directly contributes only its own name
+        let file = dir.path().join("net_util/tcp/send_buf.c");
+        let results = counter(PathWords::Only).count_paths(&[file])?;
+        assert_eq!(results.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_identifiers_expands_sub_tokens() {
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            split_identifiers: true,
+            expansions: [("cfg", "config")]
+                .into_iter()
+                .map(|(a, e)| (a.to_string(), e.to_string()))
+                .collect(),
+            ..Config::default()
+        });
+
+        let mut counts = AHashMap::new();
+        counter.extract_words(b"cfgPath config_path HTTPServer MAX_CFG", &mut counts);
+
+        assert_eq!(counts.get("config"), Some(&3));
+        assert_eq!(counts.get("path"), Some(&2));
+        assert_eq!(counts.get("http"), Some(&1));
+        assert_eq!(counts.get("server"), Some(&1));
+        assert_eq!(counts.get("max"), Some(&1));
+        assert_eq!(counts.get("cfg"), None);
+    }
+
+    #[test]
+    fn test_transform_rewrites_and_drops_tokens() {
+        let counter = FastWordCounter::new(Config {
+            transform: Some(TokenTransform::new(|word| {
+                if word.starts_with(|c: char| c.is_ascii_digit()) {
+                    None
+                } else if word == "u32" {
+                    // A static replacement of the same length
+                    Some(Cow::Borrowed("u64"))
+                } else if word.bytes().any(|b| b.is_ascii_uppercase()) {
+                    Some(Cow::Owned(word.to_ascii_lowercase()))
+                } else {
+                    Some(Cow::Borrowed(word))
+                }
+            })),
+            ..Config::default()
+        });
+        let mut counts = AHashMap::new();
+        counter.extract_words(b"Foo foo FOO 42 bar u32 u64", &mut counts);
+
+        assert_eq!(counts.get("foo"), Some(&3));
+        assert_eq!(counts.get("bar"), Some(&1));
+        assert_eq!(counts.get("u64"), Some(&2));
+        assert_eq!(counts.len(), 3);
+    }
+
+    #[test]
+    fn test_results_round_trip_and_verify() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.c"), "int x = y; int z; x++;")?;
+        std::fs::write(temp_dir.path().join("b.c"), "return x; int int")?;
+        let out = tempfile::TempDir::new()?;
+
+        for shares in [false, true] {
+            let counter = FastWordCounter::new(Config {
+                shares,
+                show_progress: false,
+                ..Config::default()
+            });
+            let results = counter.count_directory(temp_dir.path())?;
+            counter.verify(&results)?;
+
+            // Text table, as written with --output
+            let text = out.path().join("results.txt");
+            let mut file = std::fs::File::create(&text)?;
+            writeln!(
+                file,
+                "{}",
+                artifact::header(artifact::RESULTS, artifact::RESULTS_VERSION)
+            )?;
+            counter.write_results(&results, &mut file)?;
+            let loaded = CountReport::load(&text)?;
+            loaded.validate(Some(10))?;
+            assert_eq!(loaded.as_slice(), results);
+
+            // Binary snapshot
+            let bin = out.path().join("results.bin");
+            snapshot_file::save(&bin, &results, false)?;
+            assert_eq!(snapshot_file::load(&bin)?, results);
+        }
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            ..Config::default()
+        });
+        let mut results = counter.count_directory(temp_dir.path())?;
+
+        // Reordered rows and miscounts are caught
+        results.swap(0, 1);
+        assert!(counter.verify(&results).is_err());
+        results.swap(0, 1);
+        counter.verify(&results)?;
+        results[0].1 += 1;
+        assert!(counter.verify(&results).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_borrowed_keys_match_per_token_counting() {
+        let data = b"int x = y; /* int */ int longidentifier; free(x); x_1 int";
+        let config = |transform| Config {
+            show_progress: false,
+            code_aware: true,
+            max_token_len: Some(10),
+            stopwords: vec!["free".to_string()],
+            aliases: [("y".to_string(), "x".to_string())].into_iter().collect(),
+            transform,
+            ..Config::default()
+        };
+        let borrowed = FastWordCounter::new(config(None));
+        assert!(borrowed.counts_borrowed_keys());
+        // An identity transform takes the per-token path
+        let per_token = FastWordCounter::new(config(Some(TokenTransform::new(|word| {
+            Some(Cow::Borrowed(word))
+        }))));
+        assert!(!per_token.counts_borrowed_keys());
+
+        let count = |counter: &FastWordCounter| {
+            // Words already in the worker's map are added to
+            let mut counts: AHashMap<String, u64> = [("int".to_string(), 5)].into_iter().collect();
+            let extracted = counter.extract_words(data, &mut counts);
+            (counts, extracted)
+        };
+        let (counts, extracted) = count(&borrowed);
+        assert_eq!((counts.clone(), extracted), count(&per_token));
+        assert_eq!(counts.get("int"), Some(&8));
+        assert_eq!(counts.get("x"), Some(&3));
+        assert_eq!(counts.get("free"), None);
+        assert_eq!(extracted.oversized_tokens, 1);
+        assert_eq!(extracted.tokens, 7);
+    }
 }
@@ -0,0 +1,89 @@
+use ahash::AHashMap;
+use std::path::PathBuf;
+
+const SPDX_TAG: &[u8] = b"SPDX-License-Identifier:";
+
+// Tokens that commonly appear in license headers
+pub const LICENSE_PHRASES: [&str; 8] = [
+    "Copyright",
+    "GPL",
+    "LGPL",
+    "MIT",
+    "Apache",
+    "BSD",
+    "MPL",
+    "License",
+];
+
+// License markers found in a single file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileLicense {
+    pub spdx: Vec<String>,
+    pub phrases: AHashMap<String, u64>,
+}
+
+impl FileLicense {
+    pub fn has_marker(&self) -> bool {
+        !self.spdx.is_empty() || !self.phrases.is_empty()
+    }
+}
+
+// Aggregate result of a license scan
+#[derive(Debug, Clone, Default)]
+pub struct LicenseReport {
+    // SPDX expressions and the number of files declaring each
+    pub spdx: Vec<(String, u64)>,
+    // License-phrase tokens and their total occurrences
+    pub phrases: Vec<(String, u64)>,
+    // Files with neither an SPDX tag nor a license phrase
+    pub unlicensed: Vec<PathBuf>,
+}
+
+// Extract SPDX identifiers and license-phrase tokens from one file
+pub(crate) fn scan_file(data: &[u8]) -> FileLicense {
+    let mut found = FileLicense::default();
+
+    for mut line in data.split(|&b| b == b'\n') {
+        if let Some(pos) = find(line, SPDX_TAG) {
+            let value = String::from_utf8_lossy(&line[pos + SPDX_TAG.len()..]);
+            let value = value
+                .trim()
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim();
+            if !value.is_empty() {
+                found.spdx.push(value.to_string());
+            }
+            // The tag itself should not count as a license phrase
+            line = &line[..pos];
+        }
+
+        for word in line.split(|b| !b.is_ascii_alphanumeric()) {
+            if let Some(phrase) = LICENSE_PHRASES.iter().find(|p| p.as_bytes() == word) {
+                *found.phrases.entry((*phrase).to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    found
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_file() {
+        let data = b"/* SPDX-License-Identifier: GPL-2.0-only */\n// Copyright 2024\nint x;\n";
+        let found = scan_file(data);
+
+        assert_eq!(found.spdx, vec!["GPL-2.0-only".to_string()]);
+        assert_eq!(found.phrases.get("Copyright"), Some(&1));
+        assert!(found.has_marker());
+        assert!(!scan_file(b"int main() {}").has_marker());
+    }
+}
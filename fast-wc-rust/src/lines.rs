@@ -0,0 +1,105 @@
+use std::fmt;
+
+// Upper bounds (inclusive) of the line-length histogram buckets; the last
+// bucket holds everything longer
+pub const LINE_BUCKETS: [usize; 5] = [40, 80, 120, 160, 200];
+
+// Line-length statistics for one file or an aggregate of files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineStats {
+    pub lines: u64,
+    pub max_line_len: u64,
+    pub total_line_len: u64,
+    pub histogram: [u64; LINE_BUCKETS.len() + 1],
+}
+
+impl LineStats {
+    // Scan `data` once, measuring every line (a trailing partial line counts)
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut stats = LineStats::default();
+        let mut start = 0;
+
+        for (i, &byte) in data.iter().enumerate() {
+            if byte == b'\n' {
+                stats.add_line(i - start);
+                start = i + 1;
+            }
+        }
+        if start < data.len() {
+            stats.add_line(data.len() - start);
+        }
+
+        stats
+    }
+
+    fn add_line(&mut self, len: usize) {
+        self.lines += 1;
+        self.max_line_len = self.max_line_len.max(len as u64);
+        self.total_line_len += len as u64;
+        let bucket = LINE_BUCKETS
+            .iter()
+            .position(|&bound| len <= bound)
+            .unwrap_or(LINE_BUCKETS.len());
+        self.histogram[bucket] += 1;
+    }
+
+    // Fold another file's stats into this aggregate
+    pub fn merge(&mut self, other: &LineStats) {
+        self.lines += other.lines;
+        self.max_line_len = self.max_line_len.max(other.max_line_len);
+        self.total_line_len += other.total_line_len;
+        for (a, b) in self.histogram.iter_mut().zip(other.histogram) {
+            *a += b;
+        }
+    }
+
+    pub fn mean_line_len(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.total_line_len as f64 / self.lines as f64
+        }
+    }
+
+    // Human-readable labels for each histogram bucket
+    pub fn bucket_labels() -> Vec<String> {
+        let mut labels = Vec::with_capacity(LINE_BUCKETS.len() + 1);
+        let mut low = 0;
+        for bound in LINE_BUCKETS {
+            labels.push(format!("{}-{}", low, bound));
+            low = bound + 1;
+        }
+        labels.push(format!("{}+", low));
+        labels
+    }
+}
+
+impl fmt::Display for LineStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} lines, max {} cols, mean {:.1} cols",
+            self.lines,
+            self.max_line_len,
+            self.mean_line_len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_stats() {
+        let long = "x".repeat(90);
+        let data = format!("ab\n\n{}\ntail", long);
+        let stats = LineStats::from_bytes(data.as_bytes());
+
+        assert_eq!(stats.lines, 4);
+        assert_eq!(stats.max_line_len, 90);
+        assert_eq!(stats.total_line_len, 96);
+        assert_eq!(stats.histogram[0], 3);
+        assert_eq!(stats.histogram[2], 1);
+    }
+}
@@ -0,0 +1,84 @@
+use crate::TokenTable;
+use aho_corasick::{AhoCorasick, MatchKind};
+use anyhow::{Result, bail};
+
+// Counts a fixed list of words with one Aho-Corasick pass over the raw bytes,
+// without tokenizing or building a frequency map. A match only counts when it
+// is a whole token: the bytes on either side must not be token characters.
+//
+// Leftmost-longest matching keeps this exact: a match rejected for starting
+// mid-token lies inside that token, and one rejected for ending mid-token can
+// only hide a longer target spanning the whole token, which leftmost-longest
+// would have preferred.
+pub struct LiteralMatcher {
+    automaton: AhoCorasick,
+    words: Vec<String>,
+}
+
+impl LiteralMatcher {
+    // Build a matcher for `words`, dropping duplicates. Every word must be a
+    // single token under `table`, or it could never match.
+    pub fn new(words: &[String], table: &TokenTable) -> Result<Self> {
+        let mut unique: Vec<String> = Vec::with_capacity(words.len());
+        for word in words {
+            if word.is_empty() || !word.bytes().all(|b| table[b as usize]) {
+                bail!("'{}' is not a single token", word);
+            }
+            if !unique.contains(word) {
+                unique.push(word.clone());
+            }
+        }
+
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&unique)?;
+        Ok(Self {
+            automaton,
+            words: unique,
+        })
+    }
+
+    // Target words, indexed like the counts filled in by `count`
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    // Add the whole-token occurrences of each word in `data` to `counts`,
+    // which must have one slot per word
+    pub fn count(&self, data: &[u8], table: &TokenTable, counts: &mut [u64]) {
+        for found in self.automaton.find_iter(data) {
+            let before = found.start().checked_sub(1).map(|i| data[i]);
+            let after = data.get(found.end()).copied();
+            if before.is_some_and(|b| table[b as usize]) || after.is_some_and(|b| table[b as usize])
+            {
+                continue;
+            }
+            let count = &mut counts[found.pattern().as_usize()];
+            *count = count.saturating_add(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TOKEN_CHARS;
+
+    #[test]
+    fn test_counts_whole_tokens_only() -> Result<()> {
+        let words = ["int", "int32_t", "x", "int"].map(String::from);
+        let matcher = LiteralMatcher::new(&words, &TOKEN_CHARS)?;
+        assert_eq!(matcher.words(), ["int", "int32_t", "x"]);
+
+        let mut counts = vec![0; matcher.words().len()];
+        matcher.count(
+            b"int x; int32_t xint = print(x); uint8_t int",
+            &TOKEN_CHARS,
+            &mut counts,
+        );
+        assert_eq!(counts, [2, 1, 2]);
+
+        assert!(LiteralMatcher::new(&["a b".to_string()], &TOKEN_CHARS).is_err());
+        Ok(())
+    }
+}
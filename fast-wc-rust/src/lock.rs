@@ -0,0 +1,69 @@
+use crate::error::Locked;
+use anyhow::{Context, Result};
+use std::fs::{File, TryLockError};
+use std::path::{Path, PathBuf};
+
+// Name of the lock file created inside a locked directory
+pub const LOCK_FILE: &str = ".fast-wc.lock";
+
+// Exclusive advisory lock on a directory of shared state (results, caches,
+// indexes), held until dropped. Concurrent runs pointed at the same
+// directory serialize on it instead of interleaving writes. The lock file is
+// left in place; only the OS lock on it matters.
+#[derive(Debug)]
+pub struct DirLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl DirLock {
+    // Lock `dir`, creating it if needed. If another process holds the lock,
+    // block until it is released when `wait` is set, else fail with `Locked`.
+    pub fn acquire(dir: &Path, wait: bool) -> Result<Self> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(LOCK_FILE);
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file {}", path.display()))?;
+
+        if wait {
+            file.lock()
+                .with_context(|| format!("Failed to lock {}", path.display()))?;
+        } else {
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) => return Err(Locked { path }.into()),
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+                }
+            }
+        }
+        Ok(Self { path, _file: file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_fails_until_released() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let held = DirLock::acquire(dir.path(), false)?;
+
+        let err = DirLock::acquire(dir.path(), false).unwrap_err();
+        assert_eq!(err.downcast_ref::<Locked>().unwrap().path, held.path());
+
+        drop(held);
+        DirLock::acquire(dir.path(), false)?;
+        Ok(())
+    }
+}
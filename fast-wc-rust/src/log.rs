@@ -0,0 +1,114 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+// Output format for diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "unknown log format '{}' (expected text or json)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Info => "info",
+            Level::Warn => "warn",
+            Level::Error => "error",
+        })
+    }
+}
+
+// Routes diagnostics either as plain text (info on stdout, warnings and
+// errors on stderr) or as one JSON object per line on stderr
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Logger {
+    format: LogFormat,
+}
+
+impl Logger {
+    pub fn new(format: LogFormat) -> Self {
+        Self { format }
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    pub fn info(&self, event: &str, message: &str, fields: &[(&str, Value)]) {
+        self.emit(Level::Info, event, message, fields);
+    }
+
+    pub fn warn(&self, event: &str, message: &str, fields: &[(&str, Value)]) {
+        self.emit(Level::Warn, event, message, fields);
+    }
+
+    pub fn error(&self, event: &str, message: &str, fields: &[(&str, Value)]) {
+        self.emit(Level::Error, event, message, fields);
+    }
+
+    pub fn emit(&self, level: Level, event: &str, message: &str, fields: &[(&str, Value)]) {
+        match self.format {
+            LogFormat::Text => match level {
+                Level::Info => println!("{}", message),
+                Level::Warn | Level::Error => eprintln!("{}", message),
+            },
+            LogFormat::Json => eprintln!("{}", Self::json_line(level, event, message, fields)),
+        }
+    }
+
+    // Build a single-line JSON record for a diagnostic
+    fn json_line(level: Level, event: &str, message: &str, fields: &[(&str, Value)]) -> String {
+        let mut record = Map::new();
+        record.insert("level".into(), Value::from(level.to_string()));
+        record.insert("event".into(), Value::from(event));
+        record.insert("message".into(), Value::from(message));
+        for (key, value) in fields {
+            record.insert((*key).into(), value.clone());
+        }
+        Value::Object(record).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_line_contains_fields() {
+        let line = Logger::json_line(
+            Level::Error,
+            "file_error",
+            "Error reading a.c",
+            &[("path", json!("a.c"))],
+        );
+        let parsed: Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(parsed["level"], "error");
+        assert_eq!(parsed["event"], "file_error");
+        assert_eq!(parsed["path"], "a.c");
+    }
+}
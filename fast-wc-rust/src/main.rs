@@ -1,75 +1,1825 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use clap::Parser;
-use fast_wc_rust::{Config, FastWordCounter};
-use std::path::PathBuf;
-use std::time::Instant;
+use fast_wc_rust::artifact;
+use fast_wc_rust::fold::{self, PairFormat};
+use fast_wc_rust::hooks::Stage;
+use fast_wc_rust::report::DEFAULT_BUCKETS;
+use fast_wc_rust::settings::{self, Source};
+use fast_wc_rust::snapshot_file;
+use fast_wc_rust::{
+    AtomicFile, CaseGroup, Casing, Config, CountReport, CounterBuilder, DirLock, FastWordCounter,
+    IoMode, LogFormat, Logger, NoFilesMatched, OutputFormat, PathEncoding, PathWords, ProfileReport, SeenOrder,
+    Settings, SnapshotStore, SortOrder, TokenCategory, TokenClass, TokenMode, WcCounts,
+};
+use serde_json::json;
+use std::ffi::OsStr;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "huge-pages")]
+#[global_allocator]
+static GLOBAL: fast_wc_rust::HugePageAlloc = fast_wc_rust::HugePageAlloc;
+
+// Locations sampled per word for `where` requests in --lsp-ish mode
+const DEFAULT_RPC_SAMPLES: usize = 10;
 
 #[derive(Parser)]
 #[command(name = "fast-wc-rust")]
 #[command(about = "High-performance word counter for C/H files")]
 #[command(version)]
-struct Args {
-    /// Directory to scan for .c and .h files
-    directory: PathBuf,
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // Without a subcommand, the arguments are `count`'s
+    #[command(flatten)]
+    count: CountArgs,
+}
+
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("locked_outputs").args(["output", "cache"]).multiple(true)))]
+struct CountArgs {
+    /// Directories and files to count (directories are scanned for source files), or - to count text read from stdin
+    #[arg(value_name = "PATH", required_unless_present = "files_from")]
+    paths: Vec<PathBuf>,
+
+    /// Count the files listed in PATH, one per line (- reads the list from stdin), instead of scanning a directory
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "paths", "operators", "endpoints", "classify", "classes", "wc", "grep",
+            "count_regex", "words", "licenses", "complexity", "casing", "ownership", "lsp_ish"
+        ]
+    )]
+    files_from: Option<PathBuf>,
 
-    /// Number of threads to use
-    #[arg(short = 'n', long, default_value_t = num_cpus::get())]
-    threads: usize,
+    /// Number of threads to use [default: number of CPUs]
+    #[arg(short = 'n', long)]
+    threads: Option<usize>,
 
-    /// Use memory mapping for file I/O
-    #[arg(short = 'm', long, default_value_t = true)]
+    /// Use memory mapping for file I/O [default: true, false on Windows]
+    #[arg(short = 'm', long, overrides_with = "no_mmap")]
     mmap: bool,
 
+    /// Read files instead of memory-mapping them
+    #[arg(long, overrides_with = "mmap")]
+    no_mmap: bool,
+
     /// Enable parallel merging
     #[arg(short = 'p', long)]
     parallel_merge: bool,
 
-    /// Silent mode (no progress output)
+    /// Silent mode (no progress output and no results)
     #[arg(short = 's', long)]
     silent: bool,
 
+    /// Hide progress output but still print results
+    #[arg(short = 'q', long)]
+    quiet: bool,
+
+    /// Add each word's percent of all tokens and the cumulative percent as columns
+    #[arg(long)]
+    shares: bool,
+
+    /// Diagnostic output format (text or json); json is written to stderr
+    #[arg(long)]
+    log_format: Option<LogFormat>,
+
+    /// Also list the N files contributing the most tokens
+    #[arg(long, value_name = "N")]
+    by_file_size: Option<usize>,
+
+    /// Skip tokens longer than this many bytes and flag the file
+    #[arg(long, value_name = "BYTES")]
+    max_token_len: Option<usize>,
+
+    /// Stop adding new words from a file after this many and flag the file
+    #[arg(long, value_name = "N")]
+    max_unique_per_file: Option<usize>,
+
+    /// Keep only the N most frequent words, lumping the rest into <other>
+    #[arg(long, value_name = "N")]
+    max_unique: Option<usize>,
+
+    /// Show up to K randomly sampled locations for each word
+    #[arg(long, value_name = "K")]
+    sample_occurrences: Option<usize>,
+
+    /// Characters that make up a token, e.g. "a-zA-Z0-9_-" (default: a-zA-Z0-9_)
+    #[arg(long, value_name = "SPEC")]
+    token_chars: Option<String>,
+
+    /// Hand each worker's partial counts to the merger every N files
+    #[arg(long, value_name = "N")]
+    flush_every_files: Option<usize>,
+
+    /// Hand a worker's partial counts to the merger once it holds N words
+    #[arg(long, value_name = "N")]
+    flush_every_entries: Option<usize>,
+
+    /// File extension to count, e.g. rs (repeatable) [default: c, h]
+    #[arg(long = "ext", value_name = "EXT")]
+    extensions: Vec<String>,
+
+    /// Only count files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "include", value_name = "GLOB")]
+    include_globs: Vec<String>,
+
+    /// Skip files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude_globs: Vec<String>,
+
+    /// Exit with an error if any file cannot be read instead of skipping it
+    #[arg(long)]
+    fail_on_error: bool,
+
+    /// Follow symlinked directories and files during discovery (loops are skipped)
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Descend at most N directory levels (1 = only files directly in DIRECTORY)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// How file paths are printed: lossy (invalid UTF-8 shown as U+FFFD) or percent (exact bytes as %XX)
+    #[arg(long, value_name = "ENCODING")]
+    path_encoding: Option<PathEncoding>,
+
+    /// Unreadable files: skip (with a summary), abort the run, or collect silently
+    #[arg(long, value_name = "POLICY", conflicts_with = "fail_on_error")]
+    error_policy: Option<String>,
+
+    /// Choose threads, mmap, merge strategy and flushing from the input files
+    #[arg(long)]
+    auto_tune: bool,
+
+    /// Tokenizer: bytes (token chars) or unicode (UAX #29 word boundaries)
+    #[arg(long, value_name = "MODE")]
+    token_mode: Option<TokenMode>,
+
+    /// Sort full result dumps on disk in runs of N words once there are more
+    #[arg(long, value_name = "N")]
+    spill_threshold: Option<usize>,
+
+    /// Ignore comments and string/char literals so only code is counted
+    #[arg(long)]
+    code_aware: bool,
+
+    /// Pre-fault mapped pages (MAP_POPULATE) instead of faulting while tokenizing
+    #[arg(long)]
+    prefault: bool,
+
+    /// Drop C/C++ keywords (int, return, if, ...) from the results
+    #[arg(long)]
+    exclude_keywords: bool,
+
+    /// Drop the words listed in this file (one per line, # comments)
+    #[arg(long, value_name = "PATH")]
+    stopwords: Option<PathBuf>,
+
+    /// Count aliases as their canonical word, from a TOML file of canonical = ["alias", ...]
+    #[arg(long, value_name = "PATH")]
+    aliases: Option<PathBuf>,
+
+    /// Count the lowercased sub-tokens of identifiers (parseHttpHeader -> parse, http, header), expanded via the config file's [expansions] table
+    #[arg(long)]
+    split_identifiers: bool,
+
+    /// File reads: cached (page cache, mmap or read) or direct (O_DIRECT)
+    #[arg(long, value_name = "MODE")]
+    io_mode: Option<IoMode>,
+
+    /// With mmap, read files up to this size into a reused buffer instead (0 maps all) [default: 16384]
+    #[arg(long, value_name = "BYTES")]
+    small_file_bytes: Option<usize>,
+
+    /// Hash each file's contents (xxh3) in the same pass; shown by --file-stats
+    #[arg(long)]
+    content_hash: bool,
+
+    /// Decompress gzip/zstd files (and count foo.c.gz as a .c file) [default: true]
+    #[arg(long, value_name = "BOOL")]
+    decompress: Option<bool>,
+
+    /// Count source files inside .tar, .tar.gz, .tgz, .tar.zst and .zip archives (word counts only)
+    #[arg(long)]
+    archives: bool,
+
+    /// Count the words in file and directory names instead of file contents (no file is read)
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "include_paths", "files_from", "operators", "endpoints", "classify", "classes", "wc",
+            "grep", "count_regex", "words", "licenses", "complexity", "casing", "ownership", "watch", "cache", "format"
+        ]
+    )]
+    paths_only: bool,
+
+    /// Count the words in file and directory names as well as file contents
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "files_from", "operators", "endpoints", "classify", "classes", "wc", "grep",
+            "count_regex", "words", "licenses", "complexity", "casing", "ownership", "watch", "cache", "format"
+        ]
+    )]
+    include_paths: bool,
+
+    /// Split mapped files above this size across threads (0 disables) [default: 67108864]
+    #[arg(long, value_name = "BYTES")]
+    chunk_bytes: Option<usize>,
+
+    /// Where chunks are cut: exact (extend each cut to a token boundary) or split (fixed offsets; tokens across a cut count as two fragments)
+    #[arg(long, value_name = "POLICY")]
+    chunk_boundary_policy: Option<String>,
+
+    /// Merge strategy: per-worker (merge maps after scanning), sharded (workers add into shared shards) or arena (per-worker, merged in place into the largest map)
+    #[arg(long, value_name = "STRATEGY")]
+    merge_strategy: Option<String>,
+
+    /// Result order: count, count-asc, alpha, length or files (number of files containing the word)
+    #[arg(long, value_name = "ORDER")]
+    sort: Option<String>,
+
+    /// Order of words the sort ranks equal: alpha, first-seen (first occurrence, files in path order) or file-order (first file containing the word)
+    #[arg(long, value_name = "ORDER")]
+    tie_break: Option<String>,
+
+    /// Show each word's first and last file under its count
+    #[arg(short = 'v', long)]
+    verbose: bool,
+
+    /// File order for --verbose first/last files: traversal (path order) or mtime (oldest first)
+    #[arg(long, value_name = "ORDER")]
+    seen_order: Option<String>,
+
+    /// Fully deterministic processing for reproducible snapshots: files in path order, no auto-tuning, order-independent --max-unique and --max-unique-per-file
+    #[arg(long)]
+    stable: bool,
+
+    /// Count an extra token class in the same pass, as NAME=CHARS (repeatable)
+    #[arg(long = "class", value_name = "NAME=CHARS", value_parser = TokenClass::parse)]
+    classes: Vec<TokenClass>,
+
+    /// Split words into identifiers, numeric literals and all-caps macros
+    #[arg(long, conflicts_with_all = ["operators", "endpoints"])]
+    classify: bool,
+
+    /// Count only these words, matched directly in the file bytes (comma-separated or repeated)
+    #[arg(long, value_name = "WORD", value_delimiter = ',', conflicts_with_all = ["operators", "endpoints", "classify"])]
+    words: Vec<String>,
+
+    /// Count matches of a regex (repeatable) instead of words
+    #[arg(long = "count-regex", value_name = "RE")]
+    count_regex: Vec<String>,
+
+    /// Same as the grep subcommand, kept for existing scripts
+    #[arg(long, value_name = "RE", conflicts_with = "count_regex", hide = true)]
+    grep: Vec<String>,
+
+    /// With --count-regex, also report match counts per file
+    #[arg(long, requires = "count_regex")]
+    regex_per_file: bool,
+
+    /// Count C operators and punctuators instead of words
+    #[arg(long)]
+    operators: bool,
+
+    /// Report per-file sizes, line-length statistics and status (ok, skipped-binary or the error)
+    #[arg(long)]
+    file_stats: bool,
+
+    /// Skip files that look binary (a NUL byte in the first 8 KiB)
+    #[arg(long)]
+    skip_binary: bool,
+
+    /// Report the N most branch-dense files (if/for/while/case/&&/||)
+    #[arg(long, value_name = "N")]
+    complexity: Option<usize>,
+
+    /// Report identifier naming conventions (snake_case, camelCase, SCREAMING_CASE, mixed) per directory, with the N most frequent identifiers breaking the dominant one
+    #[arg(long, value_name = "N")]
+    casing: Option<usize>,
+
+    /// For the N most frequent identifiers, report the file holding the largest share of each one's occurrences
+    #[arg(long, value_name = "N")]
+    ownership: Option<usize>,
+
+    /// Report newline, word and byte counts per file and in total, like `wc`
+    #[arg(long)]
+    wc: bool,
+
+    /// Report SPDX license identifiers and files without a license marker
+    #[arg(long)]
+    licenses: bool,
+
+    /// Count URLs and email addresses instead of words
+    #[arg(long, conflicts_with = "operators")]
+    endpoints: bool,
+
+    /// Tokenize and/or filter words with a WASM plugin module
+    #[cfg(feature = "wasm")]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["operators", "endpoints"])]
+    wasm_plugin: Option<PathBuf>,
+
+    /// Tokenize and/or filter words with a native plugin library
+    #[cfg(feature = "native-plugins")]
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["operators", "endpoints"])]
+    native_plugin: Option<PathBuf>,
+
+    /// Merge words differing only in case, reported under their most common spelling
+    #[arg(long, conflicts_with_all = ["operators", "endpoints"])]
+    ignore_case: bool,
+
+    /// With --ignore-case, list each group's spellings and their counts
+    #[arg(long, requires = "ignore_case")]
+    case_variants: bool,
+
+    /// Write results to this file (atomically, via a temp file and rename) instead of stdout
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// Format of the --output file: text, bin (compact binary snapshot for --merge-with; the default for *.bin), or xlsx (results, summary and per-extension sheets; needs the xlsx feature)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        requires = "output",
+        conflicts_with_all = [
+            "files_from", "operators", "endpoints", "classify", "classes", "wc", "grep",
+            "count_regex", "words", "licenses", "complexity", "casing", "ownership", "lsp_ish", "watch", "cache",
+            "ignore_case", "case_variants"
+        ]
+    )]
+    format: Option<OutputFormat>,
+
+    /// Add the counts saved in this binary snapshot (from --output *.bin) to the results (repeatable)
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "operators", "endpoints", "classify", "classes", "wc", "grep", "count_regex",
+            "licenses", "complexity", "casing", "ownership", "lsp_ish", "watch"
+        ]
+    )]
+    merge_with: Vec<PathBuf>,
+
+    /// With --output, fsync the results file and its directory before returning
+    #[arg(long, requires = "output")]
+    fsync: bool,
+
+    /// Check the results' invariants (no empty or repeated words, sorted, counts adding up to the tokens counted) and that any --output file reads back unchanged
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "operators", "endpoints", "classify", "classes", "wc", "grep", "count_regex",
+            "words", "licenses", "complexity", "casing", "ownership", "lsp_ish", "watch", "cache"
+        ]
+    )]
+    verify: bool,
+
+    /// If another run holds the lock on the --output or --cache directory, wait for it instead of failing
+    #[arg(long, requires = "locked_outputs")]
+    lock_wait: bool,
+
+    /// Serve JSON-RPC queries (query/top/where) over stdin/stdout after counting
+    #[arg(long = "lsp-ish")]
+    lsp_ish: bool,
+
+    /// Keep running and print refreshed counts whenever files change, recounting only the changed files
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "files_from", "operators", "endpoints", "classify", "classes", "wc", "grep",
+            "count_regex", "words", "licenses", "complexity", "casing", "ownership", "lsp_ish", "output"
+        ]
+    )]
+    watch: bool,
+
+    /// Keep per-file counts in PATH between runs, so only files whose size or mtime changed are re-read
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = [
+            "files_from", "operators", "endpoints", "classify", "classes", "wc", "grep",
+            "count_regex", "words", "licenses", "complexity", "casing", "ownership", "lsp_ish", "watch"
+        ]
+    )]
+    cache: Option<PathBuf>,
+
+    /// Config file [default: ./fast-wc.toml if present, or $FAST_WC_CONFIG]
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Apply the [profile.NAME] table from the config file [env: FAST_WC_PROFILE]
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Print the effective configuration and where each value came from
+    #[arg(long)]
+    print_config: bool,
+
+    /// Drop words counted fewer than N times
+    #[arg(long, value_name = "N")]
+    min_count: Option<u64>,
+
+    /// Drop words counted more than N times
+    #[arg(long, value_name = "N")]
+    max_count: Option<u64>,
+
     /// Show only top N results
     #[arg(short = 't', long)]
     top: Option<usize>,
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+impl CountArgs {
+    // The directory to scan, for modes that walk a single directory. Clap
+    // requires a path unless --files-from was given, which the counting
+    // modes exclude but `bench` accepts along with the other count options
+    fn directory(&self) -> Result<&Path> {
+        match self.paths.as_slice() {
+            [dir] => Ok(dir),
+            [] => bail!(
+                "this mode needs a directory to scan; --files-from only applies to word counts"
+            ),
+            _ => anyhow::bail!(
+                "this mode takes a single directory; only word counts accept several paths"
+            ),
+        }
+    }
 
-    let config = Config {
-        num_threads: args.threads,
-        use_mmap: args.mmap,
-        silent: args.silent,
-        parallel_merge: args.parallel_merge,
-    };
+    // Layout of the --output file: --format (or the `format` setting), or a
+    // snapshot for a .bin path
+    fn output_format(&self, config: &Config) -> OutputFormat {
+        match (config.output_format, &self.output) {
+            (Some(format), _) => format,
+            (None, Some(path))
+                if path.extension() == Some(OsStr::new(snapshot_file::SNAPSHOT_EXTENSION)) =>
+            {
+                OutputFormat::Bin
+            }
+            _ => OutputFormat::Text,
+        }
+    }
 
-    if !args.silent {
+    // Whether the input is text on stdin (`-` as the only path)
+    fn reads_stdin(&self) -> bool {
+        self.paths == [Path::new("-")]
+    }
+
+    // Whether the input is exactly one directory, as `write_directory` needs
+    fn scans_one_directory(&self) -> bool {
+        matches!(self.paths.as_slice(), [dir] if dir.is_dir())
+    }
+}
+
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Count words (the default when no subcommand is given)
+    Count(Box<CountArgs>),
+    /// Compare two results files written with --output, listing the words whose counts changed
+    Diff(DiffArgs),
+    /// Sum several results files written with --output into one
+    Merge(MergeArgs),
+    /// Summarize a results file: totals and how occurrences split across frequency classes
+    Stats(StatsArgs),
+    /// Time repeated counts of a directory phase by phase, with the same options as count
+    Bench(Box<BenchArgs>),
+    /// List the number of files and total bytes per extension in a tree, without reading any file
+    Filetypes(FiletypesArgs),
+    /// Print lines matching a regex as path:line:text, like grep, then the match counts per pattern
+    Grep(GrepArgs),
+    /// Write a reproducible synthetic corpus, e.g. to replay a benchmark scenario
+    GenCorpus(GenCorpusArgs),
+    /// Merge word/count pairs (TSV word<TAB>count or NDJSON {"word","count"} lines) read from stdin
+    Fold(FoldArgs),
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// Results file of the earlier run
+    old: PathBuf,
+
+    /// Results file of the later run
+    new: PathBuf,
+
+    /// Show only the N largest changes
+    #[arg(short = 't', long)]
+    top: Option<usize>,
+}
+
+// Print the words whose counts differ between two results files, largest
+// change first
+fn diff(args: &DiffArgs) -> Result<()> {
+    let old = CountReport::load(&args.old)?;
+    let new = CountReport::load(&args.new)?;
+    let changes = old.changes(&new);
+    for change in changes.iter().take(args.top.unwrap_or(usize::MAX)) {
+        println!(
+            "{:>32} | {:>8} | {:>8} | {:>+9}",
+            change.word,
+            change.old,
+            change.new,
+            change.delta()
+        );
+    }
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct MergeArgs {
+    /// Results files to sum
+    #[arg(required = true, value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Write the merged results to this file instead of stdout
+    #[arg(short = 'o', long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    /// With --output, fsync the results file and its directory before returning
+    #[arg(long, requires = "output")]
+    fsync: bool,
+
+    /// Show only top N results
+    #[arg(short = 't', long)]
+    top: Option<usize>,
+}
+
+// Sum results files and print or write the totals
+fn merge(args: &MergeArgs) -> Result<()> {
+    let mut merged = CountReport::default();
+    for path in &args.files {
+        merged = merged.add(&CountReport::load(path)?);
+    }
+    let results = merged.top_n(args.top.unwrap_or(usize::MAX));
+
+    let counter = FastWordCounter::builder().show_progress(false).build()?;
+    match &args.output {
+        Some(path) => write_output(&counter, results, path, args.fsync),
+        None => {
+            counter.print_results(results);
+            Ok(())
+        }
+    }
+}
+
+#[derive(clap::Args)]
+struct StatsArgs {
+    /// Results file written with --output
+    file: PathBuf,
+}
+
+// Print a results file's totals and its frequency classes
+fn stats(args: &StatsArgs) -> Result<()> {
+    let report = CountReport::load(&args.file)?;
+    let occurrences = report
+        .iter()
+        .fold(0u64, |total, (_, count)| total.saturating_add(count));
+    println!("{} unique words, {} occurrences", report.len(), occurrences);
+    for bucket in report.bucketize(&DEFAULT_BUCKETS) {
         println!(
-            "fast-wc-rust with {} threads, nmap: {}, parallel merge: {}",
-            args.threads, args.mmap, args.parallel_merge
-        )
+            "{:>12} (>= {:>4}): {:>8} words, {:>10} occurrences ({:>5.1}%)  e.g. {}",
+            bucket.name,
+            bucket.min_count,
+            bucket.words,
+            bucket.occurrences,
+            bucket.share * 100.0,
+            bucket.examples.join(", ")
+        );
     }
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct FiletypesArgs {
+    /// Directory to scan
+    #[arg(value_name = "DIRECTORY")]
+    dir: PathBuf,
+
+    /// Only include files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "include", value_name = "GLOB")]
+    include_globs: Vec<String>,
+
+    /// Skip files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude_globs: Vec<String>,
+
+    /// Follow symlinked directories and files (loops are skipped)
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Descend at most N directory levels (1 = only files directly in DIRECTORY)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Show only the N largest extensions
+    #[arg(short = 't', long)]
+    top: Option<usize>,
+}
 
+// Print files and bytes per extension, largest first, then the totals
+fn filetypes(args: &FiletypesArgs) -> Result<()> {
+    let counter = FastWordCounter::builder()
+        .include_globs(&args.include_globs)
+        .exclude_globs(&args.exclude_globs)
+        .follow_symlinks(args.follow_symlinks)
+        .max_depth(args.max_depth)
+        .show_progress(false)
+        .build()?;
+    let logger = *counter.logger();
+    let types = counter
+        .file_types(&args.dir)
+        .map_err(|e| exit_if_no_files(&logger, e))?;
+
+    for file_type in types.iter().take(args.top.unwrap_or(usize::MAX)) {
+        let extension = match file_type.extension.as_str() {
+            "" => "(none)".to_string(),
+            ext => format!(".{}", ext),
+        };
+        println!(
+            "{:>16} | {:>8} files | {:>14} bytes",
+            extension, file_type.files, file_type.bytes
+        );
+    }
+    let files: u64 = types.iter().map(|t| t.files).sum();
+    let bytes: u64 = types.iter().map(|t| t.bytes).sum();
+    println!("{:>16} | {:>8} files | {:>14} bytes", "total", files, bytes);
+
+    let errors = counter.errors();
+    if !errors.is_empty() {
+        logger.warn(
+            "file_errors",
+            &format!("warning: {} files could not be inspected", errors.len()),
+            &[("files", json!(errors.len()))],
+        );
+    }
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct GrepArgs {
+    /// Regex to search for
+    #[arg(value_name = "PATTERN")]
+    pattern: String,
+
+    /// Directory to search
+    #[arg(value_name = "DIRECTORY")]
+    dir: PathBuf,
+
+    /// Another regex to search for; lines matching any pattern are printed (repeatable)
+    #[arg(short = 'e', long = "regexp", value_name = "RE")]
+    patterns: Vec<String>,
+
+    /// File extension to search, e.g. rs (repeatable) [default: c, h]
+    #[arg(long = "ext", value_name = "EXT")]
+    extensions: Vec<String>,
+
+    /// Only search files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "include", value_name = "GLOB")]
+    include_globs: Vec<String>,
+
+    /// Skip files matching this glob, relative to DIRECTORY (repeatable)
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude_globs: Vec<String>,
+
+    /// Follow symlinked directories and files (loops are skipped)
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Descend at most N directory levels (1 = only files directly in DIRECTORY)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Read files instead of memory-mapping them
+    #[arg(long)]
+    no_mmap: bool,
+}
+
+// Search the tree for the patterns, printing matching lines and the counts
+fn grep(args: &GrepArgs) -> Result<()> {
+    let mut builder = FastWordCounter::builder()
+        .include_globs(&args.include_globs)
+        .exclude_globs(&args.exclude_globs)
+        .follow_symlinks(args.follow_symlinks)
+        .max_depth(args.max_depth)
+        .show_progress(false);
+    if !args.extensions.is_empty() {
+        builder = builder.extensions(&args.extensions);
+    }
+    if args.no_mmap {
+        builder = builder.use_mmap(false);
+    }
+    let counter = builder.build()?;
+
+    let patterns: Vec<String> = std::iter::once(args.pattern.clone())
+        .chain(args.patterns.iter().cloned())
+        .collect();
+    print_grep(&counter, &args.dir, &patterns)
+}
+
+// Print every line of `dir` matching any of `patterns`, then the match count
+// per pattern and the number of files with a match
+fn print_grep(counter: &FastWordCounter, dir: &Path, patterns: &[String]) -> Result<()> {
+    let logger = *counter.logger();
+    let report = counter
+        .grep_directory(dir, patterns, std::io::stdout())
+        .map_err(|e| exit_if_no_files(&logger, e))?;
+
+    println!();
+    println!("Matches ({} files):", report.files.len());
+    counter.print_results(&report.totals);
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct BenchArgs {
+    /// Number of timed runs
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    runs: usize,
+
+    #[command(flatten)]
+    count: CountArgs,
+}
+
+// Profile `runs` counts of the directory with the count options given,
+// printing each run's phases and the fastest run's throughput
+fn bench(args: &BenchArgs) -> Result<()> {
+    let dir = args.count.directory()?;
+    let (mut config, _) = resolve_config(&args.count)?;
+    config.show_progress = false;
+    config.print_results = false;
     let counter = FastWordCounter::new(config);
+
+    let mut best: Option<ProfileReport> = None;
+    for run in 1..=args.runs {
+        let report = counter.profile_run(dir)?;
+        println!("run {}/{}: {}\n", run, args.runs, report);
+        if best
+            .as_ref()
+            .is_none_or(|best| report.total() < best.total())
+        {
+            best = Some(report);
+        }
+    }
+    if let Some(best) = best {
+        println!(
+            "best of {}: {:.2?}, {:.1} MiB/s",
+            args.runs,
+            best.total(),
+            best.bytes_per_sec() / (1024.0 * 1024.0)
+        );
+    }
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct FoldArgs {
+    /// Output format: tsv or ndjson
+    #[arg(long, value_name = "FORMAT", default_value = "tsv")]
+    format: PairFormat,
+
+    /// Show only top N results
+    #[arg(short = 't', long)]
+    top: Option<usize>,
+}
+
+// Merge the pairs on stdin and write them out again, most frequent first
+fn fold(args: &FoldArgs) -> Result<()> {
+    let counter = FastWordCounter::builder()
+        .parallel_merge(true)
+        .top(args.top)
+        .show_progress(false)
+        .print_results(false)
+        .build()?;
+    let results = counter.fold_reader(std::io::stdin().lock())?;
+    let mut out = std::io::BufWriter::new(std::io::stdout().lock());
+    for (word, count) in &results {
+        fold::write_pair(&mut out, args.format, word, *count)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[derive(clap::Args)]
+struct GenCorpusArgs {
+    /// Directory to write the corpus into (created if missing)
+    dir: PathBuf,
+
+    /// Number of files
+    #[arg(long, default_value_t = 100)]
+    files: usize,
+
+    /// File size in bytes (the minimum with --size-max or --pareto)
+    #[arg(long, value_name = "BYTES", default_value_t = 10240)]
+    size: usize,
+
+    /// Draw file sizes uniformly between --size and this
+    #[arg(long, value_name = "BYTES", conflicts_with = "pareto")]
+    size_max: Option<usize>,
+
+    /// Draw heavy-tailed file sizes from a Pareto distribution with this shape
+    #[arg(long, value_name = "ALPHA")]
+    pareto: Option<f64>,
+
+    /// Number of distinct words
+    #[arg(long, default_value_t = 10000)]
+    vocab: usize,
+
+    /// Zipf exponent of word frequencies (0 = uniform)
+    #[arg(long, value_name = "THETA", default_value_t = 1.0)]
+    zipf: f64,
+
+    /// Words per line
+    #[arg(long, default_value_t = 10)]
+    words_per_line: usize,
+
+    /// Spread files over subdirectories of at most N files
+    #[arg(long, value_name = "N")]
+    files_per_dir: Option<usize>,
+
+    /// File extension
+    #[arg(long, default_value = "c")]
+    ext: String,
+
+    /// Random seed; the same arguments and seed always produce the same files
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+// Generate a corpus for the gen-corpus subcommand
+fn gen_corpus(args: &GenCorpusArgs) -> Result<()> {
+    use fast_wc_rust::corpus::{CorpusSpec, SizeDistribution};
+
+    let file_size = match (args.size_max, args.pareto) {
+        (Some(max), _) => SizeDistribution::Uniform {
+            min: args.size,
+            max,
+        },
+        (None, Some(alpha)) => SizeDistribution::Pareto {
+            min: args.size,
+            alpha,
+        },
+        (None, None) => SizeDistribution::Fixed(args.size),
+    };
+    let spec = CorpusSpec {
+        files: args.files,
+        file_size,
+        vocab: args.vocab,
+        zipf: args.zipf,
+        words_per_line: args.words_per_line,
+        files_per_dir: args.files_per_dir,
+        extension: args.ext.clone(),
+        seed: args.seed,
+    };
+    let paths = spec
+        .generate(&args.dir)
+        .with_context(|| format!("Failed to write corpus to {}", args.dir.display()))?;
+
+    let bytes: u64 = paths
+        .iter()
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|meta| meta.len())
+        .sum();
+    println!(
+        "Wrote {} files ({} bytes) to {}",
+        paths.len(),
+        bytes,
+        args.dir.display()
+    );
+    Ok(())
+}
+
+// Resolve configuration from defaults, config file, FAST_WC_* environment
+// variables and command-line flags, in increasing order of precedence
+fn resolve_config(args: &CountArgs) -> Result<(Config, Settings)> {
+    let mut settings = Settings::new();
+
+    let config_file = args
+        .config
+        .clone()
+        .or_else(|| std::env::var_os("FAST_WC_CONFIG").map(PathBuf::from))
+        .or_else(|| {
+            let default = PathBuf::from(settings::DEFAULT_CONFIG_FILE);
+            default.exists().then_some(default)
+        });
+    let profile = args
+        .profile
+        .clone()
+        .or_else(|| std::env::var("FAST_WC_PROFILE").ok());
+    match (&config_file, &profile) {
+        (Some(path), profile) => settings.load_file(path, profile.as_deref())?,
+        (None, Some(name)) => {
+            anyhow::bail!("profile '{}' requested but no config file found", name)
+        }
+        (None, None) => {}
+    }
+
+    settings.load_env(std::env::vars())?;
+
+    for (key, value) in cli_overrides(args) {
+        if let Some(value) = value {
+            settings.set(key, value, Source::Cli)?;
+        }
+    }
+
+    // The CLI has always defaulted to sequential merging
+    let mut config = Config::default();
+    config.parallel_merge = false;
+    settings.apply(&mut config)?;
+
+    // Flags that select reports rather than tune counting
+    config.collect_file_stats = args.by_file_size.is_some() || args.file_stats;
+    config.line_stats = args.file_stats;
+    if args.lsp_ish {
+        // `where` requests need sampled locations
+        config.sample_occurrences = config.sample_occurrences.or(Some(DEFAULT_RPC_SAMPLES));
+    }
+    if args.verbose {
+        config.seen_order = config.seen_order.or(Some(SeenOrder::Traversal));
+    }
+
+    // Reject values the builder would, e.g. `-n 0`, instead of counting nothing
+    let config = CounterBuilder::from(config).config()?;
+    Ok((config, settings))
+}
+
+// The settings given as command-line flags, one entry per settings key
+fn cli_overrides(args: &CountArgs) -> [(&'static str, Option<String>); settings::KEYS.len()] {
+    [
+        ("threads", args.threads.map(|n| n.to_string())),
+        (
+            "mmap",
+            if args.mmap {
+                Some("true".into())
+            } else {
+                args.no_mmap.then(|| "false".into())
+            },
+        ),
+        ("parallel_merge", args.parallel_merge.then(|| "true".into())),
+        // stdout carries the RPC stream in --lsp-ish mode
+        (
+            "progress",
+            (args.silent || args.quiet || args.lsp_ish).then(|| "false".into()),
+        ),
+        ("print_results", args.silent.then(|| "false".into())),
+        ("shares", args.shares.then(|| "true".into())),
+        (
+            "log_format",
+            args.log_format
+                .map(|f| format!("{:?}", f).to_ascii_lowercase()),
+        ),
+        ("max_token_len", args.max_token_len.map(|n| n.to_string())),
+        (
+            "max_unique_per_file",
+            args.max_unique_per_file.map(|n| n.to_string()),
+        ),
+        ("max_unique", args.max_unique.map(|n| n.to_string())),
+        (
+            "sample_occurrences",
+            args.sample_occurrences.map(|n| n.to_string()),
+        ),
+        ("token_chars", args.token_chars.clone()),
+        (
+            "token_mode",
+            args.token_mode
+                .map(|m| format!("{:?}", m).to_ascii_lowercase()),
+        ),
+        (
+            "flush_every_files",
+            args.flush_every_files.map(|n| n.to_string()),
+        ),
+        (
+            "flush_every_entries",
+            args.flush_every_entries.map(|n| n.to_string()),
+        ),
+        ("auto_tune", args.auto_tune.then(|| "true".into())),
+        (
+            "spill_threshold",
+            args.spill_threshold.map(|n| n.to_string()),
+        ),
+        ("code_aware", args.code_aware.then(|| "true".into())),
+        ("prefault", args.prefault.then(|| "true".into())),
+        (
+            "exclude_keywords",
+            args.exclude_keywords.then(|| "true".into()),
+        ),
+        (
+            "stopwords",
+            args.stopwords.as_ref().map(|p| p.display().to_string()),
+        ),
+        (
+            "io_mode",
+            args.io_mode
+                .map(|m| format!("{:?}", m).to_ascii_lowercase()),
+        ),
+        (
+            "small_file_bytes",
+            args.small_file_bytes.map(|n| n.to_string()),
+        ),
+        ("content_hash", args.content_hash.then(|| "true".into())),
+        ("chunk_bytes", args.chunk_bytes.map(|n| n.to_string())),
+        ("decompress", args.decompress.map(|b| b.to_string())),
+        ("archives", args.archives.then(|| "true".into())),
+        (
+            "path_words",
+            if args.paths_only {
+                Some("only".into())
+            } else {
+                args.include_paths.then(|| "include".into())
+            },
+        ),
+        ("skip_binary", args.skip_binary.then(|| "true".into())),
+        ("chunk_boundary_policy", args.chunk_boundary_policy.clone()),
+        ("merge_strategy", args.merge_strategy.clone()),
+        ("sort", args.sort.clone()),
+        ("tie_break", args.tie_break.clone()),
+        ("stable", args.stable.then(|| "true".into())),
+        ("seen_order", args.seen_order.clone()),
+        ("min_count", args.min_count.map(|n| n.to_string())),
+        ("max_count", args.max_count.map(|n| n.to_string())),
+        ("top", args.top.map(|n| n.to_string())),
+        ("error_policy", args.error_policy.clone()),
+        (
+            "follow_symlinks",
+            args.follow_symlinks.then(|| "true".into()),
+        ),
+        ("max_depth", args.max_depth.map(|n| n.to_string())),
+        (
+            "path_encoding",
+            args.path_encoding
+                .map(|e| format!("{:?}", e).to_ascii_lowercase()),
+        ),
+        (
+            "aliases",
+            args.aliases.as_ref().map(|p| p.display().to_string()),
+        ),
+        (
+            "split_identifiers",
+            args.split_identifiers.then(|| "true".into()),
+        ),
+        ("fail_on_error", args.fail_on_error.then(|| "true".into())),
+        (
+            "format",
+            args.format.map(|f| format!("{:?}", f).to_ascii_lowercase()),
+        ),
+        (
+            "include_globs",
+            (!args.include_globs.is_empty()).then(|| args.include_globs.join(",")),
+        ),
+        (
+            "exclude_globs",
+            (!args.exclude_globs.is_empty()).then(|| args.exclude_globs.join(",")),
+        ),
+        (
+            "extensions",
+            (!args.extensions.is_empty()).then(|| args.extensions.join(",")),
+        ),
+    ]
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+
+    match &cli.command {
+        None => count(&cli.count),
+        Some(Command::Count(args)) => count(args),
+        Some(Command::Diff(args)) => diff(args),
+        Some(Command::Merge(args)) => merge(args),
+        Some(Command::Stats(args)) => stats(args),
+        Some(Command::Bench(args)) => bench(args),
+        Some(Command::Filetypes(args)) => filetypes(args),
+        Some(Command::Grep(args)) => grep(args),
+        Some(Command::GenCorpus(args)) => gen_corpus(args),
+        Some(Command::Fold(args)) => fold(args),
+    }
+}
+
+// Resolve the configuration, run hooks around the count, and count
+fn count(args: &CountArgs) -> Result<()> {
+    let (config, settings) = resolve_config(args)?;
+
+    if args.print_config {
+        print!("{}", settings.describe(&config));
+        return Ok(());
+    }
+
+    let hooks = settings.hooks();
+    let hook_logger = Logger::new(config.log_format);
+    let hook_logger = config.show_progress.then_some(&hook_logger);
+    hooks.run(Stage::Pre, hook_logger)?;
+    run(args, config)?;
+    hooks.run(Stage::Post, hook_logger)
+}
+
+// Count and print results for the selected mode
+fn run(args: &CountArgs, mut config: Config) -> Result<()> {
+    // Serialize runs that write into the same output or cache directory
+    let _locks = lock_output_dirs(args)?;
+
+    // Case folding, snapshot merging and the RPC server need every word;
+    // --top is applied after folding or merging, and ignored by the server
+    if args.ignore_case || !args.merge_with.is_empty() || args.lsp_ish {
+        config.top = None;
+    }
+
+    let show_progress = config.show_progress;
+    let counter = FastWordCounter::new(config.clone());
+    let logger = *counter.logger();
+
+    if show_progress {
+        logger.info(
+            "config",
+            &format!(
+                "fast-wc-rust with {} threads, nmap: {}, parallel merge: {}",
+                config.num_threads, config.use_mmap, config.parallel_merge
+            ),
+            &[
+                ("threads", json!(config.num_threads)),
+                ("mmap", json!(config.use_mmap)),
+                ("parallel_merge", json!(config.parallel_merge)),
+            ],
+        );
+    }
     let start = Instant::now();
 
-    let results = counter.count_directory(&args.directory)?;
+    if let Some(n) = args.complexity {
+        let files = counter
+            .branch_density(args.directory()?, n)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        for file in files {
+            println!(
+                "{:>8.1} per 100 lines | {:>8} branches | {:>8} lines | {}",
+                file.density(),
+                file.branches,
+                file.lines,
+                counter.display_path(&file.path)
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(n) = args.casing {
+        let report = counter
+            .casing_audit(args.directory()?, n)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        let header: Vec<String> = Casing::ALL
+            .iter()
+            .map(|casing| format!("{:>14}", casing.name()))
+            .collect();
+        println!("{} | directory", header.join(" | "));
+        let rows = report
+            .directories
+            .iter()
+            .map(|(dir, stats)| (counter.display_path(dir), stats))
+            .chain(std::iter::once(("total".into(), &report.total)));
+        for (name, stats) in rows {
+            let shares: Vec<String> = Casing::ALL
+                .iter()
+                .map(|&casing| format!("{:>13.1}%", stats.percent(casing)))
+                .collect();
+            println!("{} | {}", shares.join(" | "), name);
+        }
+        println!();
+        println!(
+            "Identifiers breaking the dominant {} convention:",
+            report.total.dominant().name()
+        );
+        for offender in &report.offenders {
+            println!(
+                "{:>32} | {:>8} | {}",
+                offender.word,
+                offender.count,
+                offender.casing.name()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(n) = args.ownership {
+        let owned = counter
+            .identifier_ownership(args.directory()?, n)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        for entry in owned {
+            println!(
+                "{:>32} | {:>8} | {:>5.1}% in {}",
+                entry.word,
+                entry.total,
+                entry.share(),
+                counter.display_path(&entry.owner)
+            );
+        }
+        return Ok(());
+    }
+
+    if !args.grep.is_empty() {
+        return print_grep(&counter, args.directory()?, &args.grep);
+    }
+
+    if !args.count_regex.is_empty() {
+        let report = counter
+            .count_directory_regex(args.directory()?, &args.count_regex, args.regex_per_file)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        counter.print_results(&report.totals);
+        if args.regex_per_file {
+            println!();
+            println!("Matches per file:");
+            for (path, counts) in &report.files {
+                for ((pattern, _), count) in report.totals.iter().zip(counts) {
+                    if *count > 0 {
+                        println!(
+                            "{:>8} | {} | {}",
+                            count,
+                            pattern,
+                            counter.display_path(path)
+                        );
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    if args.wc {
+        let report = counter
+            .count_directory_wc(args.directory()?)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        for (path, counts) in &report.files {
+            print_wc_line(counts, &counter.display_path(path));
+        }
+        print_wc_line(&report.total, "total");
+        return Ok(());
+    }
+
+    if args.licenses {
+        let report = counter
+            .scan_licenses(args.directory()?)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        println!("SPDX identifiers (files):");
+        counter.print_results(&report.spdx);
+        println!();
+        println!("License phrases (occurrences):");
+        counter.print_results(&report.phrases);
+        println!();
+        println!(
+            "Files without a license marker: {}",
+            report.unlicensed.len()
+        );
+        for path in &report.unlicensed {
+            println!("  {}", counter.display_path(path));
+        }
+        return Ok(());
+    }
+
+    if !args.classes.is_empty() {
+        let results = counter
+            .count_directory_multi(args.directory()?, &args.classes)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        for class in &args.classes {
+            if !config.print_results {
+                break;
+            }
+            println!("== {} ==", class.name);
+            counter.print_results(&results[&class.name]);
+            println!();
+        }
+        return Ok(());
+    }
+
+    if args.classify {
+        let classified = counter
+            .count_directory_classified(args.directory()?)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+
+        for category in TokenCategory::ALL {
+            let words = classified.get(category);
+            println!(
+                "== {}: {} distinct, {} total ==",
+                category.name(),
+                words.len(),
+                classified.total(category)
+            );
+            if config.print_results {
+                counter.print_results(take_top(&classified.sorted(category), args.top));
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    if args.lsp_ish {
+        let results = counter
+            .count_paths(&args.paths)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+        let store = SnapshotStore::from_counts(results.into_iter().collect());
+        let stdin = std::io::stdin();
+        return fast_wc_rust::rpc::serve(&counter, &store, stdin.lock(), std::io::stdout());
+    }
+
+    #[cfg(feature = "xlsx")]
+    if let (Some(OutputFormat::Xlsx), Some(path)) = (config.output_format, &args.output) {
+        let counts = counter
+            .count_directory_by_extension(args.directory()?)
+            .map_err(|e| exit_if_no_files(&logger, e))?;
+        fast_wc_rust::xlsx::write_workbook(path, &counter, &counts, start.elapsed(), args.fsync)?;
+        print_extra_reports(&counter, args);
+        return Ok(());
+    }
+
+    if args.watch {
+        return fast_wc_rust::watch::watch(config.clone(), args.directory()?, |counts| {
+            if show_progress {
+                logger.info(
+                    "watch",
+                    &format!("{} files counted, watching for changes", counts.files()),
+                    &[("files", json!(counts.files()))],
+                );
+            }
+            counts.counter().print_results(&counts.results());
+            if config.print_results {
+                println!();
+            }
+        });
+    }
+
+    // Full dumps of plain word counts can be sorted on disk
+    if config.spill_threshold.is_some()
+        && config.sort == SortOrder::CountDesc
+        && (config.print_results || args.output.is_some())
+        && config.sample_occurrences.is_none()
+        && config.top.is_none()
+        && !args.ignore_case
+        && !args.operators
+        && !args.endpoints
+        && args.words.is_empty()
+        && !args.verify
+        && args.scans_one_directory()
+        && args.cache.is_none()
+        && config.path_words == PathWords::Off
+        && args.merge_with.is_empty()
+        && args.output_format(&config) == OutputFormat::Text
+        && !uses_plugin(args)
+    {
+        let unique = match &args.output {
+            Some(path) => {
+                let mut file = create_output(path, args.fsync)?;
+                let unique = counter
+                    .write_directory(args.directory()?, &mut file)
+                    .map_err(|e| exit_if_no_files(&logger, e))?;
+                file.commit()
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                unique
+            }
+            None => counter
+                .write_directory(args.directory()?, std::io::stdout().lock())
+                .map_err(|e| exit_if_no_files(&logger, e))?,
+        };
+        if show_progress {
+            let elapsed = start.elapsed();
+            logger.info(
+                "completed",
+                &format!("Processing and output completed in {:.2?}", elapsed),
+                &[("elapsed_ms", json!(elapsed.as_secs_f64() * 1000.0))],
+            );
+            logger.info(
+                "unique_words",
+                &format!("Found {} unique words", unique),
+                &[("unique_words", json!(unique))],
+            );
+        }
+        print_extra_reports(&counter, args);
+        return Ok(());
+    }
+
+    let mut results = if args.operators {
+        counter.count_directory_operators(args.directory()?)
+    } else if args.endpoints {
+        counter.count_directory_endpoints(args.directory()?)
+    } else if !args.words.is_empty() {
+        counter.count_directory_literals(args.directory()?, &args.words)
+    } else if show_progress {
+        with_live_progress(&counter, || count_words(&counter, args))
+    } else {
+        count_words(&counter, args)
+    }
+    .map_err(|e| exit_if_no_files(&logger, e))?;
+    if args.verify && !uses_plugin(args) {
+        counter.verify(&results)?;
+    }
+
+    if !args.merge_with.is_empty() {
+        results = counter.merge_snapshots(results, &args.merge_with)?;
+        if !args.ignore_case {
+            results.truncate(args.top.unwrap_or(usize::MAX));
+        }
+    }
+
+    let mut case_groups = Vec::new();
+    let mut unique = counter.unique_words() as usize;
+    if args.ignore_case {
+        case_groups = CountReport::from(results).fold_case();
+        unique = case_groups.len();
+        case_groups.truncate(args.top.unwrap_or(usize::MAX));
+        results = case_groups
+            .iter()
+            .map(|group| (group.dominant.clone(), group.total))
+            .collect();
+    }
 
     let elapsed = start.elapsed();
 
-    if !args.silent {
-        println!("Processing completed in {:.2?}", elapsed);
-        println!("Found {} unique words", results.len());
+    if show_progress {
+        logger.info(
+            "completed",
+            &format!("Processing completed in {:.2?}", elapsed),
+            &[("elapsed_ms", json!(elapsed.as_secs_f64() * 1000.0))],
+        );
+        logger.info(
+            "unique_words",
+            &format!("Found {} unique words", unique),
+            &[("unique_words", json!(unique))],
+        );
+        if logger.format() == LogFormat::Text {
+            println!();
+        }
+    }
+
+    match &args.output {
+        Some(path) if args.output_format(&config) == OutputFormat::Bin => {
+            snapshot_file::save(path, &results, args.fsync)?
+        }
+        Some(path) => write_output(&counter, &results, path, args.fsync)?,
+        None if args.case_variants => print_case_groups(&case_groups),
+        None => counter.print_results(&results),
+    }
+    if let (true, Some(path)) = (args.verify, &args.output) {
+        verify_output(path, &results, args.output_format(&config))?;
+    }
+    print_extra_reports(&counter, args);
+
+    Ok(())
+}
+
+// Directory an output file is written into
+fn output_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+// Lock the directories of the --output and --cache files; a directory
+// holding both is locked once, as a second lock on it would never be granted
+fn lock_output_dirs(args: &CountArgs) -> Result<Vec<DirLock>> {
+    let mut locked: Vec<PathBuf> = Vec::new();
+    let mut locks = Vec::new();
+    for path in args.output.iter().chain(&args.cache) {
+        let dir = output_dir(path);
+        let key = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        if !locked.contains(&key) {
+            locks.push(DirLock::acquire(dir, args.lock_wait)?);
+            locked.push(key);
+        }
+    }
+    Ok(locks)
+}
+
+// Open an output file and write its format header
+fn create_output(path: &Path, fsync: bool) -> Result<AtomicFile> {
+    let mut file = AtomicFile::create(path, fsync)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    writeln!(
+        file,
+        "{}",
+        artifact::header(artifact::RESULTS, artifact::RESULTS_VERSION)
+    )?;
+    Ok(file)
+}
+
+// Write results to `path` through an `AtomicFile`
+fn write_output(
+    counter: &FastWordCounter,
+    results: &[(String, u64)],
+    path: &Path,
+    fsync: bool,
+) -> Result<()> {
+    let mut file = create_output(path, fsync)?;
+    counter.write_results(results, &mut file)?;
+    file.commit()
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+// Read an --output file back and check it holds exactly `results`
+fn verify_output(path: &Path, results: &[(String, u64)], format: OutputFormat) -> Result<()> {
+    let written = match format {
+        OutputFormat::Bin => snapshot_file::load(path)?,
+        _ => CountReport::load(path)?.into_vec(),
+    };
+    if written != results {
+        bail!(
+            "Result verification failed: {} does not read back as the results written",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+// Print case-folded groups with every spelling under its dominant one
+fn print_case_groups(groups: &[CaseGroup]) {
+    for group in groups {
+        println!("{:>32} | {:>8}", group.dominant, group.total);
+        for (variant, count) in &group.variants {
+            println!("{:>32}   {} {}", "", variant, count);
+        }
+    }
+}
+
+// One `wc`-style line: newlines, words, bytes, name
+fn print_wc_line(counts: &WcCounts, name: &str) {
+    println!(
+        "{:>8} {:>8} {:>10} {}",
+        counts.lines, counts.words, counts.bytes, name
+    );
+}
+
+// Per-file reports printed after the word counts
+fn print_extra_reports(counter: &FastWordCounter, args: &CountArgs) {
+    if let Some(n) = args.by_file_size {
         println!();
+        println!("Largest contributors:");
+        counter.print_largest_contributors(n);
+    }
+
+    if args.file_stats {
+        println!();
+        println!("File statistics:");
+        counter.print_file_stats();
+    }
+}
+
+// Whether a tokenizer/filter plugin replaces the built-in word count
+fn uses_plugin(args: &CountArgs) -> bool {
+    #[cfg(feature = "wasm")]
+    if args.wasm_plugin.is_some() {
+        return true;
+    }
+    #[cfg(feature = "native-plugins")]
+    if args.native_plugin.is_some() {
+        return true;
+    }
+    let _ = args;
+    false
+}
+
+// Count words, through a plugin if one was given
+fn count_words(counter: &FastWordCounter, args: &CountArgs) -> Result<Vec<(String, u64)>> {
+    if let Some(path) = &args.cache {
+        let session = counter.session()?;
+        session.load_cache(path)?;
+        let results = session.count(args.directory()?)?;
+        session.save_cache(path)?;
+        return Ok(results);
     }
+    if let Some(list) = &args.files_from {
+        return counter.count_file_list(read_file_list(list)?);
+    }
+    if args.reads_stdin() {
+        return counter.count_reader(std::io::stdin().lock());
+    }
+    #[cfg(feature = "wasm")]
+    if let Some(path) = &args.wasm_plugin {
+        let plugin = fast_wc_rust::WasmPlugin::load(path)?;
+        return counter.count_directory_wasm(args.directory()?, &plugin);
+    }
+    #[cfg(feature = "native-plugins")]
+    if let Some(path) = &args.native_plugin {
+        let plugin = fast_wc_rust::NativePlugin::load(path)?;
+        return counter.count_directory_native(args.directory()?, &plugin);
+    }
+    counter.count_paths(&args.paths)
+}
 
-    let display_results = if let Some(top) = args.top {
-        &results[..results.len().min(top)]
+// How often a running count logs its progress
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Run `count` on a worker thread, logging files, tokens and the estimated
+// number of unique words every `PROGRESS_INTERVAL` until it finishes
+fn with_live_progress<T: Send>(counter: &FastWordCounter, count: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        let worker = s.spawn(move || {
+            let _ = tx.send(count());
+        });
+        loop {
+            match rx.recv_timeout(PROGRESS_INTERVAL) {
+                Ok(result) => return result,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let progress = counter.progress();
+                    counter.logger().info(
+                        "progress",
+                        &format!(
+                            "{}/{} files, {} tokens, ~{} unique words so far",
+                            progress.files_processed,
+                            progress.files_total,
+                            progress.tokens_processed,
+                            progress.unique_words_estimate
+                        ),
+                        &[
+                            ("files_processed", json!(progress.files_processed)),
+                            ("files_total", json!(progress.files_total)),
+                            ("tokens_processed", json!(progress.tokens_processed)),
+                            (
+                                "unique_words_estimate",
+                                json!(progress.unique_words_estimate),
+                            ),
+                        ],
+                    );
+                }
+                // The worker panicked before sending; re-raise it here
+                Err(mpsc::RecvTimeoutError::Disconnected) => match worker.join() {
+                    Err(panic) => std::panic::resume_unwind(panic),
+                    Ok(()) => unreachable!("worker exited without a result"),
+                },
+            }
+        }
+    })
+}
+
+// Read a newline-delimited list of paths from a file, or stdin for `-`
+fn read_file_list(path: &Path) -> Result<Vec<PathBuf>> {
+    let bytes = if path == Path::new("-") {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+        bytes
     } else {
-        &results
+        std::fs::read(path)
+            .with_context(|| format!("Failed to read file list {}", path.display()))?
     };
+    Ok(bytes
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(path_from_bytes)
+        .collect())
+}
 
-    counter.print_results(display_results);
+// File names are arbitrary bytes on Unix, so list entries are taken as-is;
+// elsewhere they must be UTF-8
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
 
-    Ok(())
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// Limit results to the first `top` entries, if requested
+fn take_top(results: &[(String, u64)], top: Option<usize>) -> &[(String, u64)] {
+    match top {
+        Some(top) => &results[..results.len().min(top)],
+        None => results,
+    }
+}
+
+// Warn and exit with status 2 when discovery matched nothing; any other
+// error is passed through
+fn exit_if_no_files(logger: &Logger, e: anyhow::Error) -> anyhow::Error {
+    let Some(no_files) = e.downcast_ref::<NoFilesMatched>() else {
+        return e;
+    };
+
+    logger.warn(
+        "no_files_matched",
+        &format!("warning: {}", no_files),
+        &[
+            ("root", json!(no_files.root.display().to_string())),
+            ("files_seen", json!(no_files.stats.files_seen)),
+            (
+                "filtered_by_extension",
+                json!(no_files.stats.filtered_by_extension),
+            ),
+            ("filtered_by_glob", json!(no_files.stats.filtered_by_glob)),
+            ("broken_symlinks", json!(no_files.stats.broken_symlinks)),
+            ("symlink_loops", json!(no_files.stats.symlink_loops)),
+            ("duplicate_files", json!(no_files.stats.duplicate_files)),
+            ("walk_errors", json!(no_files.stats.walk_errors)),
+        ],
+    );
+    std::process::exit(2);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_needs_a_directory() {
+        let cli = Cli::try_parse_from(["fast-wc-rust", "bench", "--files-from", "list.txt"])
+            .expect("bench accepts the count options");
+        let Some(Command::Bench(args)) = &cli.command else {
+            panic!("expected the bench subcommand");
+        };
+        let err = bench(args).unwrap_err();
+        assert!(err.to_string().contains("needs a directory"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_cli_config_is_rejected() {
+        let cli = Cli::try_parse_from(["fast-wc-rust", "-n", "0", "src"]).unwrap();
+        let err = resolve_config(&cli.count).unwrap_err();
+        assert!(err.to_string().contains("num_threads"), "{}", err);
+
+        let cli = Cli::try_parse_from(["fast-wc-rust", "--top", "0", "src"]).unwrap();
+        assert!(resolve_config(&cli.count).is_err());
+
+        let cli = Cli::try_parse_from(["fast-wc-rust", "-n", "2", "src"]).unwrap();
+        assert_eq!(resolve_config(&cli.count).unwrap().0.num_threads, 2);
+    }
+
+    #[test]
+    fn test_output_and_cache_directories_are_locked() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let output = dir.path().join("counts.txt");
+        let cache = dir.path().join("cache").join("counts.cache");
+        let parse = |args: &[&Path]| {
+            let mut argv = vec![OsStr::new("fast-wc-rust")];
+            argv.extend(args.iter().map(|a| a.as_os_str()));
+            Cli::try_parse_from(argv).unwrap()
+        };
+        let arg = |s| Path::new(s);
+
+        // One directory holding both files is locked once
+        let same = parse(&[
+            arg("-o"),
+            &output,
+            arg("--cache"),
+            &dir.path().join("c"),
+            arg("."),
+        ]);
+        assert_eq!(lock_output_dirs(&same.count)?.len(), 1);
+
+        let both = parse(&[arg("-o"), &output, arg("--cache"), &cache, arg(".")]);
+        let locks = lock_output_dirs(&both.count)?;
+        assert_eq!(locks.len(), 2);
+        // A run using the same cache waits for or fails on the first's lock
+        let cache_only = parse(&[arg("--cache"), &cache, arg(".")]);
+        assert!(lock_output_dirs(&cache_only.count).is_err());
+        drop(locks);
+        assert_eq!(lock_output_dirs(&cache_only.count)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cli_overrides_cover_every_settings_key() {
+        let cli = Cli::try_parse_from(["fast-wc-rust", "src"]).unwrap();
+        let mut keys: Vec<_> = cli_overrides(&cli.count).map(|(key, _)| key).into();
+        let mut expected = settings::KEYS.to_vec();
+        keys.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_mmap_flags() {
+        let mmap = |argv: &[&str]| {
+            let cli = Cli::try_parse_from(argv).unwrap();
+            assert_eq!(cli.count.paths, [Path::new("src")]);
+            resolve_config(&cli.count).unwrap().0.use_mmap
+        };
+        assert!(mmap(&["fast-wc-rust", "-m", "src"]));
+        assert!(!mmap(&["fast-wc-rust", "--no-mmap", "src"]));
+        // The last of the two wins
+        assert!(mmap(&["fast-wc-rust", "--no-mmap", "--mmap", "src"]));
+        assert!(!mmap(&["fast-wc-rust", "-m", "--no-mmap", "src"]));
+    }
+
+    #[test]
+    fn test_grep_subcommand_and_flag() {
+        let cli = Cli::try_parse_from(["fast-wc-rust", "grep", "TODO", "src", "-e", "FIXME"])
+            .expect("grep takes a pattern and a directory");
+        let Some(Command::Grep(args)) = &cli.command else {
+            panic!("expected the grep subcommand");
+        };
+        assert_eq!(args.pattern, "TODO");
+        assert_eq!(args.patterns, ["FIXME"]);
+        assert_eq!(args.dir, Path::new("src"));
+
+        // The old flag still works
+        let cli = Cli::try_parse_from(["fast-wc-rust", "--grep", "TODO", "src"])
+            .expect("--grep is kept as an alias");
+        assert!(cli.command.is_none());
+        assert_eq!(cli.count.grep, ["TODO"]);
+    }
 }
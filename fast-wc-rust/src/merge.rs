@@ -0,0 +1,275 @@
+use crate::accumulate::Accumulate;
+use ahash::AHashMap;
+use rayon::prelude::*;
+use std::collections::hash_map::Entry;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Merge strategies for combining per-worker count maps, generic over the
+// per-word value (plain counts by default). Counts saturate at `u64::MAX`;
+// each clamped word bumps `saturated`.
+
+// Fixed seeds so every worker assigns a word to the same shard
+const SHARD_HASHER: ahash::RandomState = ahash::RandomState::with_seeds(
+    0x243f_6a88_85a3_08d3,
+    0x1319_8a2e_0370_7344,
+    0xa409_3822_299f_31d0,
+    0x082e_fa98_ec4e_6c89,
+);
+
+// Add `local` into `acc`
+pub fn merge_into<V: Accumulate>(
+    acc: &mut AHashMap<String, V>,
+    local: AHashMap<String, V>,
+    saturated: &AtomicU64,
+) {
+    for (word, value) in local {
+        match acc.entry(word) {
+            Entry::Occupied(mut entry) => {
+                if entry.get_mut().merge(value) {
+                    saturated.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+}
+
+// Fold every map into one, on the calling thread
+pub fn merge_sequential<V: Accumulate>(
+    maps: Vec<AHashMap<String, V>>,
+    saturated: &AtomicU64,
+) -> AHashMap<String, V> {
+    let mut maps = maps.into_iter();
+    // Reuse the first map's allocation as the accumulator
+    let mut acc = maps.next().unwrap_or_default();
+    for local in maps {
+        merge_into(&mut acc, local, saturated);
+    }
+    acc
+}
+
+// Pairwise rayon reduction that folds whole maps into each other
+pub fn merge_reduce<V: Accumulate>(
+    maps: Vec<AHashMap<String, V>>,
+    saturated: &AtomicU64,
+) -> AHashMap<String, V> {
+    maps.into_par_iter().reduce(
+        || AHashMap::with_capacity(4096),
+        |mut acc, local| {
+            merge_into(&mut acc, local, saturated);
+            acc
+        },
+    )
+}
+
+// Hash-partitioned reduction: each map is split into `shards` pieces by hash
+// prefix, then shard k of every map is merged by its own task. Shards hold
+// disjoint words, so merger tasks never touch the same entry and the final
+// concatenation needs no further summing.
+pub fn merge_partitioned<V: Accumulate>(
+    maps: Vec<AHashMap<String, V>>,
+    shards: usize,
+    saturated: &AtomicU64,
+) -> AHashMap<String, V> {
+    let shards = shards.max(1).next_power_of_two();
+    if shards == 1 || maps.len() < 2 {
+        return merge_sequential(maps, saturated);
+    }
+    let shift = 64 - shards.trailing_zeros();
+
+    let split: Vec<Vec<AHashMap<String, V>>> = maps
+        .into_par_iter()
+        .map(|map| {
+            let per_shard = map.len() / shards + 1;
+            let mut parts: Vec<AHashMap<String, V>> = (0..shards)
+                .map(|_| AHashMap::with_capacity(per_shard))
+                .collect();
+            for (word, value) in map {
+                let shard = (SHARD_HASHER.hash_one(&word) >> shift) as usize;
+                parts[shard].insert(word, value);
+            }
+            parts
+        })
+        .collect();
+
+    // Transpose to shard-major order
+    let mut by_shard: Vec<Vec<AHashMap<String, V>>> = (0..shards)
+        .map(|_| Vec::with_capacity(split.len()))
+        .collect();
+    for parts in split {
+        for (shard, part) in parts.into_iter().enumerate() {
+            by_shard[shard].push(part);
+        }
+    }
+
+    let merged: Vec<AHashMap<String, V>> = by_shard
+        .into_par_iter()
+        .map(|parts| merge_sequential(parts, saturated))
+        .collect();
+    concat_shards(merged)
+}
+
+// Concatenate maps with disjoint keys into the largest one
+fn concat_shards<V>(mut shards: Vec<AHashMap<String, V>>) -> AHashMap<String, V> {
+    let largest = (0..shards.len())
+        .max_by_key(|&i| shards[i].len())
+        .unwrap_or(0);
+    if shards.is_empty() {
+        return AHashMap::new();
+    }
+    let mut result = shards.swap_remove(largest);
+    result.reserve(shards.iter().map(|m| m.len()).sum());
+    for shard in shards {
+        result.extend(shard);
+    }
+    result
+}
+
+// Fold every map into the largest one in place (`MergeStrategy::Arena`).
+// The largest map's keys stay where they are, so its entries are never
+// re-inserted; the other maps' words are looked up against it, and a
+// duplicate's `String` is dropped rather than re-inserted. The table compares
+// each word's stored hash tag before its bytes (and `String` equality checks
+// length first), and it grows at most once, for every word that could be new.
+pub fn merge_arena<V: Accumulate>(
+    mut maps: Vec<AHashMap<String, V>>,
+    saturated: &AtomicU64,
+) -> AHashMap<String, V> {
+    let Some(largest) = (0..maps.len()).max_by_key(|&i| maps[i].len()) else {
+        return AHashMap::new();
+    };
+    let mut arena = maps.swap_remove(largest);
+    arena.reserve(maps.iter().map(|map| map.len()).sum());
+    for map in maps {
+        merge_into(&mut arena, map, saturated);
+    }
+    arena
+}
+
+// Shared counts split into shards by hash prefix, which workers add into
+// concurrently (`MergeStrategy::Sharded`). A worker locks each shard once
+// per flushed map, so there is no separate merge phase at the end.
+pub struct ShardedCounts<V = u64> {
+    shift: u32,
+    shards: Vec<Mutex<AHashMap<String, V>>>,
+}
+
+impl<V: Accumulate> ShardedCounts<V> {
+    // `shards` is rounded up to a power of two
+    pub fn new(shards: usize) -> Self {
+        let shards = shards.max(2).next_power_of_two();
+        Self {
+            shift: 64 - shards.trailing_zeros(),
+            shards: (0..shards).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    // Add a worker's map, partitioning it first so each shard lock is
+    // taken once
+    pub fn add(&self, local: AHashMap<String, V>, saturated: &AtomicU64) {
+        let per_shard = local.len() / self.shards.len() + 1;
+        let mut parts: Vec<AHashMap<String, V>> = (0..self.shards.len())
+            .map(|_| AHashMap::with_capacity(per_shard))
+            .collect();
+        for (word, value) in local {
+            let shard = (SHARD_HASHER.hash_one(&word) >> self.shift) as usize;
+            parts[shard].insert(word, value);
+        }
+        for (shard, part) in self.shards.iter().zip(parts) {
+            if !part.is_empty() {
+                merge_into(&mut shard.lock().unwrap(), part, saturated);
+            }
+        }
+    }
+
+    pub fn into_counts(self) -> AHashMap<String, V> {
+        concat_shards(
+            self.shards
+                .into_iter()
+                .map(|shard| shard.into_inner().unwrap())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, u64)]) -> AHashMap<String, u64> {
+        pairs.iter().map(|(w, c)| (w.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn test_partitioned_matches_sequential() {
+        let maps: Vec<_> = (0..6u64)
+            .map(|i| {
+                (0..200u64)
+                    .map(|w| (format!("w{}", (w * (i + 1)) % 257), w + i))
+                    .collect::<AHashMap<_, _>>()
+            })
+            .collect();
+        let saturated = AtomicU64::new(0);
+
+        let expected = merge_sequential(maps.clone(), &saturated);
+        for shards in [1, 3, 16] {
+            assert_eq!(
+                merge_partitioned(maps.clone(), shards, &saturated),
+                expected
+            );
+        }
+        assert_eq!(saturated.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_partitioned_saturates() {
+        let saturated = AtomicU64::new(0);
+        let merged = merge_partitioned(
+            vec![map(&[("w", u64::MAX - 1)]), map(&[("w", 5), ("x", 1)])],
+            8,
+            &saturated,
+        );
+        assert_eq!(merged.get("w"), Some(&u64::MAX));
+        assert_eq!(merged.get("x"), Some(&1));
+        assert_eq!(saturated.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_arena_matches_sequential_and_saturates() {
+        let maps = vec![
+            map(&[("a", 1)]),
+            map(&[("a", 2), ("b", 3), ("ab", u64::MAX), ("", 1)]),
+            map(&[("b", 4), ("ab", 1), ("c", 5)]),
+        ];
+        let saturated = AtomicU64::new(0);
+        assert_eq!(
+            merge_arena(maps.clone(), &saturated),
+            merge_sequential(maps, &AtomicU64::new(0))
+        );
+        assert_eq!(saturated.load(Ordering::Relaxed), 1);
+        assert!(merge_arena::<u64>(Vec::new(), &saturated).is_empty());
+    }
+
+    #[test]
+    fn test_sharded_counts_match_sequential() {
+        let maps = vec![
+            map(&[("a", 1), ("b", 2)]),
+            map(&[("b", 3), ("c", u64::MAX)]),
+            map(&[("c", 1), ("d", 4)]),
+        ];
+        let saturated = AtomicU64::new(0);
+        let sharded = ShardedCounts::new(4);
+        maps.clone()
+            .into_par_iter()
+            .for_each(|local| sharded.add(local, &saturated));
+
+        assert_eq!(
+            sharded.into_counts(),
+            merge_sequential(maps, &AtomicU64::new(0))
+        );
+        assert_eq!(saturated.load(Ordering::Relaxed), 1);
+    }
+}
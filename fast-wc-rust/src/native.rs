@@ -0,0 +1,188 @@
+// Native tokenizer/filter plugins loaded with dlopen (feature
+// `native-plugins`).
+//
+// A plugin is a shared library exporting `fast_wc_plugin`, which returns a
+// pointer to a static vtable:
+//
+//   typedef void (*fast_wc_emit)(void *ctx, const uint8_t *ptr, size_t len);
+//
+//   typedef struct {
+//       uint32_t abi_version;  /* FAST_WC_PLUGIN_ABI_VERSION (1) */
+//       /* Call `emit(ctx, ...)` once per token in data[0..len]; may be NULL */
+//       void (*tokenize)(const uint8_t *data, size_t len,
+//                        fast_wc_emit emit, void *ctx);
+//       /* Return non-zero to keep a word; may be NULL */
+//       int32_t (*keep)(const uint8_t *word, size_t len);
+//   } fast_wc_plugin_vtable;
+//
+//   const fast_wc_plugin_vtable *fast_wc_plugin(void);
+//
+// Both callbacks are invoked concurrently from worker threads and must be
+// thread-safe. The emitted bytes only need to live for the `emit` call.
+
+use ahash::AHashMap;
+use anyhow::{Context, Result, anyhow, bail};
+use std::ffi::c_void;
+use std::path::Path;
+
+pub const ABI_VERSION: u32 = 1;
+// Symbol every plugin library must export
+pub const ENTRY_SYMBOL: &[u8] = b"fast_wc_plugin";
+
+pub type EmitFn = unsafe extern "C" fn(ctx: *mut c_void, ptr: *const u8, len: usize);
+pub type TokenizeFn =
+    unsafe extern "C" fn(data: *const u8, len: usize, emit: EmitFn, ctx: *mut c_void);
+pub type KeepFn = unsafe extern "C" fn(word: *const u8, len: usize) -> i32;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginVTable {
+    pub abi_version: u32,
+    pub tokenize: Option<TokenizeFn>,
+    pub keep: Option<KeepFn>,
+}
+
+// A loaded plugin; the library stays open for as long as this lives
+pub struct NativePlugin {
+    vtable: PluginVTable,
+    _library: Option<libloading::Library>,
+}
+
+impl NativePlugin {
+    // Load a plugin library and validate its vtable.
+    //
+    // Loading runs the library's initializers, so only load trusted code.
+    pub fn load(path: &Path) -> Result<Self> {
+        // SAFETY: the caller chose to run this library; the entry point's
+        // signature is fixed by the plugin ABI above
+        unsafe {
+            let library = libloading::Library::new(path)
+                .with_context(|| format!("Failed to load plugin {}", path.display()))?;
+            let entry: libloading::Symbol<unsafe extern "C" fn() -> *const PluginVTable> = library
+                .get(ENTRY_SYMBOL)
+                .with_context(|| format!("{} does not export fast_wc_plugin", path.display()))?;
+            let vtable = entry()
+                .as_ref()
+                .copied()
+                .ok_or_else(|| anyhow!("fast_wc_plugin in {} returned NULL", path.display()))?;
+
+            let mut plugin = Self::from_vtable(vtable)?;
+            plugin._library = Some(library);
+            Ok(plugin)
+        }
+    }
+
+    // Wrap a vtable that is linked in rather than loaded
+    pub fn from_vtable(vtable: PluginVTable) -> Result<Self> {
+        if vtable.abi_version != ABI_VERSION {
+            bail!(
+                "plugin ABI version {} is not supported (expected {})",
+                vtable.abi_version,
+                ABI_VERSION
+            );
+        }
+        if vtable.tokenize.is_none() && vtable.keep.is_none() {
+            bail!("plugin provides neither tokenize nor keep");
+        }
+        Ok(Self {
+            vtable,
+            _library: None,
+        })
+    }
+
+    pub fn is_tokenizer(&self) -> bool {
+        self.vtable.tokenize.is_some()
+    }
+
+    pub fn is_filter(&self) -> bool {
+        self.vtable.keep.is_some()
+    }
+
+    // Run the plugin tokenizer over `data`, adding emitted tokens to `counts`
+    pub fn tokenize(&self, data: &[u8], counts: &mut AHashMap<String, u64>) {
+        let Some(tokenize) = self.vtable.tokenize else {
+            return;
+        };
+        // SAFETY: `counts` outlives the call and is only touched by `emit`
+        unsafe {
+            tokenize(
+                data.as_ptr(),
+                data.len(),
+                emit,
+                counts as *mut AHashMap<String, u64> as *mut c_void,
+            );
+        }
+    }
+
+    // Ask the plugin filter whether to keep `word`; true without a filter
+    pub fn keep(&self, word: &str) -> bool {
+        match self.vtable.keep {
+            // SAFETY: the pointer and length describe a live byte slice
+            Some(keep) => unsafe { keep(word.as_ptr(), word.len()) != 0 },
+            None => true,
+        }
+    }
+}
+
+unsafe extern "C" fn emit(ctx: *mut c_void, ptr: *const u8, len: usize) {
+    if ctx.is_null() || (ptr.is_null() && len > 0) {
+        return;
+    }
+    // SAFETY: `ctx` is the map passed by `tokenize`; the plugin guarantees
+    // `ptr..ptr+len` is readable for the duration of this call
+    let (counts, bytes) = unsafe {
+        (
+            &mut *(ctx as *mut AHashMap<String, u64>),
+            std::slice::from_raw_parts(ptr, len),
+        )
+    };
+    let word = String::from_utf8_lossy(bytes);
+    if let Some(count) = counts.get_mut(word.as_ref()) {
+        *count += 1;
+    } else {
+        counts.insert(word.into_owned(), 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Splits on ASCII whitespace
+    unsafe extern "C" fn split_ws(data: *const u8, len: usize, emit: EmitFn, ctx: *mut c_void) {
+        let data = unsafe { std::slice::from_raw_parts(data, len) };
+        for word in data.split(|b| b.is_ascii_whitespace()) {
+            if !word.is_empty() {
+                unsafe { emit(ctx, word.as_ptr(), word.len()) };
+            }
+        }
+    }
+
+    unsafe extern "C" fn longer_than_two(_word: *const u8, len: usize) -> i32 {
+        (len > 2) as i32
+    }
+
+    #[test]
+    fn test_vtable_tokenize_and_keep() -> Result<()> {
+        let plugin = NativePlugin::from_vtable(PluginVTable {
+            abi_version: ABI_VERSION,
+            tokenize: Some(split_ws),
+            keep: Some(longer_than_two),
+        })?;
+
+        let mut counts = AHashMap::new();
+        plugin.tokenize(b"a->b  a->b\tx", &mut counts);
+        assert_eq!(counts.get("a->b"), Some(&2));
+        assert_eq!(counts.get("x"), Some(&1));
+        assert!(plugin.keep("a->b"));
+        assert!(!plugin.keep("x"));
+
+        let stale = PluginVTable {
+            abi_version: 0,
+            tokenize: Some(split_ws),
+            keep: None,
+        };
+        assert!(NativePlugin::from_vtable(stale).is_err());
+        Ok(())
+    }
+}
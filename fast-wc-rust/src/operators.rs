@@ -0,0 +1,71 @@
+use ahash::AHashMap;
+
+// C operators and punctuators, longest first within each length so that
+// matching is maximal munch
+const PUNCTUATORS_3: [&[u8]; 3] = [b"<<=", b">>=", b"..."];
+const PUNCTUATORS_2: [&[u8]; 20] = [
+    b"->", b"++", b"--", b"<<", b">>", b"<=", b">=", b"==", b"!=", b"&&", b"||", b"*=", b"/=",
+    b"%=", b"+=", b"-=", b"&=", b"^=", b"|=", b"##",
+];
+const PUNCTUATORS_1: &[u8] = b"[](){}.&*+-~!/%<>^|?:;=,#";
+
+// Length of the longest punctuator starting at `data[i]`, or 0
+#[inline]
+fn match_at(data: &[u8], i: usize) -> usize {
+    let rest = &data[i..];
+    if PUNCTUATORS_3.iter().any(|p| rest.starts_with(p)) {
+        3
+    } else if PUNCTUATORS_2.iter().any(|p| rest.starts_with(p)) {
+        2
+    } else if PUNCTUATORS_1.contains(&rest[0]) {
+        1
+    } else {
+        0
+    }
+}
+
+// Count operators and punctuators in `data` using maximal munch
+pub fn count_operators(data: &[u8], counts: &mut AHashMap<String, u64>) -> u64 {
+    let mut i = 0;
+    let mut found = 0;
+
+    while i < data.len() {
+        let len = match_at(data, i);
+        if len == 0 {
+            i += 1;
+            continue;
+        }
+
+        let op = &data[i..i + len];
+        match counts.get_mut(std::str::from_utf8(op).unwrap()) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(String::from_utf8(op.to_vec()).unwrap(), 1);
+            }
+        }
+        found += 1;
+        i += len;
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maximal_munch() {
+        let mut counts = AHashMap::new();
+        count_operators(b"a <<= b->c; if (x == y && z) x++;", &mut counts);
+
+        assert_eq!(counts.get("<<="), Some(&1));
+        assert_eq!(counts.get("->"), Some(&1));
+        assert_eq!(counts.get("=="), Some(&1));
+        assert_eq!(counts.get("&&"), Some(&1));
+        assert_eq!(counts.get("++"), Some(&1));
+        assert_eq!(counts.get(";"), Some(&2));
+        assert_eq!(counts.get("<"), None);
+        assert_eq!(counts.get("="), None);
+    }
+}
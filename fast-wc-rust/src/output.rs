@@ -0,0 +1,167 @@
+use crate::PathEncoding;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Render `path` as text. Paths are kept as `OsStr` until output; only here
+// are names that are not valid UTF-8 replaced or escaped.
+pub fn encode_path(path: &Path, encoding: PathEncoding) -> Cow<'_, str> {
+    match encoding {
+        PathEncoding::Lossy => path.to_string_lossy(),
+        PathEncoding::Percent => {
+            let bytes = path.as_os_str().as_encoded_bytes();
+            let mut out = String::with_capacity(bytes.len());
+            for chunk in bytes.utf8_chunks() {
+                for c in chunk.valid().chars() {
+                    if c == '%' || c.is_control() {
+                        let mut buf = [0; 4];
+                        for byte in c.encode_utf8(&mut buf).bytes() {
+                            let _ = write!(out, "%{:02X}", byte);
+                        }
+                    } else {
+                        out.push(c);
+                    }
+                }
+                for byte in chunk.invalid() {
+                    let _ = write!(out, "%{:02X}", byte);
+                }
+            }
+            Cow::Owned(out)
+        }
+    }
+}
+
+// A file written under a temporary name next to its destination and renamed
+// into place by `commit`, so readers see either the old file or the complete
+// new one, never a truncated write. Dropping without committing removes the
+// temporary file.
+pub struct AtomicFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    fsync: bool,
+}
+
+impl AtomicFile {
+    // With `fsync`, the data and the rename are flushed to disk before
+    // `commit` returns
+    pub fn create(path: &Path, fsync: bool) -> io::Result<Self> {
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name")
+        })?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(".tmp-{}", std::process::id()));
+        let tmp_path = path.with_file_name(tmp_name);
+
+        let file = File::create(&tmp_path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            tmp_path,
+            writer: Some(BufWriter::new(file)),
+            fsync,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Flush, optionally fsync, and rename over the destination
+    pub fn commit(mut self) -> io::Result<()> {
+        let writer = self.writer.take().expect("writer present until commit");
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        if self.fsync {
+            file.sync_all()?;
+        }
+        drop(file);
+
+        std::fs::rename(&self.tmp_path, &self.path)?;
+
+        // Persist the rename itself; directories can't be opened for this on
+        // Windows, where the rename is already durable once it returns
+        #[cfg(unix)]
+        if self.fsync {
+            let parent = match self.path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer
+            .as_mut()
+            .expect("writer present until commit")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer
+            .as_mut()
+            .expect("writer present until commit")
+            .flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.writer.take().is_some() {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_percent_encoding_keeps_exact_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(
+            b"src/na\xefve 100%\n\xc3\xa9.c",
+        ));
+        assert_eq!(
+            encode_path(path, PathEncoding::Percent),
+            "src/na%EFve 100%25%0A\u{e9}.c"
+        );
+        assert_eq!(
+            encode_path(path, PathEncoding::Lossy),
+            "src/na\u{fffd}ve 100%\n\u{e9}.c"
+        );
+        assert_eq!(
+            encode_path(Path::new("plain/a.c"), PathEncoding::Percent),
+            "plain/a.c"
+        );
+    }
+
+    #[test]
+    fn test_commit_replaces_and_drop_discards() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("counts.txt");
+        std::fs::write(&path, "old")?;
+
+        let mut file = AtomicFile::create(&path, true)?;
+        file.write_all(b"new")?;
+        assert_eq!(std::fs::read_to_string(&path)?, "old");
+        file.commit()?;
+        assert_eq!(std::fs::read_to_string(&path)?, "new");
+
+        // An interrupted write leaves the previous results untouched
+        let mut file = AtomicFile::create(&path, false)?;
+        file.write_all(b"partial")?;
+        drop(file);
+        assert_eq!(std::fs::read_to_string(&path)?, "new");
+        assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+        Ok(())
+    }
+}
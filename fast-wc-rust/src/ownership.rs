@@ -0,0 +1,146 @@
+use ahash::AHashMap;
+use std::path::{Path, PathBuf};
+
+// The file accounting for the largest share of a word's occurrences
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ownership {
+    pub word: String,
+    // Occurrences across all files
+    pub total: u64,
+    pub owner: PathBuf,
+    // Occurrences in `owner`
+    pub owner_count: u64,
+}
+
+impl Ownership {
+    // `owner`'s share of the occurrences, in percent: near 100 for
+    // file-local names, low for project-wide vocabulary
+    pub fn share(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.owner_count as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Owner {
+    total: u64,
+    // Index of the owning file, and its occurrences
+    file: usize,
+    count: u64,
+}
+
+impl Owner {
+    // Whether `other`'s file owns more of the word; equal counts go to the
+    // earlier file, so the result does not depend on scheduling
+    fn beaten_by(&self, other: &Owner) -> bool {
+        (other.count, std::cmp::Reverse(other.file)) > (self.count, std::cmp::Reverse(self.file))
+    }
+
+    // Fold in another file's (or worker's) counts for the same word
+    fn absorb(&mut self, other: Owner) {
+        let total = self.total.saturating_add(other.total);
+        if self.beaten_by(&other) {
+            *self = other;
+        }
+        self.total = total;
+    }
+}
+
+// Per-word totals and owning file, built one file at a time by
+// `FastWordCounter::identifier_ownership` and merged across workers
+#[derive(Debug, Default)]
+pub(crate) struct Owners {
+    words: AHashMap<String, Owner>,
+}
+
+impl Owners {
+    // Add one file's word counts; `file` indexes the discovered file list
+    pub(crate) fn add_file(&mut self, file: usize, counts: AHashMap<String, u64>) {
+        for (word, count) in counts {
+            let candidate = Owner {
+                total: count,
+                file,
+                count,
+            };
+            match self.words.get_mut(&word) {
+                Some(owner) => owner.absorb(candidate),
+                None => {
+                    self.words.insert(word, candidate);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn merge(mut self, other: Owners) -> Owners {
+        if self.words.len() < other.words.len() {
+            return other.merge(self);
+        }
+        for (word, candidate) in other.words {
+            match self.words.get_mut(&word) {
+                Some(owner) => owner.absorb(candidate),
+                None => {
+                    self.words.insert(word, candidate);
+                }
+            }
+        }
+        self
+    }
+
+    // The `n` most frequent words (count descending, then alphabetically)
+    // with their owners, resolving file indexes against `files`
+    pub(crate) fn top(self, n: usize, files: &[impl AsRef<Path>]) -> Vec<Ownership> {
+        let mut words: Vec<(String, Owner)> = self.words.into_iter().collect();
+        words.sort_unstable_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        words.truncate(n);
+        words
+            .into_iter()
+            .map(|(word, owner)| Ownership {
+                word,
+                total: owner.total,
+                owner: files[owner.file].as_ref().to_path_buf(),
+                owner_count: owner.count,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(pairs: &[(&str, u64)]) -> AHashMap<String, u64> {
+        pairs.iter().map(|(w, c)| (w.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn test_owners_pick_largest_share() {
+        let files = ["a.c", "b.c", "c.c"];
+        let mut first = Owners::default();
+        first.add_file(0, counts(&[("ctx", 2), ("local_tmp", 5)]));
+        let mut second = Owners::default();
+        second.add_file(2, counts(&[("ctx", 2)]));
+        second.add_file(1, counts(&[("ctx", 4), ("local_tmp", 1)]));
+
+        let top = first.merge(second).top(10, &files);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].word, "ctx");
+        assert_eq!(top[0].total, 8);
+        assert_eq!(top[0].owner, PathBuf::from("b.c"));
+        assert_eq!(top[0].share(), 50.0);
+        assert_eq!(top[1].owner, PathBuf::from("a.c"));
+        assert!((top[1].share() - 83.3).abs() < 0.1);
+
+        // Ties go to the earlier file whatever the merge order
+        let mut late = Owners::default();
+        late.add_file(1, counts(&[("x", 3)]));
+        let mut early = Owners::default();
+        early.add_file(0, counts(&[("x", 3)]));
+        assert_eq!(
+            late.merge(early).top(1, &files)[0].owner,
+            PathBuf::from("a.c")
+        );
+    }
+}
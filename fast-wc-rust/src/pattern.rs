@@ -0,0 +1,141 @@
+use anyhow::{Context, Result};
+use regex::bytes::Regex;
+use std::path::PathBuf;
+
+// Result of counting regex matches over a corpus
+#[derive(Debug, Clone, Default)]
+pub struct PatternReport {
+    // Each pattern and its total number of matches, in the order given
+    pub totals: Vec<(String, u64)>,
+    // Files with at least one match and their per-pattern counts (indexed
+    // like `totals`), sorted by path; empty unless requested
+    pub files: Vec<(PathBuf, Vec<u64>)>,
+}
+
+impl PatternReport {
+    // Sum per-file counts into per-pattern totals, keeping the files with a
+    // match (sorted by path) only if `per_file`
+    pub(crate) fn from_files(
+        patterns: &[String],
+        mut files: Vec<(PathBuf, Vec<u64>)>,
+        per_file: bool,
+    ) -> Self {
+        let mut totals = vec![0u64; patterns.len()];
+        for (_, counts) in &files {
+            for (total, count) in totals.iter_mut().zip(counts) {
+                *total = total.saturating_add(*count);
+            }
+        }
+        if per_file {
+            files.retain(|(_, counts)| counts.iter().any(|&c| c > 0));
+            files.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        } else {
+            files.clear();
+        }
+        Self {
+            totals: patterns.iter().cloned().zip(totals).collect(),
+            files,
+        }
+    }
+}
+
+// Compile `patterns` for matching against raw file bytes
+pub fn compile(patterns: &[String]) -> Result<Vec<Regex>> {
+    patterns
+        .iter()
+        .map(|p| Regex::new(p).with_context(|| format!("invalid regex '{}'", p)))
+        .collect()
+}
+
+// Add the number of non-overlapping matches of each regex in `data` to
+// `counts`, which must have one slot per regex
+pub fn count_matches(regexes: &[Regex], data: &[u8], counts: &mut [u64]) {
+    for (regex, count) in regexes.iter().zip(counts.iter_mut()) {
+        *count = count.saturating_add(regex.find_iter(data).count() as u64);
+    }
+}
+
+// Count matches like `count_matches`, and call `emit` once per line holding
+// the start of a match, in file order, with its 1-based number and its text
+// (without the line terminator)
+pub fn grep_lines<F>(regexes: &[Regex], data: &[u8], counts: &mut [u64], mut emit: F)
+where
+    F: FnMut(usize, &[u8]),
+{
+    let mut starts = Vec::new();
+    for (regex, count) in regexes.iter().zip(counts.iter_mut()) {
+        let before = starts.len();
+        starts.extend(regex.find_iter(data).map(|m| m.start()));
+        *count = count.saturating_add((starts.len() - before) as u64);
+    }
+    if starts.is_empty() {
+        return;
+    }
+    if regexes.len() > 1 {
+        starts.sort_unstable();
+    }
+
+    let mut line_no = 1;
+    let mut scanned = 0;
+    let mut next_line = 0;
+    for start in starts {
+        // Already printed this match's line
+        if start < next_line {
+            continue;
+        }
+        line_no += data[scanned..start].iter().filter(|&&b| b == b'\n').count();
+        scanned = start;
+
+        let line_start = data[..start]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line_end = data[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |i| start + i);
+        let line = &data[line_start..line_end];
+        emit(line_no, line.strip_suffix(b"\r").unwrap_or(line));
+        next_line = line_end + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_matches_per_pattern() -> Result<()> {
+        let regexes = compile(&[r"\bTODO\b".to_string(), r"0x[0-9a-fA-F]+".to_string()])?;
+        let mut counts = vec![0; 2];
+        count_matches(&regexes, b"// TODO: 0xFF\n// TODOS 0x1 0x2", &mut counts);
+        assert_eq!(counts, [1, 3]);
+
+        assert!(compile(&["(".to_string()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_grep_lines_reports_each_line_once() -> Result<()> {
+        let regexes = compile(&["x".to_string(), "TODO".to_string()])?;
+        let mut counts = vec![0; 2];
+        let mut lines = Vec::new();
+        grep_lines(
+            &regexes,
+            b"int x = x;\r\nint y;\n// TODO\nx",
+            &mut counts,
+            |n, line| lines.push((n, String::from_utf8_lossy(line).into_owned())),
+        );
+
+        assert_eq!(counts, [3, 1]);
+        assert_eq!(
+            lines,
+            [
+                (1, "int x = x;".to_string()),
+                (3, "// TODO".to_string()),
+                (4, "x".to_string())
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+// WASM plugin host (feature `wasm`).
+//
+// A plugin is a core WebAssembly module exporting `memory` and
+// `alloc(len: i32) -> i32`, plus one or both of:
+//
+// - `tokenize(ptr: i32, len: i32)`: scan the buffer and call the imported
+//   `env.emit(ptr: i32, len: i32)` once per token
+// - `keep(ptr: i32, len: i32) -> i32`: return non-zero to keep a word
+//
+// Modules may be given as `.wasm` binaries or `.wat` text.
+
+use ahash::AHashMap;
+use anyhow::{Context, Result, anyhow};
+use std::path::Path;
+use wasmtime::{Caller, Engine, Linker, Memory, Module, Store, TypedFunc};
+
+// A compiled plugin; cheap to instantiate once per file or thread
+pub struct WasmPlugin {
+    engine: Engine,
+    module: Module,
+    linker: Linker<PluginState>,
+    has_tokenize: bool,
+    has_keep: bool,
+}
+
+#[derive(Default)]
+struct PluginState {
+    counts: AHashMap<String, u64>,
+}
+
+// A live instance with its own store and memory
+pub struct PluginInstance {
+    store: Store<PluginState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    tokenize: Option<TypedFunc<(i32, i32), ()>>,
+    keep: Option<TypedFunc<(i32, i32), i32>>,
+}
+
+impl WasmPlugin {
+    pub fn load(path: &Path) -> Result<Self> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, path)
+            .with_context(|| format!("Failed to load WASM plugin {}", path.display()))?;
+        Self::from_module(engine, module)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let engine = Engine::default();
+        let module = Module::new(&engine, bytes)?;
+        Self::from_module(engine, module)
+    }
+
+    fn from_module(engine: Engine, module: Module) -> Result<Self> {
+        let has_export = |name: &str| module.exports().any(|e| e.name() == name);
+        for required in ["memory", "alloc"] {
+            if !has_export(required) {
+                return Err(anyhow!("WASM plugin does not export '{}'", required));
+            }
+        }
+        let has_tokenize = has_export("tokenize");
+        let has_keep = has_export("keep");
+        if !has_tokenize && !has_keep {
+            return Err(anyhow!("WASM plugin exports neither 'tokenize' nor 'keep'"));
+        }
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            "env",
+            "emit",
+            |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+                let memory = caller
+                    .get_export("memory")
+                    .and_then(|e| e.into_memory())
+                    .ok_or_else(|| anyhow!("plugin has no memory export"))?;
+                let (start, end) = (
+                    ptr as u32 as usize,
+                    ptr as u32 as usize + len as u32 as usize,
+                );
+                let word = memory
+                    .data(&caller)
+                    .get(start..end)
+                    .ok_or_else(|| anyhow!("emit out of bounds"))?;
+                let word = String::from_utf8_lossy(word).into_owned();
+                *caller.data_mut().counts.entry(word).or_insert(0) += 1;
+                Ok(())
+            },
+        )?;
+
+        Ok(Self {
+            engine,
+            module,
+            linker,
+            has_tokenize,
+            has_keep,
+        })
+    }
+
+    pub fn is_tokenizer(&self) -> bool {
+        self.has_tokenize
+    }
+
+    pub fn is_filter(&self) -> bool {
+        self.has_keep
+    }
+
+    pub fn instantiate(&self) -> Result<PluginInstance> {
+        let mut store = Store::new(&self.engine, PluginState::default());
+        let instance = self.linker.instantiate(&mut store, &self.module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("plugin 'memory' is not a memory"))?;
+        let alloc = instance.get_typed_func(&mut store, "alloc")?;
+        let tokenize = if self.has_tokenize {
+            Some(instance.get_typed_func(&mut store, "tokenize")?)
+        } else {
+            None
+        };
+        let keep = if self.has_keep {
+            Some(instance.get_typed_func(&mut store, "keep")?)
+        } else {
+            None
+        };
+
+        Ok(PluginInstance {
+            store,
+            memory,
+            alloc,
+            tokenize,
+            keep,
+        })
+    }
+}
+
+impl PluginInstance {
+    // Copy `bytes` into plugin memory, returning (ptr, len)
+    fn write(&mut self, bytes: &[u8]) -> Result<(i32, i32)> {
+        let len = i32::try_from(bytes.len()).context("input too large for WASM plugin")?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, bytes)?;
+        Ok((ptr, len))
+    }
+
+    // Run the plugin tokenizer over `data`, adding emitted tokens to `counts`
+    pub fn tokenize(&mut self, data: &[u8], counts: &mut AHashMap<String, u64>) -> Result<()> {
+        let tokenize = self
+            .tokenize
+            .clone()
+            .ok_or_else(|| anyhow!("plugin does not export 'tokenize'"))?;
+        let (ptr, len) = self.write(data)?;
+        tokenize.call(&mut self.store, (ptr, len))?;
+
+        for (word, count) in self.store.data_mut().counts.drain() {
+            *counts.entry(word).or_insert(0) += count;
+        }
+        Ok(())
+    }
+
+    // Ask the plugin filter whether to keep `word`
+    pub fn keep(&mut self, word: &str) -> Result<bool> {
+        let keep = self
+            .keep
+            .clone()
+            .ok_or_else(|| anyhow!("plugin does not export 'keep'"))?;
+        let (ptr, len) = self.write(word.as_bytes())?;
+        Ok(keep.call(&mut self.store, (ptr, len))? != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Emits the whole buffer as one token; keeps words longer than 3 bytes.
+    // `alloc` always hands out offset 1024.
+    const PLUGIN: &str = r#"
+        (module
+          (import "env" "emit" (func $emit (param i32 i32)))
+          (memory (export "memory") 1)
+          (func (export "alloc") (param i32) (result i32) i32.const 1024)
+          (func (export "tokenize") (param i32 i32)
+            local.get 0
+            local.get 1
+            call $emit)
+          (func (export "keep") (param i32 i32) (result i32)
+            local.get 1
+            i32.const 3
+            i32.gt_s))
+    "#;
+
+    #[test]
+    fn test_tokenize_and_keep() -> Result<()> {
+        let plugin = WasmPlugin::from_bytes(PLUGIN.as_bytes())?;
+        assert!(plugin.is_tokenizer() && plugin.is_filter());
+
+        let mut instance = plugin.instantiate()?;
+        let mut counts = AHashMap::new();
+        instance.tokenize(b"whole-buffer", &mut counts)?;
+        assert_eq!(counts.get("whole-buffer"), Some(&1));
+
+        assert!(instance.keep("long")?);
+        assert!(!instance.keep("abc")?);
+        Ok(())
+    }
+}
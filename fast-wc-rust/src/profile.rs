@@ -0,0 +1,104 @@
+use serde_json::{Value, json};
+use std::fmt;
+use std::time::Duration;
+
+// Per-phase timings from `FastWordCounter::profile_run`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub files: u64,
+    pub bytes: u64,
+    pub unique_words: usize,
+    pub discovery: Duration,
+    // Reading and tokenizing, up to the per-worker maps
+    pub scan: Duration,
+    pub merge: Duration,
+    pub sort: Duration,
+}
+
+impl ProfileReport {
+    pub fn total(&self) -> Duration {
+        self.discovery + self.scan + self.merge + self.sort
+    }
+
+    // Input bytes per second over the whole run
+    pub fn bytes_per_sec(&self) -> f64 {
+        per_sec(self.bytes as f64, self.total())
+    }
+
+    // Input bytes per second while scanning
+    pub fn scan_bytes_per_sec(&self) -> f64 {
+        per_sec(self.bytes as f64, self.scan)
+    }
+
+    pub fn files_per_sec(&self) -> f64 {
+        per_sec(self.files as f64, self.total())
+    }
+
+    // Stats as a JSON object: sizes, per-phase milliseconds and throughput
+    pub fn to_json(&self) -> Value {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        json!({
+            "files": self.files,
+            "bytes": self.bytes,
+            "unique_words": self.unique_words,
+            "discovery_ms": ms(self.discovery),
+            "scan_ms": ms(self.scan),
+            "merge_ms": ms(self.merge),
+            "sort_ms": ms(self.sort),
+            "total_ms": ms(self.total()),
+            "bytes_per_sec": self.bytes_per_sec(),
+            "scan_bytes_per_sec": self.scan_bytes_per_sec(),
+        })
+    }
+}
+
+fn per_sec(amount: f64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { amount / secs } else { 0.0 }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} files, {} bytes, {} unique words",
+            self.files, self.bytes, self.unique_words
+        )?;
+        for (phase, elapsed) in [
+            ("discovery", self.discovery),
+            ("scan", self.scan),
+            ("merge", self.merge),
+            ("sort", self.sort),
+            ("total", self.total()),
+        ] {
+            writeln!(f, "{:>10}: {:>10.2?}", phase, elapsed)?;
+        }
+        write!(
+            f,
+            "throughput: {:.1} MiB/s overall, {:.1} MiB/s scanning",
+            self.bytes_per_sec() / (1024.0 * 1024.0),
+            self.scan_bytes_per_sec() / (1024.0 * 1024.0)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput() {
+        let report = ProfileReport {
+            bytes: 2000,
+            files: 4,
+            scan: Duration::from_millis(500),
+            merge: Duration::from_millis(500),
+            ..ProfileReport::default()
+        };
+        assert_eq!(report.total(), Duration::from_secs(1));
+        assert_eq!(report.bytes_per_sec(), 2000.0);
+        assert_eq!(report.scan_bytes_per_sec(), 4000.0);
+        assert_eq!(ProfileReport::default().bytes_per_sec(), 0.0);
+        assert_eq!(report.to_json()["bytes_per_sec"], 2000.0);
+    }
+}
@@ -0,0 +1,587 @@
+use crate::artifact::{self, RESULTS, RESULTS_VERSION};
+use crate::error::FileError;
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result, bail};
+use std::path::Path;
+
+// Default frequency classes for `CountReport::bucketize`
+pub const DEFAULT_BUCKETS: [(&str, u64); 3] = [("very common", 1000), ("common", 100), ("rare", 1)];
+
+// Example words kept per bucket
+const BUCKET_EXAMPLES: usize = 5;
+
+// Summary of the words in one frequency class
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyBucket {
+    pub name: String,
+    // Smallest count that falls into this bucket
+    pub min_count: u64,
+    pub words: usize,
+    pub occurrences: u64,
+    // Fraction of all occurrences in the report
+    pub share: f64,
+    // The most frequent words in the bucket
+    pub examples: Vec<String>,
+}
+
+// Words that differ only in case, from `CountReport::fold_case`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseGroup {
+    // The most frequent spelling (alphabetically first on ties)
+    pub dominant: String,
+    pub total: u64,
+    // Every spelling with its count, most frequent first
+    pub variants: Vec<(String, u64)>,
+}
+
+// A word whose count differs between two reports, from
+// `CountReport::changes`; a count of 0 means the word is absent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WordChange {
+    pub word: String,
+    pub old: u64,
+    pub new: u64,
+}
+
+impl WordChange {
+    // Signed change from `old` to `new`
+    pub fn delta(&self) -> i128 {
+        i128::from(self.new) - i128::from(self.old)
+    }
+}
+
+// A report entry with its share of all counted tokens, from
+// `CountReport::shares`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordShare {
+    pub word: String,
+    pub count: u64,
+    // This word's share of the total, in percent
+    pub percent: f64,
+    // Share of this word and every one before it in report order, in
+    // percent: "the top 20 words are 60% of tokens"
+    pub cumulative: f64,
+}
+
+// Running shares over counts in result order
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Shares {
+    total: u64,
+    cumulative: u64,
+}
+
+impl Shares {
+    pub(crate) fn new(total: u64) -> Self {
+        Self {
+            total,
+            cumulative: 0,
+        }
+    }
+
+    // Percent and cumulative percent of the next count
+    pub(crate) fn add(&mut self, count: u64) -> (f64, f64) {
+        self.cumulative = self.cumulative.saturating_add(count);
+        (self.percent(count), self.percent(self.cumulative))
+    }
+
+    fn percent(&self, count: u64) -> f64 {
+        match self.total {
+            0 => 0.0,
+            total => count as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+// Sorted word counts (count descending, then alphabetically) that can be
+// read without cloning keys, plus the files that could not be counted
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CountReport {
+    entries: Vec<(String, u64)>,
+    errors: Vec<FileError>,
+    // Tokens counted by the run, if more than the entries hold (e.g. a run
+    // limited by `top`)
+    total: Option<u64>,
+}
+
+impl CountReport {
+    pub fn from_counts(counts: AHashMap<String, u64>) -> Self {
+        let mut entries: Vec<_> = counts.into_iter().collect();
+        entries.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Self::from(entries)
+    }
+
+    // Attach the per-file failures of the run that produced this report
+    pub fn with_errors(mut self, errors: Vec<FileError>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    // Attach the run's token total, for shares of a report that holds only
+    // part of the vocabulary
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    // Tokens counted: the attached total, or the sum of the entries
+    pub fn total(&self) -> u64 {
+        self.total.unwrap_or_else(|| {
+            self.entries
+                .iter()
+                .fold(0, |total, (_, count)| total.saturating_add(*count))
+        })
+    }
+
+    // Each entry with its percent of `total` and the cumulative percent
+    // through it, in report order
+    pub fn shares(&self) -> Vec<WordShare> {
+        let mut shares = Shares::new(self.total());
+        self.iter()
+            .map(|(word, count)| {
+                let (percent, cumulative) = shares.add(count);
+                WordShare {
+                    word: word.to_string(),
+                    count,
+                    percent,
+                    cumulative,
+                }
+            })
+            .collect()
+    }
+
+    // Files that were skipped because they could not be read
+    pub fn errors(&self) -> &[FileError] {
+        &self.errors
+    }
+
+    // Borrowed `(word, count)` pairs in report order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&str, u64)> + '_ {
+        self.entries
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+    }
+
+    // The `n` most frequent words (fewer if the report is shorter)
+    pub fn top_n(&self, n: usize) -> &[(String, u64)] {
+        &self.entries[..self.entries.len().min(n)]
+    }
+
+    pub fn as_slice(&self) -> &[(String, u64)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Check the invariants of a run's results (see `validate_with`) in
+    // report order: count descending, then alphabetically
+    pub fn validate(&self, tokens: Option<u64>) -> Result<()> {
+        self.validate_with(tokens, |a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)))
+    }
+
+    // Check that no word is empty or listed twice, that entries are sorted
+    // by `order`, and, given the number of tokens a run processed, that the
+    // counts add up to it (pass None for reports holding only some words)
+    pub fn validate_with<F>(&self, tokens: Option<u64>, order: F) -> Result<()>
+    where
+        F: Fn(&(String, u64), &(String, u64)) -> std::cmp::Ordering,
+    {
+        let mut words = AHashSet::with_capacity(self.entries.len());
+        for (i, (word, _)) in self.entries.iter().enumerate() {
+            if word.is_empty() {
+                bail!("entry {} has an empty word", i + 1);
+            }
+            if !words.insert(word.as_str()) {
+                bail!("'{}' is listed more than once", word);
+            }
+        }
+        for pair in self.entries.windows(2) {
+            if order(&pair[0], &pair[1]) == std::cmp::Ordering::Greater {
+                bail!("'{}' is out of order after '{}'", pair[1].0, pair[0].0);
+            }
+        }
+        if let Some(tokens) = tokens {
+            let sum = self
+                .entries
+                .iter()
+                .fold(0u64, |total, (_, count)| total.saturating_add(*count));
+            if sum != tokens {
+                bail!("counts sum to {} but {} tokens were counted", sum, tokens);
+            }
+        }
+        Ok(())
+    }
+
+    // Read a results file written with `--output`. Headerless files from
+    // before format versioning (v0) share the v1 table layout; files from a
+    // newer format are refused with `UnsupportedVersion`.
+    pub fn load(path: &Path) -> Result<CountReport> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read results file {}", path.display()))?;
+        let first = text.lines().next().unwrap_or_default();
+        let version = artifact::check_header(path, first, RESULTS, RESULTS_VERSION)?;
+
+        let body = text.lines().skip(usize::from(version > 0));
+        let mut entries = Vec::new();
+        for (number, line) in body.enumerate() {
+            // Sampled locations have no count column; share columns may
+            // follow it
+            let Some((word, rest)) = line.split_once(" | ") else {
+                continue;
+            };
+            let count = rest
+                .split(" | ")
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .parse()
+                .with_context(|| format!("{}: bad count on line {}", path.display(), number + 1))?;
+            entries.push((word.trim().to_string(), count));
+        }
+        Ok(CountReport::from(entries))
+    }
+
+    pub fn into_vec(self) -> Vec<(String, u64)> {
+        self.entries
+    }
+
+    fn to_map(&self) -> AHashMap<String, u64> {
+        self.entries.iter().cloned().collect()
+    }
+
+    // Group words into named frequency classes. `thresholds` pairs a name
+    // with a minimum count; each word goes into the first bucket it reaches,
+    // so list them from highest to lowest. Words below every threshold are
+    // left out.
+    pub fn bucketize(&self, thresholds: &[(&str, u64)]) -> Vec<FrequencyBucket> {
+        let total: u64 = self.entries.iter().map(|(_, count)| count).sum();
+        let mut buckets: Vec<FrequencyBucket> = thresholds
+            .iter()
+            .map(|&(name, min_count)| FrequencyBucket {
+                name: name.to_string(),
+                min_count,
+                words: 0,
+                occurrences: 0,
+                share: 0.0,
+                examples: Vec::new(),
+            })
+            .collect();
+
+        for (word, count) in self.iter() {
+            let Some(bucket) = buckets.iter_mut().find(|b| count >= b.min_count) else {
+                continue;
+            };
+            bucket.words += 1;
+            bucket.occurrences += count;
+            if bucket.examples.len() < BUCKET_EXAMPLES {
+                bucket.examples.push(word.to_string());
+            }
+        }
+
+        if total > 0 {
+            for bucket in &mut buckets {
+                bucket.share = bucket.occurrences as f64 / total as f64;
+            }
+        }
+        buckets
+    }
+
+    // Merge words that differ only in case (Unicode lowercase), keeping the
+    // dominant spelling and every variant. Groups are sorted by total
+    // descending, then by dominant spelling.
+    pub fn fold_case(&self) -> Vec<CaseGroup> {
+        let mut index: AHashMap<String, usize> = AHashMap::new();
+        let mut groups: Vec<CaseGroup> = Vec::new();
+        // Report order visits each group's dominant spelling first
+        for (word, count) in self.iter() {
+            let key = word.to_lowercase();
+            match index.get(&key) {
+                Some(&i) => {
+                    let group = &mut groups[i];
+                    group.total = group.total.saturating_add(count);
+                    group.variants.push((word.to_string(), count));
+                }
+                None => {
+                    index.insert(key, groups.len());
+                    groups.push(CaseGroup {
+                        dominant: word.to_string(),
+                        total: count,
+                        variants: vec![(word.to_string(), count)],
+                    });
+                }
+            }
+        }
+        groups.sort_by(|a, b| {
+            b.total
+                .cmp(&a.total)
+                .then_with(|| a.dominant.cmp(&b.dominant))
+        });
+        groups
+    }
+
+    // Sum of both reports' counts (saturating)
+    pub fn add(&self, other: &CountReport) -> CountReport {
+        let mut counts = self.to_map();
+        for (word, count) in other.iter() {
+            let total = counts.entry(word.to_string()).or_insert(0);
+            *total = total.saturating_add(count);
+        }
+        Self::from_counts(counts)
+    }
+
+    // This report's counts minus `other`'s, clamped at zero; words that reach
+    // zero are dropped
+    pub fn subtract(&self, other: &CountReport) -> CountReport {
+        let mut counts = self.to_map();
+        for (word, count) in other.iter() {
+            if let Some(total) = counts.get_mut(word) {
+                *total = total.saturating_sub(count);
+            }
+        }
+        counts.retain(|_, count| *count > 0);
+        Self::from_counts(counts)
+    }
+
+    // Words whose count differs in `newer`, including words only one of the
+    // reports has; largest absolute change first, then alphabetically
+    pub fn changes(&self, newer: &CountReport) -> Vec<WordChange> {
+        let mut theirs = newer.to_map();
+        let mut changes: Vec<WordChange> = self
+            .iter()
+            .filter_map(|(word, old)| {
+                let new = theirs.remove(word).unwrap_or(0);
+                (new != old).then(|| WordChange {
+                    word: word.to_string(),
+                    old,
+                    new,
+                })
+            })
+            .collect();
+        changes.extend(
+            theirs
+                .into_iter()
+                .map(|(word, new)| WordChange { word, old: 0, new }),
+        );
+        changes.sort_unstable_by(|a, b| {
+            b.delta()
+                .abs()
+                .cmp(&a.delta().abs())
+                .then_with(|| a.word.cmp(&b.word))
+        });
+        changes
+    }
+
+    // Words present in both reports, with the smaller of the two counts
+    pub fn intersect(&self, other: &CountReport) -> CountReport {
+        let theirs = other.to_map();
+        let counts = self
+            .iter()
+            .filter_map(|(word, count)| {
+                theirs
+                    .get(word)
+                    .map(|&other| (word.to_string(), count.min(other)))
+            })
+            .collect();
+        Self::from_counts(counts)
+    }
+
+    // Every count multiplied by `factor` and rounded; words that round to
+    // zero are dropped
+    pub fn scale(&self, factor: f64) -> CountReport {
+        let counts = self
+            .iter()
+            .filter_map(|(word, count)| {
+                let scaled = (count as f64 * factor).round();
+                (scaled >= 1.0).then(|| (word.to_string(), scaled as u64))
+            })
+            .collect();
+        Self::from_counts(counts)
+    }
+}
+
+// Wraps results that are already sorted, e.g. from `count_directory`
+impl From<Vec<(String, u64)>> for CountReport {
+    fn from(entries: Vec<(String, u64)>) -> Self {
+        Self {
+            entries,
+            errors: Vec::new(),
+            total: None,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a CountReport {
+    type Item = (&'a str, u64);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'a, (String, u64)>,
+        fn(&'a (String, u64)) -> (&'a str, u64),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iter_and_top_n_borrow_sorted_entries() {
+        let counts: AHashMap<String, u64> = [("b", 2), ("a", 2), ("c", 5)]
+            .into_iter()
+            .map(|(w, c)| (w.to_string(), c))
+            .collect();
+        let report = CountReport::from_counts(counts);
+
+        let words: Vec<&str> = report.iter().map(|(word, _)| word).collect();
+        assert_eq!(words, ["c", "a", "b"]);
+        assert_eq!(report.top_n(1), &[("c".to_string(), 5)]);
+        assert_eq!(report.top_n(10).len(), 3);
+    }
+
+    fn report(pairs: &[(&str, u64)]) -> CountReport {
+        CountReport::from_counts(pairs.iter().map(|(w, c)| (w.to_string(), *c)).collect())
+    }
+
+    #[test]
+    fn test_changes_sorted_by_size() {
+        let old = report(&[("int", 10), ("main", 2), ("tmp", 1)]);
+        let new = report(&[("int", 4), ("main", 2), ("assert", 7)]);
+
+        let changes = old.changes(&new);
+        let deltas: Vec<(&str, i128)> = changes
+            .iter()
+            .map(|change| (change.word.as_str(), change.delta()))
+            .collect();
+        assert_eq!(deltas, [("assert", 7), ("int", -6), ("tmp", -1)]);
+        assert!(old.changes(&old).is_empty());
+    }
+
+    #[test]
+    fn test_set_algebra() {
+        let src = report(&[("int", 10), ("main", 2), ("tmp", 1)]);
+        let tests = report(&[("int", 4), ("assert", 7), ("tmp", 3)]);
+
+        assert_eq!(
+            src.add(&tests).as_slice(),
+            report(&[("int", 14), ("main", 2), ("tmp", 4), ("assert", 7)]).as_slice()
+        );
+        assert_eq!(src.subtract(&tests), report(&[("int", 6), ("main", 2)]));
+        assert_eq!(src.intersect(&tests), report(&[("int", 4), ("tmp", 1)]));
+        assert_eq!(
+            src.scale(0.5),
+            report(&[("int", 5), ("main", 1), ("tmp", 1)])
+        );
+        assert_eq!(src.scale(0.1), report(&[("int", 1)]));
+    }
+
+    #[test]
+    fn test_bucketize() {
+        let counts = report(&[("int", 1200), ("if", 150), ("foo", 3), ("bar", 1)]);
+        let buckets = counts.bucketize(&DEFAULT_BUCKETS);
+
+        let words: Vec<usize> = buckets.iter().map(|b| b.words).collect();
+        assert_eq!(words, [1, 1, 2]);
+        assert_eq!(buckets[2].occurrences, 4);
+        assert_eq!(buckets[2].examples, ["foo", "bar"]);
+        assert!((buckets[0].share - 1200.0 / 1354.0).abs() < 1e-9);
+
+        let partial = counts.bucketize(&[("hot", 100)]);
+        assert_eq!(partial[0].words, 2);
+    }
+
+    #[test]
+    fn test_shares_accumulate_in_report_order() {
+        let shares = report(&[("int", 6), ("x", 3), ("y", 1)]).shares();
+        let columns: Vec<(&str, f64, f64)> = shares
+            .iter()
+            .map(|share| (share.word.as_str(), share.percent, share.cumulative))
+            .collect();
+        assert_eq!(
+            columns,
+            [("int", 60.0, 60.0), ("x", 30.0, 90.0), ("y", 10.0, 100.0)]
+        );
+
+        // A truncated report keeps the run's denominator
+        let top = CountReport::from(vec![("int".to_string(), 6)]).with_total(12);
+        assert_eq!(top.shares()[0].percent, 50.0);
+        assert!(CountReport::default().shares().is_empty());
+    }
+
+    #[test]
+    fn test_load_versioned_and_legacy_results() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let table = "     int |        3\n       x |        1\n";
+
+        let current = dir.path().join("v1.txt");
+        let header = artifact::header(RESULTS, RESULTS_VERSION);
+        std::fs::write(&current, format!("{}\n{}", header, table))?;
+        let legacy = dir.path().join("v0.txt");
+        std::fs::write(&legacy, table)?;
+        let shares = dir.path().join("shares.txt");
+        let table_with_shares = "     int |        3 |  75.00% |  75.00%\n";
+        std::fs::write(&shares, format!("{}\n{}", header, table_with_shares))?;
+
+        let expected = report(&[("int", 3), ("x", 1)]);
+        assert_eq!(CountReport::load(&current)?, expected);
+        assert_eq!(CountReport::load(&legacy)?, expected);
+        assert_eq!(CountReport::load(&shares)?, report(&[("int", 3)]));
+
+        let future = dir.path().join("v2.txt");
+        std::fs::write(
+            &future,
+            format!("{}\n{}", artifact::header(RESULTS, 2), table),
+        )?;
+        assert!(CountReport::load(&future).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fold_case_keeps_dominant_spelling() {
+        let groups =
+            report(&[("foo", 5), ("Foo", 2), ("bar", 4), ("FOO", 1), ("x", 1)]).fold_case();
+
+        assert_eq!(groups[0].dominant, "foo");
+        assert_eq!(groups[0].total, 8);
+        assert_eq!(
+            groups[0].variants,
+            vec![("foo".into(), 5), ("Foo".into(), 2), ("FOO".into(), 1)]
+        );
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn test_validate_checks_invariants() {
+        let valid = report(&[("int", 3), ("x", 1), ("y", 1)]);
+        assert!(valid.validate(Some(5)).is_ok());
+        assert!(valid.validate(None).is_ok());
+        assert!(valid.validate(Some(6)).is_err());
+
+        let entries = |pairs: &[(&str, u64)]| {
+            CountReport::from(
+                pairs
+                    .iter()
+                    .map(|(w, c)| (w.to_string(), *c))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        assert!(entries(&[("x", 1), ("int", 3)]).validate(None).is_err());
+        assert!(entries(&[("y", 1), ("x", 1)]).validate(None).is_err());
+        assert!(entries(&[("x", 2), ("x", 1)]).validate(None).is_err());
+        assert!(entries(&[("", 1)]).validate(None).is_err());
+        // Any comparator, e.g. alphabetical results
+        assert!(
+            entries(&[("a", 1), ("b", 9)])
+                .validate_with(None, |a, b| a.0.cmp(&b.0))
+                .is_ok()
+        );
+    }
+}
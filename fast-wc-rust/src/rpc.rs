@@ -0,0 +1,140 @@
+use crate::FastWordCounter;
+use crate::snapshot::SnapshotStore;
+use anyhow::Result;
+use serde_json::{Value, json};
+use std::io::{BufRead, Write};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+// Serve newline-delimited JSON-RPC requests against a warm index until EOF
+// or an `exit` request. Supported methods:
+//   query {word}      -> {word, count}
+//   top {n}           -> [{word, count}, ...]
+//   where {word}      -> {word, seen, locations: [{path, line, column}]}
+//   version           -> {version}
+//   exit              -> null, then stop
+pub fn serve<R: BufRead, W: Write>(
+    counter: &FastWordCounter,
+    store: &SnapshotStore,
+    reader: R,
+    mut writer: W,
+) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, exit) = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => {
+                let exit = request["method"] == "exit";
+                (handle_request(counter, store, &request), exit)
+            }
+            Err(e) => (
+                error_response(Value::Null, PARSE_ERROR, &e.to_string()),
+                false,
+            ),
+        };
+
+        // Notifications (no id) get no response
+        if !response["id"].is_null() || response.get("error").is_some() {
+            writeln!(writer, "{}", response)?;
+            writer.flush()?;
+        }
+        if exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Answer a single request object
+pub fn handle_request(counter: &FastWordCounter, store: &SnapshotStore, request: &Value) -> Value {
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let params = &request["params"];
+    let snapshot = store.current();
+
+    let result = match request["method"].as_str().unwrap_or_default() {
+        "query" => match params["word"].as_str() {
+            Some(word) => json!({ "word": word, "count": snapshot.get(word).unwrap_or(0) }),
+            None => return error_response(id, INVALID_PARAMS, "missing string param 'word'"),
+        },
+        "top" => {
+            let n = params["n"].as_u64().unwrap_or(10) as usize;
+            let top: Vec<Value> = snapshot
+                .top_n(n)
+                .into_iter()
+                .map(|(word, count)| json!({ "word": word, "count": count }))
+                .collect();
+            Value::from(top)
+        }
+        "where" => match params["word"].as_str() {
+            Some(word) => {
+                let reservoir = counter.samples(word).unwrap_or_default();
+                let locations: Vec<Value> = reservoir
+                    .samples
+                    .iter()
+                    .map(|o| {
+                        json!({
+                            "path": counter.display_path(&o.path),
+                            "line": o.line,
+                            "column": o.column,
+                        })
+                    })
+                    .collect();
+                json!({ "word": word, "seen": reservoir.seen, "locations": locations })
+            }
+            None => return error_response(id, INVALID_PARAMS, "missing string param 'word'"),
+        },
+        "version" => json!({ "version": snapshot.version }),
+        "exit" => Value::Null,
+        other => {
+            return error_response(id, METHOD_NOT_FOUND, &format!("unknown method '{}'", other));
+        }
+    };
+
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use ahash::AHashMap;
+
+    #[test]
+    fn test_serve_answers_queries() {
+        let counter = FastWordCounter::new(Config::default());
+        let store = SnapshotStore::from_counts(AHashMap::from_iter([
+            ("foo".to_string(), 3),
+            ("bar".to_string(), 1),
+        ]));
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"method":"query","params":{"word":"foo"}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":2,"method":"top","params":{"n":1}}"#,
+            "\n",
+            r#"{"jsonrpc":"2.0","id":3,"method":"nope"}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+
+        serve(&counter, &store, input.as_bytes(), &mut output).unwrap();
+
+        let responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(responses[0]["result"]["count"], 3);
+        assert_eq!(responses[1]["result"][0]["word"], "foo");
+        assert_eq!(responses[2]["error"]["code"], METHOD_NOT_FOUND);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::TokenTable;
+use ahash::AHashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// Where a token occurred (1-based line and column)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub path: Arc<PathBuf>,
+    pub line: u64,
+    pub column: u64,
+}
+
+// Uniform random sample of up to `k` occurrences out of `seen`
+#[derive(Debug, Clone, Default)]
+pub struct Reservoir {
+    pub seen: u64,
+    pub samples: Vec<Occurrence>,
+}
+
+impl Reservoir {
+    // Algorithm R: the i-th occurrence replaces a random slot with
+    // probability k / i
+    fn offer(&mut self, occurrence: Occurrence, k: usize, rng: &mut SplitMix64) {
+        self.seen += 1;
+        if self.samples.len() < k {
+            self.samples.push(occurrence);
+        } else {
+            let slot = rng.below(self.seen) as usize;
+            if slot < k {
+                self.samples[slot] = occurrence;
+            }
+        }
+    }
+
+    // Combine two reservoirs, drawing from each side in proportion to the
+    // number of occurrences it still represents
+    fn merge(mut self, mut other: Reservoir, k: usize, rng: &mut SplitMix64) -> Reservoir {
+        let seen = self.seen + other.seen;
+        if self.samples.len() + other.samples.len() <= k {
+            self.samples.append(&mut other.samples);
+            self.seen = seen;
+            return self;
+        }
+
+        let (mut left_weight, mut right_weight) = (self.seen, other.seen);
+        let left_per = self.seen / self.samples.len().max(1) as u64;
+        let right_per = other.seen / other.samples.len().max(1) as u64;
+        let mut merged = Vec::with_capacity(k);
+
+        while merged.len() < k {
+            let take_left = if self.samples.is_empty() {
+                false
+            } else if other.samples.is_empty() {
+                true
+            } else {
+                rng.below(left_weight + right_weight) < left_weight
+            };
+
+            if take_left {
+                let i = rng.below(self.samples.len() as u64) as usize;
+                merged.push(self.samples.swap_remove(i));
+                left_weight = left_weight.saturating_sub(left_per.max(1));
+            } else {
+                let i = rng.below(other.samples.len() as u64) as usize;
+                merged.push(other.samples.swap_remove(i));
+                right_weight = right_weight.saturating_sub(right_per.max(1));
+            }
+        }
+
+        Reservoir {
+            seen,
+            samples: merged,
+        }
+    }
+}
+
+// Small deterministic PRNG; sampling does not need cryptographic quality
+#[derive(Debug, Clone)]
+pub(crate) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform-ish value in 0..bound (bound must be non-zero)
+    pub(crate) fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+// Sample up to `k` occurrence locations per word in `data`
+pub(crate) fn sample_file(
+    path: &Path,
+    data: &[u8],
+    k: usize,
+    token_table: &TokenTable,
+) -> AHashMap<String, Reservoir> {
+    let path = Arc::new(path.to_path_buf());
+    let mut rng = SplitMix64::new(ahash::RandomState::with_seeds(1, 2, 3, 4).hash_one(&*path));
+    let mut reservoirs: AHashMap<String, Reservoir> = AHashMap::new();
+
+    let (mut line, mut line_start) = (1u64, 0usize);
+    let mut word_start = None;
+
+    for (i, &byte) in data
+        .iter()
+        .enumerate()
+        .chain(std::iter::once((data.len(), &b'\n')))
+    {
+        if i < data.len() && token_table[byte as usize] {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = word_start.take()
+            && let Ok(word) = std::str::from_utf8(&data[start..i])
+        {
+            let occurrence = Occurrence {
+                path: Arc::clone(&path),
+                line,
+                column: (start - line_start + 1) as u64,
+            };
+            match reservoirs.get_mut(word) {
+                Some(reservoir) => reservoir.offer(occurrence, k, &mut rng),
+                None => {
+                    let mut reservoir = Reservoir::default();
+                    reservoir.offer(occurrence, k, &mut rng);
+                    reservoirs.insert(word.to_string(), reservoir);
+                }
+            }
+        }
+        if byte == b'\n' {
+            line += 1;
+            line_start = i + 1;
+        }
+    }
+
+    reservoirs
+}
+
+// Merge per-file reservoirs into the running totals
+pub(crate) fn merge_samples(
+    into: &mut AHashMap<String, Reservoir>,
+    from: AHashMap<String, Reservoir>,
+    k: usize,
+) {
+    let mut rng = SplitMix64::new(into.len() as u64 ^ from.len() as u64);
+    for (word, reservoir) in from {
+        match into.remove(&word) {
+            Some(existing) => {
+                into.insert(word, existing.merge(reservoir, k, &mut rng));
+            }
+            None => {
+                into.insert(word, reservoir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TOKEN_CHARS;
+
+    #[test]
+    fn test_reservoir_is_bounded_and_tracks_lines() {
+        let data = b"foo\nfoo bar\nfoo foo foo\n";
+        let samples = sample_file(Path::new("x.c"), data, 2, &TOKEN_CHARS);
+
+        let foo = &samples["foo"];
+        assert_eq!(foo.seen, 5);
+        assert_eq!(foo.samples.len(), 2);
+
+        let bar = &samples["bar"];
+        assert_eq!(bar.samples[0].line, 2);
+        assert_eq!(bar.samples[0].column, 5);
+    }
+
+    #[test]
+    fn test_merge_keeps_k_samples() {
+        let mut into = sample_file(Path::new("a.c"), b"w w w", 2, &TOKEN_CHARS);
+        let from = sample_file(Path::new("b.c"), b"w w", 2, &TOKEN_CHARS);
+
+        merge_samples(&mut into, from, 2);
+
+        assert_eq!(into["w"].seen, 5);
+        assert_eq!(into["w"].samples.len(), 2);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::accumulate::Accumulate;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// How files are ordered when picking a word's first and last file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SeenOrder {
+    // Path order, the order `stable` runs count files in
+    #[default]
+    Traversal,
+    // Modification time, oldest first (path order among equal times);
+    // files without one (stdin, archive members) sort first
+    Mtime,
+}
+
+impl std::str::FromStr for SeenOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "traversal" => Ok(SeenOrder::Traversal),
+            "mtime" => Ok(SeenOrder::Mtime),
+            other => Err(format!(
+                "unknown seen order '{}' (expected traversal or mtime)",
+                other
+            )),
+        }
+    }
+}
+
+impl SeenOrder {
+    pub fn name(self) -> &'static str {
+        match self {
+            SeenOrder::Traversal => "traversal",
+            SeenOrder::Mtime => "mtime",
+        }
+    }
+
+    // Sort key of the file at `path`
+    pub(crate) fn key(self, path: &Path) -> SeenKey {
+        let mtime = match self {
+            SeenOrder::Traversal => None,
+            SeenOrder::Mtime => std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok(),
+        };
+        SeenKey {
+            mtime,
+            path: Arc::from(path),
+        }
+    }
+}
+
+// Position of a file in `SeenOrder`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct SeenKey {
+    mtime: Option<SystemTime>,
+    path: Arc<Path>,
+}
+
+// The first and last file a word appears in, per `Config::seen_order`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeenFiles {
+    pub first: PathBuf,
+    pub last: PathBuf,
+}
+
+// The earliest and latest file a word was seen in so far
+#[derive(Debug, Clone)]
+pub(crate) struct SeenSpan {
+    first: SeenKey,
+    last: SeenKey,
+}
+
+impl SeenSpan {
+    pub(crate) fn new(key: SeenKey) -> Self {
+        Self {
+            first: key.clone(),
+            last: key,
+        }
+    }
+
+    pub(crate) fn files(&self) -> SeenFiles {
+        SeenFiles {
+            first: self.first.path.to_path_buf(),
+            last: self.last.path.to_path_buf(),
+        }
+    }
+}
+
+// Widen the span to include another span's files
+impl Accumulate for SeenSpan {
+    fn merge(&mut self, other: Self) -> bool {
+        if other.first < self.first {
+            self.first = other.first;
+        }
+        if other.last > self.last {
+            self.last = other.last;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_orders_by_mtime_then_path() -> anyhow::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let (old, new) = (dir.path().join("z.c"), dir.path().join("a.c"));
+        std::fs::write(&old, "x")?;
+        std::fs::write(&new, "x")?;
+        let earlier = SystemTime::now() - std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)?
+            .set_modified(earlier)?;
+
+        let mut span = SeenSpan::new(SeenOrder::Mtime.key(&new));
+        span.merge(SeenSpan::new(SeenOrder::Mtime.key(&old)));
+        assert_eq!(
+            span.files(),
+            SeenFiles {
+                first: old.clone(),
+                last: new.clone()
+            }
+        );
+
+        let mut span = SeenSpan::new(SeenOrder::Traversal.key(&old));
+        span.merge(SeenSpan::new(SeenOrder::Traversal.key(&new)));
+        assert_eq!(span.files().first, new);
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use crate::accumulate::WordMeta;
+use crate::{Accumulate, Config, FastWordCounter, cache, input, merge};
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+// Per-file counts reused while the file's size and mtime are unchanged, or
+// (with `Config::content_hash`) while its contents hash the same
+pub(crate) struct CachedFile {
+    pub(crate) len: u64,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) hash: Option<u64>,
+    pub(crate) counts: Arc<AHashMap<String, u64>>,
+    // The file's per-word metadata, when the counter tracks any (see
+    // `FastWordCounter::tracks_word_meta`); not kept in on-disk caches
+    pub(crate) meta: Option<Arc<AHashMap<String, WordMeta>>>,
+}
+
+// Counts several roots (or one root repeatedly) with a thread pool and
+// per-file result cache that live across calls. Unchanged files are not
+// re-read, so a file watcher can recount a tree cheaply. With
+// `content_hash`, files whose mtime changed but whose contents did not
+// (checkouts, `touch`) are read once to hash but not re-tokenized. File
+// stats and samples are only recorded when a file is actually re-tokenized;
+// per-word metadata (`--verbose` first/last files and the like) is cached
+// with the counts.
+pub struct Session {
+    counter: FastWordCounter,
+    pool: rayon::ThreadPool,
+    cache: Mutex<AHashMap<PathBuf, CachedFile>>,
+}
+
+impl Session {
+    pub fn new(config: Config) -> Result<Self> {
+        Self::with_counter(FastWordCounter::new(config))
+    }
+
+    pub(crate) fn with_counter(counter: FastWordCounter) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(counter.config.num_threads)
+            .thread_name(|i| format!("fast-wc-session-{}", i))
+            .build()
+            .context("Failed to build session thread pool")?;
+        Ok(Self {
+            counter,
+            pool,
+            cache: Mutex::new(AHashMap::new()),
+        })
+    }
+
+    // The counter whose stats and diagnostics accumulate across calls
+    pub fn counter(&self) -> &FastWordCounter {
+        &self.counter
+    }
+
+    // Count words under `dir` on the session pool, reusing cached per-file
+    // counts. Cache entries for files under `dir` that no longer match are
+    // dropped.
+    pub fn count(&self, dir: &Path) -> Result<Vec<(String, u64)>> {
+        let files = self.counter.discover_files(dir)?;
+
+        let per_file: Vec<(PathBuf, CachedFile)> = self.pool.install(|| {
+            files
+                .into_par_iter()
+                .filter_map(|file| self.count_file(file))
+                .collect()
+        });
+
+        let merged = self.pool.install(|| {
+            per_file
+                .par_iter()
+                .fold(AHashMap::new, |mut acc, (_, cached)| {
+                    add_counts(&mut acc, &cached.counts, &self.counter);
+                    acc
+                })
+                .reduce(AHashMap::new, |mut acc, local| {
+                    merge::merge_into(&mut acc, local, &self.counter.stats.saturated_counts);
+                    acc
+                })
+        });
+
+        // This call's files alone make up the metadata behind its results
+        if self.counter.tracks_word_meta() {
+            let mut meta = AHashMap::new();
+            for (_, cached) in &per_file {
+                if let Some(file_meta) = &cached.meta {
+                    merge::merge_into(
+                        &mut meta,
+                        (**file_meta).clone(),
+                        &self.counter.stats.saturated_counts,
+                    );
+                }
+            }
+            *self.counter.stats.word_meta.lock().unwrap() = meta;
+        }
+
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|path, _| !path.starts_with(dir));
+        cache.extend(per_file);
+        drop(cache);
+
+        self.counter.finish_run(merged)
+    }
+
+    pub fn cached_files(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    // Add the entries of an on-disk cache written by `save_cache`, returning
+    // how many were loaded. A missing cache, or one written under other
+    // tokenizer settings, loads nothing.
+    pub fn load_cache(&self, path: &Path) -> Result<usize> {
+        let entries = cache::load(path, &self.counter)?;
+        let loaded = entries.len();
+        self.cache.lock().unwrap().extend(entries);
+        Ok(loaded)
+    }
+
+    // Write the per-file cache to `path` for a later run, returning how many
+    // entries were saved
+    pub fn save_cache(&self, path: &Path) -> Result<usize> {
+        cache::save(path, &self.counter, self.cache.lock().unwrap().iter())
+    }
+
+    fn count_file(&self, file: PathBuf) -> Option<(PathBuf, CachedFile)> {
+        let counter = &self.counter;
+        let metadata = std::fs::metadata(&file).ok();
+        let len = metadata.as_ref().map_or(0, |m| m.len());
+        let modified = metadata.and_then(|m| m.modified().ok());
+
+        // Entries loaded from disk have no metadata, so they only spare a
+        // re-read when none is needed
+        let needs_meta = counter.tracks_word_meta();
+        let (hit, cached_hash) = match self.cache.lock().unwrap().get(&file) {
+            Some(cached) => (
+                (modified.is_some()
+                    && cached.len == len
+                    && cached.modified == modified
+                    && (cached.meta.is_some() || !needs_meta))
+                    .then(|| (Arc::clone(&cached.counts), cached.meta.clone())),
+                cached.hash.map(|hash| (hash, Arc::clone(&cached.counts))),
+            ),
+            None => (None, None),
+        };
+        let (counts, hash, meta) = match hit {
+            Some((counts, meta)) => (counts, cached_hash.map(|(hash, _)| hash), meta),
+            None => {
+                let data = match input::load(&file, &counter.config) {
+                    Ok(data) if counter.skips_binary(&file, &data) => return None,
+                    Ok(data) => data,
+                    Err(e) => {
+                        counter.record_error(e);
+                        return None;
+                    }
+                };
+                let hash = counter.content_hash(&data);
+                // Metadata is rebuilt even for a matching hash, as a touched
+                // file's new mtime may order it differently
+                let meta = counter.word_meta(&file, &data).map(Arc::new);
+                match cached_hash {
+                    Some((cached, counts)) if Some(cached) == hash => (counts, hash, meta),
+                    _ => {
+                        let mut counts = AHashMap::new();
+                        let extracted = counter.extract_words(&data, &mut counts);
+                        counter.record_file_stats(&file, &data, &extracted);
+                        counter.record_samples(&file, &data);
+                        (Arc::new(counts), hash, meta)
+                    }
+                }
+            }
+        };
+
+        counter
+            .stats
+            .files_processed
+            .fetch_add(1, Ordering::Relaxed);
+        counter
+            .stats
+            .bytes_processed
+            .fetch_add(len, Ordering::Relaxed);
+        Some((
+            file,
+            CachedFile {
+                len,
+                modified,
+                hash,
+                counts,
+                meta,
+            },
+        ))
+    }
+}
+
+// Add borrowed counts into `acc`, saturating like `merge::merge_into`
+pub(crate) fn add_counts(
+    acc: &mut AHashMap<String, u64>,
+    counts: &AHashMap<String, u64>,
+    counter: &FastWordCounter,
+) {
+    for (word, &count) in counts {
+        let total = match acc.get_mut(word.as_str()) {
+            Some(total) => total,
+            None => acc.entry(word.clone()).or_insert(0),
+        };
+        if total.merge(count) {
+            counter
+                .stats
+                .saturated_counts
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SeenOrder, SortOrder};
+
+    #[test]
+    fn test_session_reuses_cache_across_roots() -> Result<()> {
+        let first = tempfile::TempDir::new()?;
+        std::fs::write(first.path().join("a.c"), "int a;")?;
+        let second = tempfile::TempDir::new()?;
+        std::fs::write(second.path().join("b.c"), "int b; int c;")?;
+
+        let session = Session::new(Config {
+            num_threads: 2,
+            show_progress: false,
+            ..Config::default()
+        })?;
+        assert_eq!(session.count(first.path())?[0], ("a".to_string(), 1));
+        assert_eq!(session.count(second.path())?[0], ("int".to_string(), 2));
+        assert_eq!(session.cached_files(), 2);
+
+        // A rewrite with a new size invalidates the entry
+        std::fs::write(first.path().join("a.c"), "int a; a;")?;
+        assert_eq!(session.count(first.path())?[0], ("a".to_string(), 2));
+
+        std::fs::remove_file(first.path().join("a.c"))?;
+        std::fs::write(first.path().join("c.c"), "x")?;
+        session.count(first.path())?;
+        assert_eq!(session.cached_files(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_validates_touched_files_by_hash() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("a.c");
+        std::fs::write(&path, "int a;")?;
+
+        let session = Session::new(Config {
+            show_progress: false,
+            collect_file_stats: true,
+            content_hash: true,
+            ..Config::default()
+        })?;
+        session.count(dir.path())?;
+
+        // New mtime, same contents: hashed but not re-tokenized
+        let file = std::fs::File::options().write(true).open(&path)?;
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(60))?;
+        assert_eq!(session.count(dir.path())?.len(), 2);
+        let stats = session.counter().file_stats();
+        assert_eq!(stats.len(), 1);
+        assert!(stats[0].hash.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_session_hits_keep_word_meta() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int a;")?;
+        std::fs::write(dir.path().join("b.c"), "int b; a;")?;
+        let cache_path = dir.path().join("counts.cache");
+
+        let config = Config {
+            show_progress: false,
+            seen_order: Some(SeenOrder::Traversal),
+            sort: SortOrder::FileFrequency,
+            ..Config::default()
+        };
+        let session = Session::new(config.clone())?;
+        let first = session.count(dir.path())?;
+        session.save_cache(&cache_path)?;
+        // Every file is a hit the second time, and nothing is counted twice
+        assert_eq!(session.count(dir.path())?, first);
+        let seen = session.counter().seen("a").unwrap();
+        assert_eq!(seen.first, dir.path().join("a.c"));
+        assert_eq!(seen.last, dir.path().join("b.c"));
+        let meta = session.counter().stats.word_meta.lock().unwrap();
+        assert_eq!(meta["int"].files, 2);
+        drop(meta);
+
+        // On-disk entries carry no metadata, so a fresh session re-reads
+        let session = Session::new(config)?;
+        session.load_cache(&cache_path)?;
+        session.count(dir.path())?;
+        assert!(session.counter().seen("b").is_some());
+        Ok(())
+    }
+}
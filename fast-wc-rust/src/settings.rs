@@ -0,0 +1,733 @@
+use crate::hooks::Hooks;
+use crate::{
+    ChunkBoundaryPolicy, Config, ErrorPolicy, IoMode, LogFormat, MergeStrategy, OutputFormat,
+    PathEncoding, PathWords, SeenOrder, SortOrder, TieBreak, TokenMode, TokenTable,
+    parse_token_chars,
+};
+use ahash::AHashMap;
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Prefix for configuration environment variables, e.g. FAST_WC_THREADS
+pub const ENV_PREFIX: &str = "FAST_WC_";
+// Config file picked up from the working directory when none is given
+pub const DEFAULT_CONFIG_FILE: &str = "fast-wc.toml";
+
+// Keys accepted in the config file, as FAST_WC_<KEY> and via the CLI layer
+pub const KEYS: [&str; 49] = [
+    "threads",
+    "mmap",
+    "parallel_merge",
+    "progress",
+    "print_results",
+    "shares",
+    "format",
+    "log_format",
+    "max_token_len",
+    "max_unique_per_file",
+    "max_unique",
+    "sample_occurrences",
+    "token_chars",
+    "flush_every_files",
+    "flush_every_entries",
+    "auto_tune",
+    "extensions",
+    "fail_on_error",
+    "include_globs",
+    "exclude_globs",
+    "token_mode",
+    "spill_threshold",
+    "code_aware",
+    "prefault",
+    "exclude_keywords",
+    "stopwords",
+    "io_mode",
+    "small_file_bytes",
+    "content_hash",
+    "decompress",
+    "archives",
+    "path_words",
+    "skip_binary",
+    "chunk_bytes",
+    "chunk_boundary_policy",
+    "merge_strategy",
+    "aliases",
+    "split_identifiers",
+    "sort",
+    "tie_break",
+    "stable",
+    "seen_order",
+    "min_count",
+    "max_count",
+    "top",
+    "error_policy",
+    "follow_symlinks",
+    "max_depth",
+    "path_encoding",
+];
+
+// Keys whose values are file paths. Relative paths in a config file are
+// resolved against the file's directory rather than the working directory.
+const PATH_KEYS: [&str; 2] = ["stopwords", "aliases"];
+
+// Where a setting's value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Profile(PathBuf, String),
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "file {}", path.display()),
+            Source::Profile(path, name) => write!(f, "profile {} in {}", name, path.display()),
+            Source::Env(var) => write!(f, "env {}", var),
+            Source::Cli => write!(f, "cli"),
+        }
+    }
+}
+
+// Layered settings: later layers override earlier ones. The intended order
+// is config file, then environment, then command-line flags.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    values: BTreeMap<&'static str, (String, Source)>,
+    hooks: Hooks,
+    // From `[expansions]` tables; a profile's entries are added to (and
+    // override) the top-level ones
+    expansions: AHashMap<String, String>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Set a raw value for `key`, overriding any earlier layer
+    pub fn set(&mut self, key: &str, raw: impl Into<String>, source: Source) -> Result<()> {
+        let Some(key) = KEYS.iter().find(|k| **k == key) else {
+            bail!("unknown setting '{}' (from {})", key, source);
+        };
+        let raw = raw.into();
+        // `fail_on_error` is shorthand for `error_policy`, resolved into it
+        // here so whichever of the two a later layer sets wins
+        if *key == "fail_on_error" {
+            let abort = parse_bool(&raw).with_context(|| {
+                format!("invalid value '{}' for {} (from {})", raw, key, source)
+            })?;
+            let policy = self
+                .values
+                .get("error_policy")
+                .and_then(|(policy, _)| policy.parse::<ErrorPolicy>().ok());
+            if abort {
+                self.values
+                    .insert("error_policy", ("abort".into(), source.clone()));
+            } else if policy == Some(ErrorPolicy::Abort) {
+                self.values
+                    .insert("error_policy", ("skip".into(), source.clone()));
+            }
+        }
+        self.values.insert(key, (raw, source));
+        Ok(())
+    }
+
+    // Load top-level keys from a TOML config file, then overlay the
+    // `[profile.<name>]` table if a profile is selected
+    pub fn load_file(&mut self, path: &Path, profile: Option<&str>) -> Result<()> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        let table: toml::Table = text
+            .parse()
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+
+        self.load_table(&table, &Source::File(path.to_path_buf()))?;
+        self.load_hooks(&table, path)?;
+        self.load_expansions(&table, path)?;
+
+        if let Some(name) = profile {
+            let profile_table = table
+                .get("profile")
+                .and_then(|p| p.get(name))
+                .and_then(|p| p.as_table())
+                .ok_or_else(|| {
+                    let available = profile_names(&table).join(", ");
+                    anyhow::anyhow!(
+                        "profile '{}' not found in {} (available: {})",
+                        name,
+                        path.display(),
+                        if available.is_empty() {
+                            "none"
+                        } else {
+                            &available
+                        }
+                    )
+                })?;
+            self.load_table(
+                profile_table,
+                &Source::Profile(path.to_path_buf(), name.to_string()),
+            )?;
+            self.load_hooks(profile_table, path)?;
+            self.load_expansions(profile_table, path)?;
+        }
+        Ok(())
+    }
+
+    // A `[hooks]` table replaces hooks from any earlier layer
+    fn load_hooks(&mut self, table: &toml::Table, path: &Path) -> Result<()> {
+        if let Some(hooks) = table.get("hooks") {
+            let hooks = hooks
+                .as_table()
+                .ok_or_else(|| anyhow::anyhow!("'hooks' in {} must be a table", path.display()))?;
+            self.hooks = Hooks::from_table(hooks)
+                .with_context(|| format!("Invalid hooks in {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn load_expansions(&mut self, table: &toml::Table, path: &Path) -> Result<()> {
+        if let Some(expansions) = table.get("expansions") {
+            let expansions = expansions.as_table().ok_or_else(|| {
+                anyhow::anyhow!("'expansions' in {} must be a table", path.display())
+            })?;
+            self.expansions.extend(
+                crate::aliases::parse_expansions(expansions)
+                    .with_context(|| format!("Invalid expansions in {}", path.display()))?,
+            );
+        }
+        Ok(())
+    }
+
+    // Pre/post hook commands from the config file
+    pub fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    pub(crate) fn load_table(&mut self, table: &toml::Table, source: &Source) -> Result<()> {
+        for (key, value) in table {
+            let raw = match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                toml::Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        toml::Value::String(s) => Ok(s.clone()),
+                        other => bail!(
+                            "unsupported list item for '{}' in {}: {}",
+                            key,
+                            source,
+                            other
+                        ),
+                    })
+                    .collect::<Result<Vec<_>>>()?
+                    .join(","),
+                toml::Value::Table(_) => continue,
+                other => bail!("unsupported value for '{}' in {}: {}", key, source, other),
+            };
+            let raw = match source {
+                Source::File(path) | Source::Profile(path, _)
+                    if PATH_KEYS.contains(&key.as_str()) =>
+                {
+                    relative_to_file(path, raw)
+                }
+                _ => raw,
+            };
+            self.set(key, raw, source.clone())?;
+        }
+        Ok(())
+    }
+
+    // Load FAST_WC_* variables; unknown FAST_WC_ names are rejected so typos
+    // don't silently do nothing
+    pub fn load_env<I>(&mut self, vars: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (name, value) in vars {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if key == "CONFIG" || key == "PROFILE" {
+                continue;
+            }
+            self.set(&key.to_ascii_lowercase(), value, Source::Env(name.clone()))?;
+        }
+        Ok(())
+    }
+
+    // Apply every set value onto `config`
+    pub fn apply(&self, config: &mut Config) -> Result<()> {
+        for (key, (raw, source)) in &self.values {
+            apply_one(config, key, raw).with_context(|| {
+                format!("invalid value '{}' for {} (from {})", raw, key, source)
+            })?;
+        }
+        if !self.expansions.is_empty() {
+            config.expansions = self.expansions.clone();
+        }
+        Ok(())
+    }
+
+    pub fn source(&self, key: &str) -> Source {
+        self.values
+            .get(key)
+            .map(|(_, source)| source.clone())
+            .unwrap_or(Source::Default)
+    }
+
+    // Render the effective configuration with the origin of each value
+    pub fn describe(&self, config: &Config) -> String {
+        let mut out = String::new();
+        for key in KEYS {
+            out.push_str(&format!(
+                "{:<20} = {:<24} # {}\n",
+                key,
+                render(config, key),
+                self.source(key)
+            ));
+        }
+        out
+    }
+}
+
+// `raw`, a path from the config file at `file`, as seen from the working
+// directory
+fn relative_to_file(file: &Path, raw: String) -> String {
+    match file.parent() {
+        Some(dir) if !raw.is_empty() && Path::new(&raw).is_relative() => {
+            dir.join(raw).to_string_lossy().into_owned()
+        }
+        _ => raw,
+    }
+}
+
+// Names of the `[profile.*]` tables in a config file
+fn profile_names(table: &toml::Table) -> Vec<String> {
+    table
+        .get("profile")
+        .and_then(|p| p.as_table())
+        .map(|p| p.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn parse_bool(raw: &str) -> Result<bool> {
+    match raw.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => bail!("expected a boolean"),
+    }
+}
+
+fn parse_limit<T: std::str::FromStr>(raw: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    if raw.is_empty() || raw.eq_ignore_ascii_case("none") {
+        Ok(None)
+    } else {
+        Ok(Some(raw.parse()?))
+    }
+}
+
+// Comma-separated list, e.g. "c,h,rs". Commas inside `{}` or `[]` belong
+// to the item, so glob alternations like "src/{a,b}/*.c" survive.
+fn parse_list(raw: &str) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (i, c) in raw.char_indices() {
+        match c {
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            ',' if depth <= 0 => {
+                items.push(&raw[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&raw[start..]);
+
+    let items: Vec<String> = items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect();
+    if items.is_empty() {
+        bail!("expected at least one item");
+    }
+    Ok(items)
+}
+
+fn apply_one(config: &mut Config, key: &str, raw: &str) -> Result<()> {
+    match key {
+        "threads" => config.num_threads = raw.parse()?,
+        "mmap" => config.use_mmap = parse_bool(raw)?,
+        "parallel_merge" => config.parallel_merge = parse_bool(raw)?,
+        "progress" => config.show_progress = parse_bool(raw)?,
+        "print_results" => config.print_results = parse_bool(raw)?,
+        "shares" => config.shares = parse_bool(raw)?,
+        // Layout of the --output file; "auto" picks by its extension
+        "format" => {
+            config.output_format = if raw.is_empty() || raw.eq_ignore_ascii_case("auto") {
+                None
+            } else {
+                Some(raw.parse::<OutputFormat>().map_err(anyhow::Error::msg)?)
+            }
+        }
+        "log_format" => config.log_format = raw.parse::<LogFormat>().map_err(anyhow::Error::msg)?,
+        "max_token_len" => config.max_token_len = parse_limit(raw)?,
+        "max_unique_per_file" => config.max_unique_per_file = parse_limit(raw)?,
+        "max_unique" => config.max_unique = parse_limit(raw)?,
+        "sample_occurrences" => config.sample_occurrences = parse_limit(raw)?,
+        "token_chars" => config.token_chars = Some(parse_token_chars(raw)?),
+        "flush_every_files" => config.flush_every_files = parse_limit(raw)?,
+        "flush_every_entries" => config.flush_every_entries = parse_limit(raw)?,
+        "auto_tune" => config.auto_tune = parse_bool(raw)?,
+        "extensions" => config.extensions = parse_list(raw)?,
+        // Shorthand for error_policy = abort (false only undoes that),
+        // already resolved into error_policy by `Settings::set`
+        "fail_on_error" => {
+            parse_bool(raw)?;
+        }
+        "include_globs" => config.include_globs = parse_list(raw)?,
+        "exclude_globs" => config.exclude_globs = parse_list(raw)?,
+        "token_mode" => config.token_mode = raw.parse::<TokenMode>().map_err(anyhow::Error::msg)?,
+        "spill_threshold" => config.spill_threshold = parse_limit(raw)?,
+        "code_aware" => config.code_aware = parse_bool(raw)?,
+        "prefault" => config.prefault = parse_bool(raw)?,
+        "exclude_keywords" => config.exclude_keywords = parse_bool(raw)?,
+        // Path to a stop-word file, one word per line
+        "stopwords" => config.stopwords = crate::keywords::load_stopwords(Path::new(raw))?,
+        "io_mode" => config.io_mode = raw.parse::<IoMode>().map_err(anyhow::Error::msg)?,
+        "path_encoding" => {
+            config.path_encoding = raw.parse::<PathEncoding>().map_err(anyhow::Error::msg)?
+        }
+        "small_file_bytes" => config.small_file_bytes = raw.parse()?,
+        "content_hash" => config.content_hash = parse_bool(raw)?,
+        "decompress" => config.decompress = parse_bool(raw)?,
+        "archives" => config.archives = parse_bool(raw)?,
+        "path_words" => config.path_words = raw.parse::<PathWords>().map_err(anyhow::Error::msg)?,
+        "skip_binary" => config.skip_binary = parse_bool(raw)?,
+        "chunk_bytes" => config.chunk_bytes = parse_limit(raw)?,
+        "chunk_boundary_policy" => {
+            config.chunk_boundary_policy = raw
+                .parse::<ChunkBoundaryPolicy>()
+                .map_err(anyhow::Error::msg)?
+        }
+        // Path to a TOML alias file
+        "aliases" => config.aliases = crate::aliases::load_aliases(Path::new(raw))?,
+        "split_identifiers" => config.split_identifiers = parse_bool(raw)?,
+        "merge_strategy" => {
+            config.merge_strategy = raw.parse::<MergeStrategy>().map_err(anyhow::Error::msg)?
+        }
+        "sort" => config.sort = raw.parse::<SortOrder>().map_err(anyhow::Error::msg)?,
+        "tie_break" => config.tie_break = raw.parse::<TieBreak>().map_err(anyhow::Error::msg)?,
+        "stable" => config.stable = parse_bool(raw)?,
+        "seen_order" => {
+            config.seen_order = if raw.is_empty() || raw.eq_ignore_ascii_case("none") {
+                None
+            } else {
+                Some(raw.parse::<SeenOrder>().map_err(anyhow::Error::msg)?)
+            }
+        }
+        "min_count" => config.min_count = parse_limit(raw)?,
+        "max_count" => config.max_count = parse_limit(raw)?,
+        "top" => config.top = parse_limit(raw)?,
+        "follow_symlinks" => config.follow_symlinks = parse_bool(raw)?,
+        "max_depth" => config.max_depth = parse_limit(raw)?,
+        "error_policy" => {
+            config.error_policy = raw.parse::<ErrorPolicy>().map_err(anyhow::Error::msg)?
+        }
+        _ => bail!("unknown setting"),
+    }
+    Ok(())
+}
+
+fn render_limit<T: std::fmt::Display>(limit: Option<T>) -> String {
+    limit.map_or_else(|| "none".to_string(), |n| n.to_string())
+}
+
+fn render(config: &Config, key: &str) -> String {
+    match key {
+        "threads" => config.num_threads.to_string(),
+        "mmap" => config.use_mmap.to_string(),
+        "parallel_merge" => config.parallel_merge.to_string(),
+        "progress" => config.show_progress.to_string(),
+        "print_results" => config.print_results.to_string(),
+        "shares" => config.shares.to_string(),
+        "format" => config.output_format.map_or_else(
+            || "auto".to_string(),
+            |format| format!("{:?}", format).to_ascii_lowercase(),
+        ),
+        "log_format" => format!("{:?}", config.log_format).to_ascii_lowercase(),
+        "max_token_len" => render_limit(config.max_token_len),
+        "max_unique_per_file" => render_limit(config.max_unique_per_file),
+        "max_unique" => render_limit(config.max_unique),
+        "sample_occurrences" => render_limit(config.sample_occurrences),
+        "token_chars" => config
+            .token_chars
+            .as_ref()
+            .map_or_else(|| "a-zA-Z0-9_".to_string(), format_token_chars),
+        "flush_every_files" => render_limit(config.flush_every_files),
+        "flush_every_entries" => render_limit(config.flush_every_entries),
+        "auto_tune" => config.auto_tune.to_string(),
+        "extensions" => config.extensions.join(","),
+        "fail_on_error" => (config.error_policy == ErrorPolicy::Abort).to_string(),
+        "include_globs" => config.include_globs.join(","),
+        "exclude_globs" => config.exclude_globs.join(","),
+        "token_mode" => format!("{:?}", config.token_mode).to_ascii_lowercase(),
+        "spill_threshold" => render_limit(config.spill_threshold),
+        "code_aware" => config.code_aware.to_string(),
+        "prefault" => config.prefault.to_string(),
+        "exclude_keywords" => config.exclude_keywords.to_string(),
+        "stopwords" => format!("{} words", config.stopwords.len()),
+        "io_mode" => format!("{:?}", config.io_mode).to_ascii_lowercase(),
+        "path_encoding" => format!("{:?}", config.path_encoding).to_ascii_lowercase(),
+        "small_file_bytes" => config.small_file_bytes.to_string(),
+        "content_hash" => config.content_hash.to_string(),
+        "decompress" => config.decompress.to_string(),
+        "archives" => config.archives.to_string(),
+        "path_words" => format!("{:?}", config.path_words).to_ascii_lowercase(),
+        "skip_binary" => config.skip_binary.to_string(),
+        "chunk_bytes" => render_limit(config.chunk_bytes),
+        "chunk_boundary_policy" => {
+            format!("{:?}", config.chunk_boundary_policy).to_ascii_lowercase()
+        }
+        "aliases" => format!("{} aliases", config.aliases.len()),
+        "split_identifiers" => format!(
+            "{} ({} expansions)",
+            config.split_identifiers,
+            config.expansions.len()
+        ),
+        "merge_strategy" => match config.merge_strategy {
+            MergeStrategy::PerWorker => "per-worker".to_string(),
+            MergeStrategy::Sharded => "sharded".to_string(),
+            MergeStrategy::Arena => "arena".to_string(),
+        },
+        "sort" => config.sort.name().to_string(),
+        "tie_break" => config.tie_break.name().to_string(),
+        "stable" => config.stable.to_string(),
+        "seen_order" => config
+            .seen_order
+            .map_or_else(|| "none".to_string(), |order| order.name().to_string()),
+        "min_count" => render_limit(config.min_count),
+        "max_count" => render_limit(config.max_count),
+        "top" => render_limit(config.top),
+        "follow_symlinks" => config.follow_symlinks.to_string(),
+        "max_depth" => render_limit(config.max_depth),
+        "error_policy" => format!("{:?}", config.error_policy).to_ascii_lowercase(),
+        _ => String::new(),
+    }
+}
+
+// Render a token table back into range syntax accepted by `parse_token_chars`
+pub fn format_token_chars(table: &TokenTable) -> String {
+    let mut out = String::new();
+    let mut b = 0usize;
+    while b < 256 {
+        if !table[b] {
+            b += 1;
+            continue;
+        }
+        let start = b;
+        while b + 1 < 256 && table[b + 1] {
+            b += 1;
+        }
+        let push = |out: &mut String, c: usize| {
+            if c == b'-' as usize || c == b'\\' as usize {
+                out.push('\\');
+            }
+            out.push(c as u8 as char);
+        };
+        push(&mut out, start);
+        if b > start + 1 {
+            out.push('-');
+        }
+        if b > start {
+            push(&mut out, b);
+        }
+        b += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer_precedence() -> Result<()> {
+        let mut settings = Settings::new();
+        let file: toml::Table =
+            "threads = 2\nmmap = false\nextensions = [\"rs\", \"py\"]\n".parse()?;
+        settings.load_table(&file, &Source::File("fast-wc.toml".into()))?;
+        settings.load_env([
+            ("FAST_WC_THREADS".to_string(), "4".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ])?;
+        settings.set("max_unique", "10", Source::Cli)?;
+
+        let mut config = Config::default();
+        settings.apply(&mut config)?;
+
+        assert_eq!(config.num_threads, 4);
+        assert!(!config.use_mmap);
+        assert_eq!(config.extensions, vec!["rs", "py"]);
+        assert_eq!(config.max_unique, Some(10));
+        assert_eq!(
+            settings.source("threads"),
+            Source::Env("FAST_WC_THREADS".into())
+        );
+        assert!(settings.set("bogus", "1", Source::Cli).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_on_error_follows_layer_precedence() -> Result<()> {
+        let policy = |file: &str, cli: (&str, &str)| -> Result<ErrorPolicy> {
+            let mut settings = Settings::new();
+            settings.load_table(&file.parse()?, &Source::File("fast-wc.toml".into()))?;
+            settings.set(cli.0, cli.1, Source::Cli)?;
+            let mut config = Config::default();
+            settings.apply(&mut config)?;
+            Ok(config.error_policy)
+        };
+        assert_eq!(
+            policy("fail_on_error = true", ("error_policy", "collect"))?,
+            ErrorPolicy::Collect
+        );
+        assert_eq!(
+            policy("error_policy = \"collect\"", ("fail_on_error", "true"))?,
+            ErrorPolicy::Abort
+        );
+        assert_eq!(
+            policy("error_policy = \"abort\"", ("fail_on_error", "false"))?,
+            ErrorPolicy::Skip
+        );
+        assert_eq!(
+            policy("error_policy = \"collect\"", ("fail_on_error", "false"))?,
+            ErrorPolicy::Collect
+        );
+        assert!(policy("", ("fail_on_error", "maybe")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_token_chars_round_trips() -> Result<()> {
+        let table = parse_token_chars("a-z_\\-")?;
+        assert_eq!(parse_token_chars(&format_token_chars(&table))?, table);
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_overrides_top_level() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("fast-wc.toml");
+        std::fs::write(
+            &path,
+            "threads = 2\nmax_unique = 5\n\n[profile.docs]\ntoken_chars = \"a-zA-Z-\"\nmax_unique = 50\n",
+        )?;
+
+        let mut settings = Settings::new();
+        settings.load_file(&path, Some("docs"))?;
+        let mut config = Config::default();
+        settings.apply(&mut config)?;
+
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.max_unique, Some(50));
+        assert!(config.token_chars.unwrap()[b'-' as usize]);
+        assert!(Settings::new().load_file(&path, Some("missing")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_hooks_replace_top_level() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("fast-wc.toml");
+        std::fs::write(
+            &path,
+            "[hooks]\npre = \"git fetch\"\n\n[profile.ci.hooks]\npost = [\"upload\"]\n",
+        )?;
+
+        let mut settings = Settings::new();
+        settings.load_file(&path, None)?;
+        assert_eq!(settings.hooks().pre, vec!["git fetch"]);
+
+        let mut settings = Settings::new();
+        settings.load_file(&path, Some("ci"))?;
+        assert!(settings.hooks().pre.is_empty());
+        assert_eq!(settings.hooks().post, vec!["upload"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_expansions_extend_top_level() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("fast-wc.toml");
+        std::fs::write(
+            &path,
+            "split_identifiers = true\n\n[expansions]\ncfg = \"config\"\nnum = \"number\"\n\n[profile.ci.expansions]\nnum = \"numeral\"\n",
+        )?;
+
+        let mut settings = Settings::new();
+        settings.load_file(&path, Some("ci"))?;
+        let mut config = Config::default();
+        settings.apply(&mut config)?;
+
+        assert!(config.split_identifiers);
+        assert_eq!(config.expansions["cfg"], "config");
+        assert_eq!(config.expansions["num"], "numeral");
+        Ok(())
+    }
+
+    #[test]
+    fn test_profile_sets_format_and_file_relative_paths() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let conf = dir.path().join("conf");
+        std::fs::create_dir(&conf)?;
+        std::fs::write(conf.join("stop.txt"), "the\n")?;
+        std::fs::write(conf.join("aliases.toml"), "uint32_t = [\"u32\"]\n")?;
+        let path = conf.join("fast-wc.toml");
+        std::fs::write(
+            &path,
+            "stopwords = \"stop.txt\"\n\n[profile.snap]\nformat = \"bin\"\naliases = \"aliases.toml\"\n",
+        )?;
+
+        let mut settings = Settings::new();
+        settings.load_file(&path, Some("snap"))?;
+        let mut config = Config::default();
+        settings.apply(&mut config)?;
+
+        assert_eq!(config.output_format, Some(OutputFormat::Bin));
+        assert_eq!(render(&config, "format"), "bin");
+        assert_eq!(config.stopwords, ["the"]);
+        assert_eq!(config.aliases["u32"], "uint32_t");
+
+        settings.set("format", "auto", Source::Cli)?;
+        settings.apply(&mut config)?;
+        assert_eq!(config.output_format, None);
+        settings.set("format", "pdf", Source::Cli)?;
+        assert!(settings.apply(&mut config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_list_keeps_glob_alternations() -> Result<()> {
+        assert_eq!(parse_list("c, h,,rs")?, vec!["c", "h", "rs"]);
+        assert_eq!(
+            parse_list("src/{a,b}/*.c,vendor/**")?,
+            vec!["src/{a,b}/*.c", "vendor/**"]
+        );
+        assert!(parse_list(" , ").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use ahash::AHashMap;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Name of the bucket holding the aggregate count of evicted words
+pub const OTHER_BUCKET: &str = "<other>";
+
+// Weighted SpaceSaving summary keeping at most `capacity` heavy hitters.
+// Each entry tracks an overestimated count and the maximum error in it.
+#[derive(Debug, Clone)]
+pub struct SpaceSaving {
+    capacity: usize,
+    entries: AHashMap<String, (u64, u64)>,
+    by_count: BTreeSet<(u64, String)>,
+    total: u64,
+}
+
+impl SpaceSaving {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: AHashMap::with_capacity(capacity),
+            by_count: BTreeSet::new(),
+            total: 0,
+        }
+    }
+
+    // Record `weight` occurrences of `word`
+    pub fn offer(&mut self, word: String, weight: u64) {
+        self.total = self.total.saturating_add(weight);
+
+        if let Some((count, _)) = self.entries.get_mut(&word) {
+            self.by_count.remove(&(*count, word.clone()));
+            *count = count.saturating_add(weight);
+            self.by_count.insert((*count, word));
+            return;
+        }
+
+        if self.entries.len() < self.capacity {
+            self.by_count.insert((weight, word.clone()));
+            self.entries.insert(word, (weight, 0));
+            return;
+        }
+
+        // Evict the current minimum; the newcomer inherits its count as error
+        let (min_count, min_word) = self.by_count.pop_first().unwrap();
+        self.entries.remove(&min_word);
+        let count = min_count.saturating_add(weight);
+        self.by_count.insert((count, word.clone()));
+        self.entries.insert(word, (count, min_count));
+    }
+
+    // Total weight offered so far
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    // Kept words with guaranteed lower-bound counts, plus an `<other>` bucket
+    // holding everything else
+    pub fn into_counts(self) -> AHashMap<String, u64> {
+        let mut counts: AHashMap<String, u64> = self
+            .entries
+            .into_iter()
+            .map(|(word, (count, error))| (word, count - error))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+
+        let kept: u64 = counts.values().fold(0u64, |acc, c| acc.saturating_add(*c));
+        let other = self.total.saturating_sub(kept);
+        if other > 0 {
+            counts.insert(OTHER_BUCKET.to_string(), other);
+        }
+        counts
+    }
+}
+
+// Registers in a `UniqueEstimate` are indexed by the top bits of the hash;
+// 2^12 registers give a standard error of about 1.6%
+const HLL_PRECISION: u32 = 12;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+// HyperLogLog estimate of the number of distinct words, shared by the
+// workers of a run. Registers only ever grow, so offers from any thread and
+// merges of other estimates commute and can be read at any time.
+#[derive(Debug)]
+pub struct UniqueEstimate {
+    registers: Box<[AtomicU8]>,
+}
+
+impl Default for UniqueEstimate {
+    fn default() -> Self {
+        Self {
+            registers: (0..HLL_REGISTERS).map(|_| AtomicU8::new(0)).collect(),
+        }
+    }
+}
+
+impl UniqueEstimate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record one occurrence of `word`; repeats leave the estimate unchanged
+    pub fn offer(&self, word: &str) {
+        let hash = xxhash_rust::xxh3::xxh3_64(word.as_bytes());
+        let index = (hash >> (64 - HLL_PRECISION)) as usize;
+        // Position of the first set bit in the remaining bits, 1-based
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[index].fetch_max(rank as u8, Ordering::Relaxed);
+    }
+
+    // Fold `other` into this estimate, as if its words had been offered here
+    pub fn merge(&self, other: &UniqueEstimate) {
+        for (mine, theirs) in self.registers.iter().zip(other.registers.iter()) {
+            mine.fetch_max(theirs.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    // Estimated number of distinct words offered so far
+    pub fn estimate(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for register in self.registers.iter() {
+            let rank = register.load(Ordering::Relaxed);
+            sum += 2f64.powi(-(rank as i32));
+            zeros += (rank == 0) as usize;
+        }
+        let raw = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+        // Small cardinalities are better served by linear counting
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space_saving_keeps_heavy_hitters() {
+        let mut sketch = SpaceSaving::new(2);
+        sketch.offer("a".into(), 100);
+        sketch.offer("b".into(), 50);
+        sketch.offer("c".into(), 1);
+        sketch.offer("d".into(), 1);
+
+        let counts = sketch.into_counts();
+
+        assert_eq!(counts.get("a"), Some(&100));
+        assert_eq!(counts.values().sum::<u64>(), 152);
+        assert!(counts.contains_key(OTHER_BUCKET));
+        assert!(counts.len() <= 3);
+    }
+
+    #[test]
+    fn test_unique_estimate_is_close_and_mergeable() {
+        let whole = UniqueEstimate::new();
+        assert_eq!(whole.estimate(), 0);
+
+        let (left, right) = (UniqueEstimate::new(), UniqueEstimate::new());
+        for i in 0..20_000 {
+            let word = format!("word{}", i);
+            whole.offer(&word);
+            whole.offer(&word);
+            if i % 2 == 0 { &left } else { &right }.offer(&word);
+        }
+        let estimate = whole.estimate() as f64;
+        assert!(
+            (estimate - 20_000.0).abs() / 20_000.0 < 0.05,
+            "{}",
+            estimate
+        );
+
+        // Merging the halves gives exactly the estimate of the whole
+        left.merge(&right);
+        assert_eq!(left.estimate(), whole.estimate());
+
+        let few = UniqueEstimate::new();
+        for word in ["a", "b", "c", "a"] {
+            few.offer(word);
+        }
+        assert_eq!(few.estimate(), 3);
+    }
+}
@@ -0,0 +1,235 @@
+use ahash::AHashMap;
+use crossbeam::channel::{Receiver, Sender, unbounded};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+
+// Change notifications published by `SnapshotStore`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CountEvent {
+    WordDelta { word: String, old: u64, new: u64 },
+    FileReindexed { path: PathBuf, version: u64 },
+    FileRemoved { path: PathBuf, version: u64 },
+}
+
+// A consistent point-in-time view of word counts. Cloning is cheap: the
+// map is shared until the next update touches it.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub version: u64,
+    pub counts: Arc<AHashMap<String, u64>>,
+}
+
+impl Snapshot {
+    pub fn get(&self, word: &str) -> Option<u64> {
+        self.counts.get(word).copied()
+    }
+
+    // Counts sorted by count (descending) then alphabetically
+    pub fn sorted(&self) -> Vec<(String, u64)> {
+        let mut pairs: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(word, count)| (word.clone(), *count))
+            .collect();
+        pairs.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        pairs
+    }
+
+    // The `n` most frequent words, borrowed from the snapshot
+    pub fn top_n(&self, n: usize) -> Vec<(&str, u64)> {
+        let mut pairs: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+            .collect();
+        pairs.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        pairs.truncate(n);
+        pairs
+    }
+}
+
+// Versioned holder of the latest snapshot. Readers take an `Arc` and keep a
+// stable view while writers publish new versions; the map is copied on
+// write only if a reader still holds the previous version.
+#[derive(Debug, Default)]
+pub struct SnapshotStore {
+    current: RwLock<Arc<Snapshot>>,
+    subscribers: Mutex<Vec<Sender<CountEvent>>>,
+}
+
+impl SnapshotStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_counts(counts: AHashMap<String, u64>) -> Self {
+        Self {
+            current: RwLock::new(Arc::new(Snapshot {
+                version: 1,
+                counts: Arc::new(counts),
+            })),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    // The latest published snapshot
+    pub fn current(&self) -> Arc<Snapshot> {
+        Arc::clone(&self.current.read().unwrap())
+    }
+
+    pub fn version(&self) -> u64 {
+        self.current.read().unwrap().version
+    }
+
+    // Apply `update` to the counts and publish the result as a new version,
+    // returning that version
+    pub fn update<F>(&self, update: F) -> u64
+    where
+        F: FnOnce(&mut AHashMap<String, u64>),
+    {
+        let mut guard = self.current.write().unwrap();
+        let previous = std::mem::take(&mut *guard);
+        let (version, mut counts) = match Arc::try_unwrap(previous) {
+            Ok(snapshot) => (snapshot.version, snapshot.counts),
+            Err(shared) => (shared.version, Arc::clone(&shared.counts)),
+        };
+
+        update(Arc::make_mut(&mut counts));
+
+        let version = version + 1;
+        *guard = Arc::new(Snapshot { version, counts });
+        version
+    }
+
+    // Receive every event published after this call
+    pub fn subscribe(&self) -> Receiver<CountEvent> {
+        let (tx, rx) = unbounded();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    // Replace one file's contribution (`old`) with its recount (`new`),
+    // publishing a `WordDelta` per changed word and then `FileReindexed`
+    pub fn reindex_file(
+        &self,
+        path: &Path,
+        old: &AHashMap<String, u64>,
+        new: &AHashMap<String, u64>,
+    ) -> u64 {
+        let mut events = Vec::new();
+        let version = self.update(|counts| apply_delta(counts, old, new, &mut events));
+        events.push(CountEvent::FileReindexed {
+            path: path.to_path_buf(),
+            version,
+        });
+        self.publish(events);
+        version
+    }
+
+    // Remove one file's contribution, publishing deltas and `FileRemoved`
+    pub fn remove_file(&self, path: &Path, old: &AHashMap<String, u64>) -> u64 {
+        let mut events = Vec::new();
+        let empty = AHashMap::new();
+        let version = self.update(|counts| apply_delta(counts, old, &empty, &mut events));
+        events.push(CountEvent::FileRemoved {
+            path: path.to_path_buf(),
+            version,
+        });
+        self.publish(events);
+        version
+    }
+
+    // Send events to all live subscribers, dropping disconnected ones
+    fn publish(&self, events: Vec<CountEvent>) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|tx| events.iter().all(|event| tx.send(event.clone()).is_ok()));
+    }
+}
+
+// Subtract `old` and add `new` into `counts`, recording each changed word
+fn apply_delta(
+    counts: &mut AHashMap<String, u64>,
+    old: &AHashMap<String, u64>,
+    new: &AHashMap<String, u64>,
+    events: &mut Vec<CountEvent>,
+) {
+    let words: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    for word in words {
+        let removed = old.get(word).copied().unwrap_or(0);
+        let added = new.get(word).copied().unwrap_or(0);
+        if removed == added {
+            continue;
+        }
+
+        let before = counts.get(word.as_str()).copied().unwrap_or(0);
+        let after = before.saturating_sub(removed).saturating_add(added);
+        if after == 0 {
+            counts.remove(word.as_str());
+        } else {
+            counts.insert(word.clone(), after);
+        }
+        events.push(CountEvent::WordDelta {
+            word: word.clone(),
+            old: before,
+            new: after,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readers_keep_consistent_view() {
+        let store = SnapshotStore::from_counts(AHashMap::from_iter([("a".to_string(), 1)]));
+        let before = store.current();
+
+        let version = store.update(|counts| {
+            *counts.entry("a".to_string()).or_insert(0) += 4;
+            counts.insert("b".to_string(), 2);
+        });
+
+        assert_eq!(version, 2);
+        assert_eq!(before.get("a"), Some(1));
+        assert_eq!(before.get("b"), None);
+        assert_eq!(store.current().get("a"), Some(5));
+        assert_eq!(store.current().sorted()[0], ("a".to_string(), 5));
+    }
+
+    #[test]
+    fn test_reindex_emits_deltas() {
+        let store = SnapshotStore::from_counts(AHashMap::from_iter([
+            ("a".to_string(), 3),
+            ("b".to_string(), 1),
+        ]));
+        let events = store.subscribe();
+
+        let old = AHashMap::from_iter([("a".to_string(), 1), ("b".to_string(), 1)]);
+        let new = AHashMap::from_iter([("a".to_string(), 2)]);
+        store.reindex_file(Path::new("x.c"), &old, &new);
+
+        let received: Vec<CountEvent> = events.try_iter().collect();
+        assert_eq!(
+            received,
+            vec![
+                CountEvent::WordDelta {
+                    word: "a".into(),
+                    old: 3,
+                    new: 4
+                },
+                CountEvent::WordDelta {
+                    word: "b".into(),
+                    old: 1,
+                    new: 0
+                },
+                CountEvent::FileReindexed {
+                    path: PathBuf::from("x.c"),
+                    version: 2
+                },
+            ]
+        );
+        assert_eq!(store.current().get("b"), None);
+    }
+}
@@ -0,0 +1,121 @@
+// Compact binary word counts for `--output counts.bin` and `--merge-with`,
+// so counts from separate machines or runs can be merged later. The file
+// starts with an artifact header line; then come the number of entries and
+// each entry's word length, word bytes and count, all integers as unsigned
+// LEB128 varints (most counts fit in one or two bytes).
+use crate::artifact::{self, SNAPSHOT, SNAPSHOT_VERSION};
+use crate::output::AtomicFile;
+use anyhow::{Context, Result, bail};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+// Extension that selects the snapshot format for `--output`
+pub const SNAPSHOT_EXTENSION: &str = "bin";
+
+// Write `results` to `path` atomically (like `--output`)
+pub fn save(path: &Path, results: &[(String, u64)], fsync: bool) -> Result<()> {
+    let mut file = AtomicFile::create(path, fsync)
+        .with_context(|| format!("Failed to create snapshot {}", path.display()))?;
+    let mut writer = io::BufWriter::new(&mut file);
+    writeln!(writer, "{}", artifact::header(SNAPSHOT, SNAPSHOT_VERSION))?;
+    write_varint(&mut writer, results.len() as u64)?;
+    for (word, count) in results {
+        write_varint(&mut writer, word.len() as u64)?;
+        writer.write_all(word.as_bytes())?;
+        write_varint(&mut writer, *count)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    file.commit()
+        .with_context(|| format!("Failed to write snapshot {}", path.display()))
+}
+
+// Read the word counts saved at `path`
+pub fn load(path: &Path) -> Result<Vec<(String, u64)>> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open snapshot {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .with_context(|| format!("{} is not a fast-wc-rust snapshot", path.display()))?;
+    if artifact::check_header(path, &line, SNAPSHOT, SNAPSHOT_VERSION)? == 0 {
+        bail!("{} is not a fast-wc-rust snapshot", path.display());
+    }
+    read_entries(&mut reader).with_context(|| format!("Corrupt snapshot {}", path.display()))
+}
+
+fn read_entries<R: Read>(reader: &mut R) -> io::Result<Vec<(String, u64)>> {
+    let len = read_varint(reader)?;
+    // Cap the reservation so a corrupt length cannot exhaust memory up front
+    let mut entries = Vec::with_capacity(len.min(1 << 20) as usize);
+    for _ in 0..len {
+        let mut word = vec![0u8; read_varint(reader)? as usize];
+        reader.read_exact(&mut word)?;
+        let word =
+            String::from_utf8(word).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        entries.push((word, read_varint(reader)?));
+    }
+    Ok(entries)
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint longer than 64 bits",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, FastWordCounter};
+
+    #[test]
+    fn test_snapshots_round_trip_and_merge() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let first = dir.path().join("a.bin");
+        let second = dir.path().join("b.bin");
+        let words = |pairs: &[(&str, u64)]| -> Vec<(String, u64)> {
+            pairs.iter().map(|(w, c)| (w.to_string(), *c)).collect()
+        };
+        save(&first, &words(&[("int", 300), ("max", u64::MAX)]), false)?;
+        save(&second, &words(&[("int", 2), ("größe", 1)]), false)?;
+        assert_eq!(load(&first)?, words(&[("int", 300), ("max", u64::MAX)]));
+
+        let counter = FastWordCounter::new(Config {
+            show_progress: false,
+            ..Config::default()
+        });
+        let merged = counter.merge_snapshots(words(&[("int", 1)]), &[first, second])?;
+        assert_eq!(
+            merged,
+            words(&[("max", u64::MAX), ("int", 303), ("größe", 1)])
+        );
+        assert_eq!(counter.unique_words(), 3);
+
+        std::fs::write(dir.path().join("c.bin"), "int | 3\n")?;
+        assert!(load(&dir.path().join("c.bin")).is_err());
+        Ok(())
+    }
+}
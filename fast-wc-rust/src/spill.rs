@@ -0,0 +1,172 @@
+use ahash::AHashMap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+
+// External sort for result sets too large to sort as one vector: counts are
+// cut into sorted runs of at most `run_len` words, spilled to temporary
+// files, and k-way merged straight into `write_row` in report order (count
+// descending, then word).
+pub fn write_sorted<F>(
+    counts: AHashMap<String, u64>,
+    run_len: usize,
+    mut write_row: F,
+) -> io::Result<()>
+where
+    F: FnMut(&str, u64) -> io::Result<()>,
+{
+    let run_len = run_len.max(1);
+    if counts.len() <= run_len {
+        let mut entries: Vec<_> = counts.into_iter().collect();
+        sort_run(&mut entries);
+        for (word, count) in &entries {
+            write_row(word, *count)?;
+        }
+        return Ok(());
+    }
+
+    let dir = SpillDir::create()?;
+    let mut runs = Vec::new();
+    let mut run = Vec::with_capacity(run_len);
+    for entry in counts {
+        run.push(entry);
+        if run.len() == run_len {
+            runs.push(dir.spill(runs.len(), &mut run)?);
+        }
+    }
+    if !run.is_empty() {
+        runs.push(dir.spill(runs.len(), &mut run)?);
+    }
+
+    let mut readers = runs
+        .iter()
+        .map(|path| File::open(path).map(BufReader::new))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some((word, count)) = read_entry(reader)? {
+            heap.push(Head {
+                count,
+                word,
+                run: i,
+            });
+        }
+    }
+    while let Some(Head { count, word, run }) = heap.pop() {
+        write_row(&word, count)?;
+        if let Some((word, count)) = read_entry(&mut readers[run])? {
+            heap.push(Head { count, word, run });
+        }
+    }
+    Ok(())
+}
+
+fn sort_run(entries: &mut [(String, u64)]) {
+    entries.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+}
+
+// Write one entry: count (u64 LE), word length (u32 LE), word bytes
+pub(crate) fn write_entry<W: Write>(writer: &mut W, word: &str, count: u64) -> io::Result<()> {
+    writer.write_all(&count.to_le_bytes())?;
+    writer.write_all(&(word.len() as u32).to_le_bytes())?;
+    writer.write_all(word.as_bytes())
+}
+
+// Next entry written by `write_entry`, or None at end of input
+pub(crate) fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<(String, u64)>> {
+    let mut count = [0u8; 8];
+    match reader.read_exact(&mut count) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut word = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut word)?;
+    let word =
+        String::from_utf8(word).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(Some((word, u64::from_le_bytes(count))))
+}
+
+// Smallest run head in report order sits at the top of the max-heap
+#[derive(PartialEq, Eq)]
+struct Head {
+    count: u64,
+    word: String,
+    run: usize,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.count, Reverse(&self.word)).cmp(&(other.count, Reverse(&other.word)))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Temporary directory for run files, removed on drop
+struct SpillDir {
+    path: PathBuf,
+}
+
+impl SpillDir {
+    fn create() -> io::Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "fast-wc-spill-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    // Sort `run`, write it to a run file and clear it
+    fn spill(&self, index: usize, run: &mut Vec<(String, u64)>) -> io::Result<PathBuf> {
+        sort_run(run);
+        let path = self.path.join(format!("run-{}", index));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        for (word, count) in run.drain(..) {
+            write_entry(&mut writer, &word, count)?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
+
+impl Drop for SpillDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spilled_output_matches_in_memory_sort() -> io::Result<()> {
+        let counts: AHashMap<String, u64> =
+            (0..500u64).map(|i| (format!("w{}", i), i % 17)).collect();
+        let mut expected: Vec<_> = counts.clone().into_iter().collect();
+        sort_run(&mut expected);
+
+        for run_len in [7, 500] {
+            let mut rows = Vec::new();
+            write_sorted(counts.clone(), run_len, |word, count| {
+                rows.push((word.to_string(), count));
+                Ok(())
+            })?;
+            assert_eq!(rows, expected);
+        }
+        Ok(())
+    }
+}
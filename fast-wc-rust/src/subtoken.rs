@@ -0,0 +1,46 @@
+// Splitting identifiers into their sub-tokens: `parse_http_header`,
+// `parseHttpHeader` and `PARSE_HTTP_HEADER` all split into parse/http/header
+// (with their original case). Words break at underscores, at a lowercase
+// letter or digit followed by an uppercase one, and before the last capital
+// of an acronym followed by lowercase (`HTTPServer` -> HTTP, Server).
+// Digits stay with the part they follow (`utf8Decode` -> utf8, Decode).
+
+// The sub-tokens of `word`, without the underscores between them
+pub fn split(word: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|piece| !piece.is_empty()) {
+        let chars: Vec<(usize, char)> = piece.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (at, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+            let boundary = c.is_uppercase()
+                && ((prev.is_lowercase() || prev.is_numeric())
+                    || (prev.is_uppercase() && next_lower));
+            if boundary {
+                parts.push(&piece[start..at]);
+                start = at;
+            }
+        }
+        parts.push(&piece[start..]);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("parse_http_header"), ["parse", "http", "header"]);
+        assert_eq!(split("parseHttpHeader"), ["parse", "Http", "Header"]);
+        assert_eq!(split("PARSE_HTTP_HEADER"), ["PARSE", "HTTP", "HEADER"]);
+        assert_eq!(split("HTTPServer"), ["HTTP", "Server"]);
+        assert_eq!(split("utf8Decode"), ["utf8", "Decode"]);
+        assert_eq!(split("__init__"), ["init"]);
+        assert_eq!(split("größeMax"), ["größe", "Max"]);
+        assert!(split("_").is_empty());
+    }
+}
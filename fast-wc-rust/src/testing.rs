@@ -0,0 +1,53 @@
+// Benchmark and test fixtures. Seeded corpora live in `corpus`, which the
+// gen-corpus subcommand also uses; they are re-exported here alongside the
+// benchmarks' original fixed-vocabulary files.
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub use crate::corpus::{CorpusSpec, SizeDistribution, word};
+
+// The benchmarks' original fixture: `num_files` C files of `file_size`
+// bytes cycling through a fixed list of 34 words
+pub fn create_test_files(dir: &Path, num_files: usize, file_size: usize) -> Vec<PathBuf> {
+    let words = [
+        "int", "main", "void", "return", "printf", "char", "const", "struct", "typedef", "static",
+        "extern", "inline", "register", "volatile", "sizeof", "malloc", "free", "memcpy", "strlen",
+        "strcpy", "strcmp", "file", "buffer", "pointer", "array", "function", "variable", "hello",
+        "world", "test", "example", "sample", "data", "string",
+    ];
+
+    let mut file_paths = Vec::new();
+
+    for i in 0..num_files {
+        let file_path = dir.join(format!("test_{}.c", i));
+
+        let mut content = String::with_capacity(file_size);
+        let mut size = 0;
+
+        while size < file_size {
+            for word in &words {
+                content.push_str(word);
+                content.push(' ');
+                size += word.len() + 1;
+
+                if size >= file_size {
+                    break;
+                }
+
+                if size % 100 == 0 {
+                    content.push('\n');
+                    size += 1;
+                }
+                if size % 50 == 0 {
+                    content.push_str("();");
+                    size += 3;
+                }
+            }
+        }
+
+        fs::write(&file_path, content).unwrap();
+        file_paths.push(file_path);
+    }
+
+    file_paths
+}
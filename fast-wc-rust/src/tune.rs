@@ -0,0 +1,127 @@
+use crate::Config;
+use std::fmt;
+use std::path::PathBuf;
+
+// Files at or above this median size are memory-mapped; smaller ones are
+// cheaper to read than to map
+const MMAP_MEDIAN_BYTES: u64 = 16 * 1024;
+// Flush partial maps early once there are this many files per thread
+const FLUSH_FILES_PER_THREAD: usize = 64;
+// Cap on a worker's partial map when the input is large relative to memory
+const FLUSH_ENTRIES_LOW_MEMORY: usize = 1 << 20;
+
+// Settings chosen by `Config::auto_tune` and the inputs they were based on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tuning {
+    pub files: usize,
+    pub total_bytes: u64,
+    pub median_bytes: u64,
+    pub cores: usize,
+    pub available_memory: Option<u64>,
+    pub threads: usize,
+    pub use_mmap: bool,
+    pub parallel_merge: bool,
+    pub flush_every_files: Option<usize>,
+    pub flush_every_entries: Option<usize>,
+}
+
+impl Tuning {
+    // Choose settings for files of the given sizes
+    pub fn choose(sizes: &[u64], cores: usize, available_memory: Option<u64>) -> Self {
+        let mut sorted = sizes.to_vec();
+        sorted.sort_unstable();
+        let median_bytes = sorted.get(sorted.len() / 2).copied().unwrap_or(0);
+        let total_bytes: u64 = sorted.iter().sum();
+
+        let threads = cores.min(sizes.len()).max(1);
+        let flush_every_files =
+            (sizes.len() >= threads * FLUSH_FILES_PER_THREAD).then(|| sizes.len() / (threads * 8));
+        let low_memory = available_memory.is_some_and(|avail| total_bytes > avail / 4);
+
+        Self {
+            files: sizes.len(),
+            total_bytes,
+            median_bytes,
+            cores,
+            available_memory,
+            threads,
+            use_mmap: median_bytes >= MMAP_MEDIAN_BYTES,
+            // The partitioned merge only kicks in with more than two maps
+            parallel_merge: threads > 2,
+            flush_every_files,
+            flush_every_entries: low_memory.then_some(FLUSH_ENTRIES_LOW_MEMORY),
+        }
+    }
+
+    // Inspect the discovered files and the machine
+    pub fn for_files(files: &[PathBuf]) -> Self {
+        let sizes: Vec<u64> = files
+            .iter()
+            .map(|f| std::fs::metadata(f).map_or(0, |m| m.len()))
+            .collect();
+        Self::choose(&sizes, num_cpus::get(), available_memory())
+    }
+
+    pub fn apply(&self, config: &mut Config) {
+        config.num_threads = self.threads;
+        config.use_mmap = self.use_mmap;
+        config.parallel_merge = self.parallel_merge;
+        config.flush_every_files = self.flush_every_files;
+        config.flush_every_entries = self.flush_every_entries;
+    }
+}
+
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} threads, mmap: {}, parallel merge: {}",
+            self.threads, self.use_mmap, self.parallel_merge
+        )?;
+        if let Some(n) = self.flush_every_files {
+            write!(f, ", flush every {} files", n)?;
+        }
+        if let Some(n) = self.flush_every_entries {
+            write!(f, ", flush at {} words", n)?;
+        }
+        write!(
+            f,
+            " ({} files, {} bytes, median {} bytes, {} cores)",
+            self.files, self.total_bytes, self.median_bytes, self.cores
+        )
+    }
+}
+
+// Available memory in bytes, where the platform reports it
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_choose_small_and_large_inputs() {
+        let small = Tuning::choose(&[100, 200, 300], 8, None);
+        assert_eq!(small.threads, 3);
+        assert!(!small.use_mmap);
+        assert!(small.parallel_merge);
+        assert_eq!(small.flush_every_files, None);
+
+        let large = Tuning::choose(&vec![1 << 20; 1000], 4, Some(1 << 20));
+        assert_eq!(large.threads, 4);
+        assert!(large.use_mmap);
+        assert_eq!(large.flush_every_files, Some(1000 / 32));
+        assert_eq!(large.flush_every_entries, Some(FLUSH_ENTRIES_LOW_MEMORY));
+    }
+}
@@ -0,0 +1,309 @@
+// Incremental recounting for `--watch`. Every matching file's counts are
+// kept, so a change is applied as a delta: the file's old counts are
+// subtracted from the totals and its new counts added, without rereading
+// anything else.
+use crate::discovery::{GlobFilter, has_extension, within_walk};
+use crate::session::add_counts;
+use crate::{Config, FastWordCounter, input};
+use ahash::{AHashMap, AHashSet};
+use anyhow::{Context, Result};
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rayon::prelude::*;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Editors write a file several times per save; changes arriving within this
+// window of each other are applied as one batch
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Word totals for one directory, with the per-file counts they are made of
+pub struct IncrementalCounts {
+    counter: FastWordCounter,
+    root: PathBuf,
+    globs: GlobFilter,
+    files: AHashMap<PathBuf, AHashMap<String, u64>>,
+    totals: AHashMap<String, u64>,
+}
+
+impl IncrementalCounts {
+    // Count every file under `root` once
+    pub fn new(config: Config, root: &Path) -> Result<Self> {
+        let globs = GlobFilter::new(&config)?;
+        let counter = FastWordCounter::new(config);
+        let files: Vec<(PathBuf, AHashMap<String, u64>)> = counter
+            .discover_files(root)?
+            .into_par_iter()
+            .filter_map(|file| count_file(&counter, &file).map(|counts| (file, counts)))
+            .collect();
+        let files: AHashMap<PathBuf, AHashMap<String, u64>> = files.into_iter().collect();
+
+        let mut totals = AHashMap::new();
+        for counts in files.values() {
+            add_counts(&mut totals, counts, &counter);
+        }
+        Ok(Self {
+            counter,
+            root: root.to_path_buf(),
+            globs,
+            files,
+            totals,
+        })
+    }
+
+    // Recount `path` after it was created, modified or removed, returning
+    // whether the totals changed. A removed directory drops every file
+    // under it.
+    pub fn update(&mut self, path: &Path) -> bool {
+        if !path.exists() {
+            let removed: Vec<PathBuf> = self
+                .files
+                .keys()
+                .filter(|file| file.starts_with(path))
+                .cloned()
+                .collect();
+            for file in &removed {
+                self.replace(file, None);
+            }
+            return !removed.is_empty();
+        }
+
+        let counts = self
+            .tracks(path)
+            .then(|| count_file(&self.counter, path))
+            .flatten();
+        if self.files.get(path) == counts.as_ref() {
+            return false;
+        }
+        self.replace(path, counts);
+        true
+    }
+
+    // Files whose counts make up the totals
+    pub fn files(&self) -> usize {
+        self.files.len()
+    }
+
+    // Current totals, sorted and limited like a normal run's results
+    pub fn results(&self) -> Vec<(String, u64)> {
+        self.counter
+            .select_results(self.totals.clone(), self.counter.config.top)
+    }
+
+    // The counter whose stats and diagnostics accumulate across updates
+    pub fn counter(&self) -> &FastWordCounter {
+        &self.counter
+    }
+
+    // Whether a changed `path` is one discovery would have picked up
+    fn tracks(&self, path: &Path) -> bool {
+        within_walk(path, &self.root, &self.counter.config)
+            && has_extension(path, &self.counter.config)
+            && self.globs.matches(path, &self.root)
+    }
+
+    // Swap `path`'s counts for `counts`, applying the difference to the totals
+    fn replace(&mut self, path: &Path, counts: Option<AHashMap<String, u64>>) {
+        if let Some(old) = self.files.remove(path) {
+            for (word, count) in old {
+                if let Some(total) = self.totals.get_mut(&word) {
+                    // Saturated totals stay clamped rather than exact
+                    *total = total.saturating_sub(count);
+                    if *total == 0 {
+                        self.totals.remove(&word);
+                    }
+                }
+            }
+        }
+        if let Some(counts) = counts {
+            add_counts(&mut self.totals, &counts, &self.counter);
+            self.files.insert(path.to_path_buf(), counts);
+        }
+    }
+}
+
+// Tokenize one file, recording failures on `counter`; binary files (with
+// `skip_binary`) and unreadable files contribute nothing
+fn count_file(counter: &FastWordCounter, path: &Path) -> Option<AHashMap<String, u64>> {
+    let data = match input::load(path, &counter.config) {
+        Ok(data) => data,
+        Err(e) => {
+            counter.record_error(e);
+            return None;
+        }
+    };
+    if counter.skips_binary(path, &data) {
+        return None;
+    }
+    let mut counts = AHashMap::new();
+    let extracted = counter.extract_words(&data, &mut counts);
+    counter.record_file_stats(path, &data, &extracted);
+    counter
+        .stats
+        .files_processed
+        .fetch_add(1, Ordering::Relaxed);
+    counter
+        .stats
+        .bytes_processed
+        .fetch_add(data.len() as u64, Ordering::Relaxed);
+    Some(counts)
+}
+
+// Count `root`, then watch it for changes until the watcher stops, calling
+// `on_update` after the initial count and after every batch of changes that
+// alters the totals
+pub fn watch<F: FnMut(&IncrementalCounts)>(
+    config: Config,
+    root: &Path,
+    mut on_update: F,
+) -> Result<()> {
+    // Notifications carry absolute paths
+    let root = std::fs::canonicalize(root)
+        .with_context(|| format!("Failed to resolve {}", root.display()))?;
+    let mut counts = IncrementalCounts::new(config, &root)?;
+    on_update(&counts);
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", root.display()))?;
+
+    while let Ok(event) = rx.recv() {
+        let mut changed = AHashSet::new();
+        collect_paths(event, &mut changed, &counts);
+        while let Ok(event) = rx.recv_timeout(WATCH_DEBOUNCE) {
+            collect_paths(event, &mut changed, &counts);
+        }
+
+        let mut updated = false;
+        for path in changed {
+            updated |= counts.update(&path);
+        }
+        if updated {
+            on_update(&counts);
+        }
+    }
+    Ok(())
+}
+
+// Add the paths an event touched to `changed`. Reads (including the
+// recount's own) are ignored; a finished write is not.
+fn collect_paths(
+    event: notify::Result<Event>,
+    changed: &mut AHashSet<PathBuf>,
+    counts: &IncrementalCounts,
+) {
+    match event {
+        Ok(event) => match event.kind {
+            EventKind::Access(AccessKind::Close(AccessMode::Write)) => changed.extend(event.paths),
+            EventKind::Access(_) => {}
+            _ => changed.extend(event.paths),
+        },
+        Err(e) => counts.counter.logger().warn(
+            "watch_error",
+            &format!("warning: file watcher: {}", e),
+            &[("error", json!(e.to_string()))],
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet() -> Config {
+        Config {
+            show_progress: false,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_updates_apply_per_file_deltas() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let root = dir.path();
+        std::fs::write(root.join("a.c"), "int a; int b;")?;
+        std::fs::create_dir(root.join("sub"))?;
+        std::fs::write(root.join("sub/b.c"), "int b;")?;
+
+        let mut counts = IncrementalCounts::new(quiet(), root)?;
+        assert_eq!(counts.files(), 2);
+        assert_eq!(counts.results()[0], ("int".to_string(), 3));
+
+        // Modified: only the difference moves
+        std::fs::write(root.join("a.c"), "int a;")?;
+        assert!(counts.update(&root.join("a.c")));
+        assert_eq!(
+            counts.results(),
+            [
+                ("int".to_string(), 2),
+                ("a".to_string(), 1),
+                ("b".to_string(), 1)
+            ]
+        );
+        assert!(!counts.update(&root.join("a.c")));
+
+        // New files count only if discovery would have found them
+        std::fs::write(root.join("c.c"), "char c;")?;
+        std::fs::write(root.join("notes.txt"), "char c;")?;
+        assert!(counts.update(&root.join("c.c")));
+        assert!(!counts.update(&root.join("notes.txt")));
+        assert_eq!(counts.files(), 3);
+
+        // Removing a directory drops the files under it
+        std::fs::remove_dir_all(root.join("sub"))?;
+        assert!(counts.update(&root.join("sub")));
+        assert_eq!(counts.files(), 2);
+        assert_eq!(
+            counts.results(),
+            [
+                ("a".to_string(), 1),
+                ("c".to_string(), 1),
+                ("char".to_string(), 1),
+                ("int".to_string(), 1)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_updates_honour_depth_and_symlinks() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("sub/deep"))?;
+        std::fs::write(root.join("sub/b.c"), "int b;")?;
+        std::fs::write(root.join("sub/deep/c.c"), "int c;")?;
+
+        let mut counts = IncrementalCounts::new(
+            Config {
+                max_depth: Some(2),
+                ..quiet()
+            },
+            root,
+        )?;
+        assert_eq!(counts.files(), 1);
+        std::fs::write(root.join("sub/deep/c.c"), "int c; int d;")?;
+        assert!(!counts.update(&root.join("sub/deep/c.c")));
+
+        // Links are only walked with follow_symlinks
+        std::os::unix::fs::symlink(root.join("sub/b.c"), root.join("link.c"))?;
+        std::os::unix::fs::symlink(root.join("sub"), root.join("linked"))?;
+        for follow_symlinks in [false, true] {
+            let mut counts = IncrementalCounts::new(
+                Config {
+                    follow_symlinks,
+                    ..quiet()
+                },
+                root,
+            )?;
+            std::fs::write(root.join("sub/b.c"), format!("int b{};", follow_symlinks))?;
+            assert_eq!(counts.update(&root.join("link.c")), follow_symlinks);
+            assert_eq!(counts.update(&root.join("linked/b.c")), follow_symlinks);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+// Newline, word and byte counts, as reported by coreutils `wc`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WcCounts {
+    pub lines: u64,
+    pub words: u64,
+    pub bytes: u64,
+}
+
+impl WcCounts {
+    // Count `data` in one pass. Lines are newline characters (a trailing
+    // partial line is not counted, as in `wc`); words are maximal runs of
+    // non-whitespace bytes, as `wc` splits them in the C locale.
+    pub fn from_bytes(data: &[u8]) -> Self {
+        let mut counts = WcCounts {
+            bytes: data.len() as u64,
+            ..WcCounts::default()
+        };
+        let mut in_word = false;
+        for &byte in data {
+            if byte == b'\n' {
+                counts.lines += 1;
+            }
+            let space = matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c');
+            if !space && !in_word {
+                counts.words += 1;
+            }
+            in_word = !space;
+        }
+        counts
+    }
+
+    pub fn add(&mut self, other: &WcCounts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.bytes += other.bytes;
+    }
+}
+
+// `wc` counts for every file, sorted by path, and their total
+#[derive(Debug, Clone, Default)]
+pub struct WcReport {
+    pub files: Vec<(PathBuf, WcCounts)>,
+    pub total: WcCounts,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_match_wc() {
+        assert_eq!(
+            WcCounts::from_bytes(b"int main(void)\n{\treturn 0; }\nno newline"),
+            WcCounts {
+                lines: 2,
+                words: 8,
+                bytes: 39,
+            }
+        );
+        assert_eq!(WcCounts::from_bytes(b""), WcCounts::default());
+        assert_eq!(WcCounts::from_bytes(b"  \n\n").words, 0);
+    }
+}
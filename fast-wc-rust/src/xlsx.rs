@@ -0,0 +1,149 @@
+// Spreadsheet export for `--format xlsx`: a Results sheet with the totals,
+// a Summary sheet with the run's statistics, and one sheet per extension
+use crate::output::AtomicFile;
+use crate::{ARCHIVE_GROUP, ExtensionCounts, FastWordCounter};
+use anyhow::{Context, Result};
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+// Rows below a sheet's header; longer tables are cut off
+const MAX_DATA_ROWS: usize = 1_048_575;
+
+// Characters Excel does not allow in sheet names
+const SHEET_NAME_FORBIDDEN: [char; 7] = ['[', ']', ':', '*', '?', '/', '\\'];
+
+// Write `counts` and `counter`'s run statistics to a workbook at `path`
+// (atomically, like `--output`)
+pub fn write_workbook(
+    path: &Path,
+    counter: &FastWordCounter,
+    counts: &ExtensionCounts,
+    elapsed: Duration,
+    fsync: bool,
+) -> Result<()> {
+    let bytes = build_workbook(counter, counts, elapsed)
+        .with_context(|| format!("Failed to build spreadsheet {}", path.display()))?;
+    let mut file = AtomicFile::create(path, fsync)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(&bytes)?;
+    file.commit()
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+fn build_workbook(
+    counter: &FastWordCounter,
+    counts: &ExtensionCounts,
+    elapsed: Duration,
+) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold();
+
+    let results = workbook.add_worksheet().set_name("Results")?;
+    write_counts(results, &counts.totals, &header)?;
+
+    let progress = counter.progress();
+    let summary: [(&str, f64); 7] = [
+        ("Files matched", progress.files_total as f64),
+        ("Files processed", progress.files_processed as f64),
+        ("Bytes processed", progress.bytes_processed as f64),
+        ("Tokens counted", progress.tokens_processed as f64),
+        ("Unique words", counter.unique_words() as f64),
+        ("Files failed", counter.errors().len() as f64),
+        ("Elapsed seconds", elapsed.as_secs_f64()),
+    ];
+    let sheet = workbook.add_worksheet().set_name("Summary")?;
+    sheet.write_with_format(0, 0, "Statistic", &header)?;
+    sheet.write_with_format(0, 1, "Value", &header)?;
+    for (row, (name, value)) in (1..).zip(summary) {
+        sheet.write_string(row, 0, name)?;
+        sheet.write_number(row, 1, value)?;
+    }
+    sheet.set_column_width(0, 20)?;
+
+    for (ext, results) in &counts.by_extension {
+        let sheet = workbook.add_worksheet().set_name(sheet_name(ext))?;
+        write_counts(sheet, results, &header)?;
+    }
+    workbook.save_to_buffer()
+}
+
+// A word/count table under a frozen header row. Counts above 2^53 lose
+// precision, as Excel stores numbers as doubles.
+fn write_counts(
+    sheet: &mut Worksheet,
+    results: &[(String, u64)],
+    header: &Format,
+) -> Result<(), XlsxError> {
+    sheet.write_with_format(0, 0, "Word", header)?;
+    sheet.write_with_format(0, 1, "Count", header)?;
+    for (row, (word, count)) in (1..).zip(results.iter().take(MAX_DATA_ROWS)) {
+        sheet.write_string(row, 0, word)?;
+        sheet.write_number(row, 1, *count as f64)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.set_column_width(0, 32)?;
+    Ok(())
+}
+
+// Sheet for an extension, e.g. ".rs"; the dot keeps it clear of the
+// Results and Summary sheets
+fn sheet_name(ext: &str) -> String {
+    if ext == ARCHIVE_GROUP {
+        return "archives".to_string();
+    }
+    format!(".{}", ext)
+        .chars()
+        .map(|c| {
+            if SHEET_NAME_FORBIDDEN.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .take(31)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::io::Read;
+
+    #[test]
+    fn test_workbook_has_results_summary_and_extension_sheets() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(dir.path().join("a.c"), "int main;")?;
+        std::fs::write(dir.path().join("b.rs"), "fn main")?;
+        let counter = FastWordCounter::new(Config {
+            extensions: vec!["c".to_string(), "rs".to_string()],
+            show_progress: false,
+            print_results: false,
+            ..Config::default()
+        });
+        let counts = counter.count_directory_by_extension(dir.path())?;
+
+        let path = dir.path().join("counts.xlsx");
+        write_workbook(&path, &counter, &counts, Duration::from_millis(5), false)?;
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+        let mut workbook = String::new();
+        zip.by_name("xl/workbook.xml")?
+            .read_to_string(&mut workbook)?;
+        for sheet in ["Results", "Summary", ".c", ".rs"] {
+            assert!(
+                workbook.contains(&format!("name=\"{}\"", sheet)),
+                "{}",
+                sheet
+            );
+        }
+        let mut strings = String::new();
+        zip.by_name("xl/sharedStrings.xml")?
+            .read_to_string(&mut strings)?;
+        assert!(strings.contains("main") && strings.contains("Unique words"));
+        assert_eq!(sheet_name("a/b"), ".a_b");
+        Ok(())
+    }
+}
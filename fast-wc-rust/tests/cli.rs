@@ -0,0 +1,77 @@
+// End-to-end checks of the fast-wc-rust binary's output
+use std::process::Command;
+
+// Run the binary on a one-file tree, returning its stdout
+fn run(args: &[&str], env: &[(&str, &str)]) -> String {
+    let dir = tempfile::TempDir::new().unwrap();
+    std::fs::write(dir.path().join("a.c"), "int x = y;\nint z;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fast-wc-rust"))
+        .args(args)
+        .arg(dir.path())
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_print_results_false_keeps_progress() {
+    let row = "int |        2";
+
+    let full = run(&[], &[]);
+    assert!(full.contains("Processed 1 files"));
+    assert!(full.contains(row));
+
+    let progress_only = run(&[], &[("FAST_WC_PRINT_RESULTS", "false")]);
+    assert!(progress_only.contains("Processed 1 files"));
+    assert!(!progress_only.contains(row));
+
+    let results_only = run(&["-q"], &[]);
+    assert!(!results_only.contains("Processed"));
+    assert!(results_only.contains(row));
+
+    assert_eq!(run(&["-s"], &[]), "");
+}
+
+#[test]
+fn test_gen_corpus_is_seeded() {
+    // The generated files' names and contents
+    let generate = |seed: &str| {
+        let dir = tempfile::TempDir::new().unwrap();
+        let status = Command::new(env!("CARGO_BIN_EXE_fast-wc-rust"))
+            .args([
+                "gen-corpus",
+                "--files",
+                "4",
+                "--size",
+                "512",
+                "--seed",
+                seed,
+            ])
+            .arg(dir.path())
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let mut files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                (
+                    path.file_name().unwrap().to_owned(),
+                    std::fs::read(&path).unwrap(),
+                )
+            })
+            .collect();
+        files.sort();
+        files
+    };
+
+    let corpus = generate("7");
+    assert_eq!(corpus.len(), 4);
+    assert_eq!(corpus, generate("7"));
+    assert_ne!(corpus, generate("8"));
+}